@@ -0,0 +1,67 @@
+//! Measures how scenario.chk extraction scales with the number of threads.
+//!
+//! Uses the maps `cargo test` downloads into /tmp/artifacts, or the directory in `BWMPQ_CORPUS`.
+//! Run with `cargo bench --bench scaling`.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 20;
+
+fn run(corpus: &[Vec<u8>], threads: usize) -> Duration {
+    let next = AtomicUsize::new(0);
+    let total = corpus.len() * ROUNDS;
+
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= total {
+                    break;
+                }
+
+                let chk = bwmpq::get_chk_from_mpq_in_memory(&corpus[i % corpus.len()]).unwrap();
+                std::hint::black_box(chk);
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let dir = std::env::var_os("BWMPQ_CORPUS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/tmp/artifacts"));
+
+    let corpus: Vec<Vec<u8>> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("could not read corpus {}: {err}", dir.display()))
+        .map(|entry| std::fs::read(entry.unwrap().path()).unwrap())
+        .collect();
+    assert!(!corpus.is_empty(), "corpus {} is empty", dir.display());
+
+    let max_threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+
+    let baseline = run(&corpus, 1);
+    let maps = (corpus.len() * ROUNDS) as f64;
+
+    println!("threads  maps/s      speedup  efficiency");
+    let mut threads = 1;
+    while threads <= max_threads {
+        let elapsed = if threads == 1 {
+            baseline
+        } else {
+            run(&corpus, threads)
+        };
+        let speedup = baseline.as_secs_f64() / elapsed.as_secs_f64();
+
+        println!(
+            "{threads:<8} {:<11.1} {speedup:<8.2} {:.0}%",
+            maps / elapsed.as_secs_f64(),
+            speedup / threads as f64 * 100.0
+        );
+
+        threads *= 2;
+    }
+}
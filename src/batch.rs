@@ -0,0 +1,164 @@
+//! Processing a whole corpus of maps at once.
+
+use crate::error::Error;
+use crate::error::Result;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use tracing::instrument;
+
+/// A map to process, either a file to read or an archive already in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchInput {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl From<PathBuf> for BatchInput {
+    fn from(path: PathBuf) -> BatchInput {
+        BatchInput::Path(path)
+    }
+}
+
+impl From<&std::path::Path> for BatchInput {
+    fn from(path: &std::path::Path) -> BatchInput {
+        BatchInput::Path(path.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for BatchInput {
+    fn from(bytes: Vec<u8>) -> BatchInput {
+        BatchInput::Bytes(bytes)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatchOptions {
+    /// How many maps are processed, and held in memory, at the same time. 0 means one per CPU.
+    pub concurrency: usize,
+}
+
+/// The outcome for one input.
+#[derive(Debug)]
+pub struct BatchItem<T> {
+    /// The position of the input in the iterator.
+    pub index: usize,
+    /// The file it was read from, `None` for `BatchInput::Bytes`.
+    pub path: Option<PathBuf>,
+    pub result: Result<T>,
+}
+
+/// Where a batch is at, passed to the callback with every item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The input that just finished.
+    pub index: usize,
+    /// Items finished so far, this one included.
+    pub completed: usize,
+    /// How many of those failed.
+    pub failed: usize,
+    /// How many inputs there are, if the iterator knows. See `Iterator::size_hint`.
+    pub total: Option<usize>,
+}
+
+/// Runs `func` on the bytes of every input, `options.concurrency` at a time, and hands every
+/// result to `on_item` as soon as it's done, with the progress so far. Returns the progress at
+/// the end.
+///
+/// Inputs are read as they're needed and results are handed over as they finish, so only about
+/// `concurrency` maps are held at a time and the iterator can be longer than fits in memory.
+/// Results come in the order they finish, `BatchItem::index` says which input each is for. A file
+/// that can't be read, an error from `func` or a panic in `func` only fails that item, the panic
+/// as `Error::Panicked`. `on_item` is called on the calling thread.
+///
+/// `func` should use the pure Rust reader, such as `get_chk_from_mpq_in_memory` or
+/// `MpqReader`. `MpqArchive` goes through StormLib, which only runs one call at a time.
+#[instrument(level = "trace", skip_all)]
+pub fn process<I, T, F, P>(inputs: I, options: BatchOptions, func: F, mut on_item: P) -> Progress
+where
+    I: IntoIterator,
+    I::Item: Into<BatchInput>,
+    I::IntoIter: Send,
+    T: Send,
+    F: Fn(&[u8]) -> Result<T> + Sync,
+    P: FnMut(BatchItem<T>, Progress),
+{
+    let concurrency = match options.concurrency {
+        0 => std::thread::available_parallelism().map_or(1, |x| x.get()),
+        x => x,
+    };
+
+    let inputs = inputs.into_iter();
+    let total = match inputs.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(lower),
+        _ => None,
+    };
+    let inputs = Mutex::new(inputs.enumerate());
+    // Bounded, so workers wait for `on_item` instead of piling results up.
+    let (sender, receiver) = mpsc::sync_channel(concurrency);
+
+    let mut progress = Progress {
+        index: 0,
+        completed: 0,
+        failed: 0,
+        total,
+    };
+    std::thread::scope(|s| {
+        for _ in 0..concurrency {
+            let sender = sender.clone();
+            let (inputs, func) = (&inputs, &func);
+            s.spawn(move || loop {
+                // A panic in the input iterator poisons the lock, the other workers stop too.
+                let Some((index, input)) = inputs.lock().ok().and_then(|mut x| x.next()) else {
+                    break;
+                };
+
+                let item = run(index, input.into(), func);
+                if sender.send(item).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for item in receiver {
+            progress.index = item.index;
+            progress.completed += 1;
+            progress.failed += item.result.is_err() as usize;
+            on_item(item, progress);
+        }
+    });
+
+    progress
+}
+
+fn run<T>(index: usize, input: BatchInput, func: &impl Fn(&[u8]) -> Result<T>) -> BatchItem<T> {
+    let (path, bytes) = match input {
+        BatchInput::Path(path) => {
+            let bytes = std::fs::read(&path);
+            (Some(path), bytes)
+        }
+        BatchInput::Bytes(bytes) => (None, Ok(bytes)),
+    };
+
+    let result = match bytes {
+        Ok(bytes) => {
+            std::panic::catch_unwind(AssertUnwindSafe(|| func(&bytes))).unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|x| x.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(Error::Panicked(message))
+            })
+        }
+        Err(err) => Err(Error::Io(err)),
+    };
+
+    BatchItem {
+        index,
+        path,
+        result,
+    }
+}
@@ -0,0 +1,352 @@
+//! `bwmpq`, a command line tool for inspecting StarCraft maps and extracting files from them.
+//!
+//! Every subcommand takes any number of maps, given as files, directories (searched for
+//! `.scm` and `.scx` files) or glob patterns. A map that can't be read is reported and the
+//! rest are still processed, the exit code tells whether any failed. With `--json` every map
+//! produces one line of JSON, errors included.
+
+use bwmpq::chk::canonical_hash;
+use bwmpq::chk::Chk;
+use bwmpq::trigedit::Decompiler;
+use bwmpq::MpqReader;
+use bwmpq::ReaderOptions;
+use clap::Parser;
+use clap::Subcommand;
+use serde_json::json;
+use serde_json::Value;
+use sha2::Digest;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Parser)]
+#[command(version, about = "Inspect StarCraft maps and extract files from them")]
+struct Args {
+    /// Print one line of JSON per map instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write the scenario.chk of every map to <OUTPUT>/<map name>.chk. Maps with the same name
+    /// get " (2)", " (3)" and so on added to it.
+    ExtractChk {
+        #[arg(required = true)]
+        maps: Vec<String>,
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// List the entries of every archive.
+    Ls {
+        #[arg(required = true)]
+        maps: Vec<String>,
+    },
+    /// Write one file out of every archive to <OUTPUT>/<map name>/<NAME>. Maps with the same
+    /// name get " (2)", " (3)" and so on added to it.
+    Extract {
+        /// The name inside the archive, such as staredit\wav\sound.wav.
+        name: String,
+        #[arg(required = true)]
+        maps: Vec<String>,
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// Print a summary of every map as JSON.
+    Info {
+        #[arg(required = true)]
+        maps: Vec<String>,
+    },
+    /// Print the triggers and briefing of every map as TrigEdit text.
+    Triggers {
+        #[arg(required = true)]
+        maps: Vec<String>,
+    },
+    /// Print every non-empty string of every map.
+    Strings {
+        #[arg(required = true)]
+        maps: Vec<String>,
+    },
+    /// Report the decoy CHKs and other protection tricks in every archive.
+    Protection {
+        #[arg(required = true)]
+        maps: Vec<String>,
+    },
+    /// Print the canonical hash of every map, which ignores how the map is packaged.
+    Hash {
+        #[arg(required = true)]
+        maps: Vec<String>,
+    },
+}
+
+impl Command {
+    fn maps(&self) -> &[String] {
+        match self {
+            Command::ExtractChk { maps, .. }
+            | Command::Ls { maps }
+            | Command::Extract { maps, .. }
+            | Command::Info { maps }
+            | Command::Triggers { maps }
+            | Command::Strings { maps }
+            | Command::Protection { maps }
+            | Command::Hash { maps } => maps,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let mut failed = false;
+    let paths = expand(args.command.maps());
+    let mut names = output_names(paths.iter().flatten()).into_iter();
+    let many = paths.len() > 1;
+    for path in paths {
+        let result = path.and_then(|path| {
+            let name = names.next().unwrap();
+            let mut out = std::io::stdout().lock();
+            run(&args.command, &path, &name, args.json, many, &mut out)
+                .map_err(|err| (path.display().to_string(), err.to_string()))
+        });
+
+        if let Err((path, err)) = result {
+            failed = true;
+            if args.json {
+                println!("{}", json!({ "path": path, "error": err }));
+            } else {
+                eprintln!("{path}: {err}");
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Turns the command line arguments into map paths. Failures carry the argument that caused
+/// them and why.
+fn expand(inputs: &[String]) -> Vec<Result<PathBuf, (String, String)>> {
+    let mut paths = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            match find_maps(path) {
+                Ok(found) => paths.extend(found.into_iter().map(Ok)),
+                Err(err) => paths.push(Err((input.clone(), err.to_string()))),
+            }
+        } else if path.exists() {
+            paths.push(Ok(path.to_owned()));
+        } else {
+            match glob::glob(input) {
+                Ok(found) => {
+                    let found: Vec<_> = found.filter_map(|x| x.ok()).collect();
+                    if found.is_empty() {
+                        paths.push(Err((input.clone(), "No such file".to_owned())));
+                    }
+                    paths.extend(found.into_iter().map(Ok));
+                }
+                Err(err) => paths.push(Err((input.clone(), err.to_string()))),
+            }
+        }
+    }
+
+    paths
+}
+
+/// Every `.scm` and `.scx` file under `dir`, in a stable order.
+fn find_maps(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut maps = Vec::new();
+
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?
+        .map(|x| x.map(|x| x.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            maps.extend(find_maps(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("scm") || x.eq_ignore_ascii_case("scx"))
+        {
+            maps.push(path);
+        }
+    }
+
+    Ok(maps)
+}
+
+/// The name each map's output goes under: its file name without the extension, with " (2)",
+/// " (3)" and so on added when an earlier map already took it. Names are compared ignoring
+/// case, as Windows would.
+fn output_names<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> Vec<String> {
+    let mut taken = HashSet::new();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut name = stem.to_string();
+            let mut n = 1;
+            while !taken.insert(name.to_lowercase()) {
+                n += 1;
+                name = format!("{stem} ({n})");
+            }
+            name
+        })
+        .collect()
+}
+
+/// Runs `command` on the map at `path`, writing its output files under `name`.
+fn run(
+    command: &Command,
+    path: &Path,
+    name: &str,
+    json: bool,
+    many: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mpq = std::fs::read(path)?;
+    let read_chk = || -> Result<Chk, Box<dyn Error>> {
+        Ok(Chk::parse(&bwmpq::get_chk_from_mpq_in_memory(&mpq)?))
+    };
+
+    let value: Value = match command {
+        Command::ExtractChk { output, .. } => {
+            let chk = bwmpq::get_chk_from_mpq_in_memory(&mpq)?;
+            let target = output.join(format!("{name}.chk"));
+            std::fs::create_dir_all(output)?;
+            std::fs::write(&target, chk)?;
+            if !json {
+                writeln!(out, "{}", target.display())?;
+            }
+            json!({ "output": target })
+        }
+        Command::Ls { .. } => {
+            let entries: Vec<_> = MpqReader::from_bytes(&mpq, ReaderOptions::map())?
+                .entries()?
+                .collect();
+            if !json {
+                header(out, path, many)?;
+                for x in &entries {
+                    writeln!(
+                        out,
+                        "{:>5} {:>5} {:#06x} {:>9} {:>9} {:#010x} {}",
+                        x.hash_index,
+                        x.block_index,
+                        x.locale,
+                        x.compressed_size,
+                        x.uncompressed_size,
+                        x.flags.raw,
+                        x.name.as_deref().unwrap_or("<unknown>")
+                    )?;
+                }
+            }
+            json!({ "entries": entries })
+        }
+        Command::Extract {
+            name: file, output, ..
+        } => {
+            let data = MpqReader::from_bytes(&mpq, ReaderOptions::map())?.read_file(file)?;
+            let target = output.join(name).join(file.replace('\\', "/"));
+            std::fs::create_dir_all(target.parent().unwrap())?;
+            std::fs::write(&target, data)?;
+            if !json {
+                writeln!(out, "{}", target.display())?;
+            }
+            json!({ "output": target })
+        }
+        Command::Info { .. } => {
+            let summary = bwmpq::MapSummary::from_chk(&read_chk()?);
+            if !json {
+                header(out, path, many)?;
+                writeln!(out, "{}", serde_json::to_string_pretty(&summary)?)?;
+            }
+            json!({ "summary": summary })
+        }
+        Command::Triggers { .. } => {
+            let chk = read_chk()?;
+            let decompiler = Decompiler::new(&chk);
+            let (triggers, briefing) = (decompiler.triggers(), decompiler.briefing());
+            if !json {
+                header(out, path, many)?;
+                write!(out, "{triggers}{briefing}")?;
+            }
+            json!({ "triggers": triggers, "briefing": briefing })
+        }
+        Command::Strings { .. } => {
+            let strings = read_chk()?.decoded_strings();
+            if !json {
+                header(out, path, many)?;
+                for (id, string) in &strings {
+                    writeln!(out, "{id}\t{}", string.text.escape_debug())?;
+                }
+            }
+            let strings: Vec<_> = strings
+                .into_iter()
+                .map(|(id, string)| json!({ "id": id, "string": string }))
+                .collect();
+            json!({ "strings": strings })
+        }
+        Command::Protection { .. } => {
+            let report = bwmpq::analyze_protection(&mpq)?;
+            if !json {
+                header(out, path, many)?;
+                for x in &report.chk_candidates {
+                    writeln!(
+                        out,
+                        "scenario.chk at hash index {}, locale {:#06x}: {}{}",
+                        x.hash_index,
+                        x.locale,
+                        if x.parses_as_chk { "valid" } else { "invalid" },
+                        if x.selected_by_locale_probe {
+                            ", used"
+                        } else {
+                            ""
+                        }
+                    )?;
+                }
+                for x in &report.tricks {
+                    writeln!(out, "{x:?}")?;
+                }
+            }
+            json!({ "protected": report.is_protected(), "report": report })
+        }
+        Command::Hash { .. } => {
+            let raw = bwmpq::get_chk_from_mpq_in_memory(&mpq)?;
+            let hash = canonical_hash(&Chk::parse(&raw));
+            if !json {
+                writeln!(out, "{hash}  {}", path.display())?;
+            }
+            let sha256 = format!("{:x}", sha2::Sha256::digest(&raw));
+            json!({ "canonical_hash": hash, "chk_sha256": sha256 })
+        }
+    };
+
+    if json {
+        let mut value = value;
+        value["path"] = json!(path);
+        writeln!(out, "{value}")?;
+    }
+
+    Ok(())
+}
+
+/// Names the map before its output when there is more than one.
+fn header(out: &mut impl Write, path: &Path, many: bool) -> std::io::Result<()> {
+    if many {
+        writeln!(out, "==> {} <==", path.display())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use crate::expand;
+use crate::find_maps;
+use crate::output_names;
+use crate::run;
+use crate::Command;
+use bwmpq::chk::Chk;
+use bwmpq::FileOptions;
+use bwmpq::MpqWriter;
+use bwmpq::WriterOptions;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A fresh directory for one test, removed when it is dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("bwmpq-cli-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A map with a title, one trigger and nothing else.
+fn map() -> (Vec<u8>, Vec<u8>) {
+    let mut chk = Chk::default();
+    chk.set_section(*b"VER ", vec![205, 0]);
+    chk.set_section(*b"DIM ", vec![64, 0, 64, 0]);
+    chk.set_section(*b"ERA ", vec![0, 0]);
+    let title = chk.add_string(b"Test").unwrap() as u16;
+    chk.set_section(*b"SPRP", [title.to_le_bytes(), [0, 0]].concat());
+    let mut trigger = bwmpq::chk::Trigger::default();
+    trigger.conditions[0].condition = 22;
+    trigger.actions[0].action = 1;
+    trigger.players[17] = 1;
+    chk.set_section(*b"TRIG", trigger.to_bytes());
+    let chk = chk.to_bytes();
+
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_file(
+        "staredit\\scenario.chk",
+        chk.clone(),
+        FileOptions::default(),
+    );
+    (writer.finish().unwrap(), chk)
+}
+
+#[test]
+fn finds_maps() {
+    let dir = TempDir::new("find");
+    std::fs::create_dir_all(dir.0.join("sub")).unwrap();
+    for name in ["b.scm", "sub/a.SCX", "notes.txt", "sub/c.scx.bak"] {
+        std::fs::write(dir.0.join(name), b"").unwrap();
+    }
+
+    assert_eq!(
+        find_maps(&dir.0).unwrap(),
+        [dir.0.join("b.scm"), dir.0.join("sub/a.SCX")]
+    );
+
+    let input = |path: &Path| path.to_str().unwrap().to_owned();
+    let paths = expand(&[
+        input(&dir.0.join("sub")),
+        input(&dir.0.join("notes.txt")),
+        input(&dir.0.join("*.scm")),
+        input(&dir.0.join("missing.scm")),
+    ]);
+    assert_eq!(
+        paths,
+        [
+            Ok(dir.0.join("sub/a.SCX")),
+            Ok(dir.0.join("notes.txt")),
+            Ok(dir.0.join("b.scm")),
+            Err((input(&dir.0.join("missing.scm")), "No such file".to_owned())),
+        ]
+    );
+}
+
+#[test]
+fn names_outputs_uniquely() {
+    let paths = [
+        PathBuf::from("a/(4)Fighting Spirit 1.3.scx"),
+        PathBuf::from("b/(4)fighting spirit 1.3.scm"),
+        PathBuf::from("c/(4)Fighting Spirit 1.3.scx"),
+        PathBuf::from("d/(4)Fighting Spirit 1.3 (2).scx"),
+        PathBuf::from("LT.scm"),
+    ];
+
+    assert_eq!(
+        output_names(&paths),
+        [
+            "(4)Fighting Spirit 1.3",
+            "(4)fighting spirit 1.3 (2)",
+            "(4)Fighting Spirit 1.3 (3)",
+            "(4)Fighting Spirit 1.3 (2) (2)",
+            "LT",
+        ]
+    );
+}
+
+#[test]
+fn runs_every_command() {
+    let dir = TempDir::new("run");
+    let (mpq, chk) = map();
+    let path = dir.0.join("My.Map.v2.scx");
+    std::fs::write(&path, &mpq).unwrap();
+    let maps = vec![path.to_str().unwrap().to_owned()];
+    let output = dir.0.join("out");
+
+    let commands = [
+        Command::ExtractChk {
+            maps: maps.clone(),
+            output: output.clone(),
+        },
+        Command::Ls { maps: maps.clone() },
+        Command::Extract {
+            name: "staredit\\scenario.chk".to_owned(),
+            maps: maps.clone(),
+            output: output.clone(),
+        },
+        Command::Info { maps: maps.clone() },
+        Command::Triggers { maps: maps.clone() },
+        Command::Strings { maps: maps.clone() },
+        Command::Protection { maps: maps.clone() },
+        Command::Hash { maps: maps.clone() },
+    ];
+
+    let mut texts = Vec::new();
+    for command in &commands {
+        let mut text = Vec::new();
+        run(command, &path, "My.Map.v2", false, false, &mut text).unwrap();
+        texts.push(String::from_utf8(text).unwrap());
+
+        let mut json = Vec::new();
+        run(command, &path, "My.Map.v2", true, false, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["path"], path.to_str().unwrap());
+    }
+
+    let chk_output = output.join("My.Map.v2.chk");
+    assert_eq!(texts[0], format!("{}\n", chk_output.display()));
+    assert_eq!(std::fs::read(chk_output).unwrap(), chk);
+    assert!(texts[1].contains("staredit\\scenario.chk"), "{}", texts[1]);
+    let extracted = output.join("My.Map.v2/staredit/scenario.chk");
+    assert_eq!(std::fs::read(extracted).unwrap(), chk);
+    assert!(texts[3].contains("\"Test\""), "{}", texts[3]);
+    assert!(texts[4].contains("Always();"), "{}", texts[4]);
+    assert_eq!(texts[5], "1\tTest\n");
+    assert!(texts[6].contains("valid, used"), "{}", texts[6]);
+    assert!(texts[7].ends_with(&format!("  {}\n", path.display())));
+
+    std::fs::write(&path, b"not an archive").unwrap();
+    for command in &commands {
+        run(command, &path, "My.Map.v2", false, false, &mut Vec::new()).unwrap_err();
+    }
+}
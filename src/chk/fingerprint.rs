@@ -0,0 +1,211 @@
+use super::model::Chk;
+use super::types::Le;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+/// The terrain is summarised on a grid this many cells wide and high, whatever the map size.
+const GRID: usize = 8;
+/// Marks grid cells with no tiles in them, on maps smaller than the grid.
+const EMPTY_CELL: u16 = u16::MAX;
+/// The number of hash functions in each MinHash signature.
+const MINHASH_SIZE: usize = 64;
+/// Strings are broken into overlapping byte runs of this length.
+const SHINGLE_LEN: usize = 5;
+
+/// How much each part of a fingerprint counts towards `similarity`.
+const TERRAIN_WEIGHT: f64 = 0.4;
+const UNITS_WEIGHT: f64 = 0.2;
+const TRIGGERS_WEIGHT: f64 = 0.2;
+const STRINGS_WEIGHT: f64 = 0.2;
+
+/// A compact summary of a map for finding edited versions of it. Unlike `canonical_hash`, two
+/// maps that differ a little have fingerprints that differ a little.
+///
+/// Fingerprints only depend on the CHK, and the hashing is fixed, so they can be stored and
+/// compared later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The raw tileset from ERA, masked the way StarCraft masks it.
+    pub tileset: u16,
+    /// A perceptual hash of MTXM: the most common tile group in each cell of an 8 by 8 grid
+    /// laid over the map, row by row. Small terrain edits only change a few cells, and resizing
+    /// the map only shifts the cell borders.
+    pub terrain: Vec<u16>,
+    /// MinHash of the preplaced units, each as its type, owner and position.
+    pub units: Vec<u64>,
+    /// MinHash of the triggers and briefing triggers, each as its players and the opcodes of its
+    /// conditions and actions. Arguments are left out, so retuned triggers still match.
+    pub triggers: Vec<u64>,
+    /// MinHash of runs of 5 bytes from every string the map refers to.
+    pub strings: Vec<u64>,
+}
+
+impl Fingerprint {
+    /// A score from 0 for unrelated maps to 1 for maps that look the same.
+    ///
+    /// Terrain counts for 40% and units, triggers and strings for 20% each. Parts that are
+    /// empty in both maps, such as the triggers of two melee maps, are left out and the other
+    /// parts count for more. Maps on different tilesets share no terrain.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let terrain = if self.tileset == other.tileset {
+            cell_similarity(&self.terrain, &other.terrain)
+        } else {
+            Some(0.0)
+        };
+
+        let parts = [
+            (terrain, TERRAIN_WEIGHT),
+            (minhash_similarity(&self.units, &other.units), UNITS_WEIGHT),
+            (
+                minhash_similarity(&self.triggers, &other.triggers),
+                TRIGGERS_WEIGHT,
+            ),
+            (
+                minhash_similarity(&self.strings, &other.strings),
+                STRINGS_WEIGHT,
+            ),
+        ];
+
+        let (score, weight) = parts
+            .iter()
+            .filter_map(|&(score, weight)| Some((score? * weight, weight)))
+            .fold((0.0, 0.0), |(a, b), (x, y)| (a + x, b + y));
+        if weight == 0.0 {
+            1.0
+        } else {
+            score / weight
+        }
+    }
+}
+
+/// Fingerprints a map, see `Fingerprint`.
+pub fn fingerprint(chk: &Chk) -> Fingerprint {
+    let units = chk.units().into_iter().map(|x| {
+        [
+            x.unit_id.to_le_bytes().as_slice(),
+            &[x.owner],
+            &x.x.to_le_bytes(),
+            &x.y.to_le_bytes(),
+        ]
+        .concat()
+    });
+
+    let triggers = chk
+        .triggers()
+        .into_iter()
+        .map(|x| (0u8, x))
+        .chain(chk.briefing_triggers().into_iter().map(|x| (1u8, x)))
+        .map(|(kind, x)| {
+            let mut feature = vec![kind];
+            feature.extend(x.players);
+            feature.extend(x.conditions.iter().map(|x| x.condition));
+            feature.extend(x.actions.iter().map(|x| x.action));
+            feature
+        });
+
+    let mut shingles = BTreeSet::new();
+    for id in chk.string_references() {
+        let string = chk.string(id).unwrap_or_default();
+        if string.len() <= SHINGLE_LEN {
+            shingles.insert(string.to_vec());
+        } else {
+            shingles.extend(string.windows(SHINGLE_LEN).map(<[u8]>::to_vec));
+        }
+    }
+    shingles.remove(&Vec::new());
+
+    Fingerprint {
+        tileset: chk.section(b"ERA ").map_or(0, |x| Le::new(x).u16() & 7),
+        terrain: terrain_cells(chk),
+        units: minhash(units),
+        triggers: minhash(triggers),
+        strings: minhash(shingles),
+    }
+}
+
+/// How alike two maps are, from 0 to 1. See `Fingerprint::similarity`.
+pub fn similarity(a: &Chk, b: &Chk) -> f64 {
+    fingerprint(a).similarity(&fingerprint(b))
+}
+
+fn terrain_cells(chk: &Chk) -> Vec<u16> {
+    let (width, height) = chk.tile_dimensions();
+    let tiles = chk.mtxm().unwrap_or_default();
+
+    let mut counts = vec![HashMap::<u16, usize>::new(); GRID * GRID];
+    for (i, tile) in tiles.iter().enumerate() {
+        let (x, y) = (i % width, i / width);
+        let cell = (y * GRID / height) * GRID + x * GRID / width;
+        // The low 4 bits pick a tile within the group, which is mostly decoration.
+        *counts[cell].entry(tile >> 4).or_default() += 1;
+    }
+
+    counts
+        .iter()
+        .map(|x| {
+            x.iter()
+                .max_by_key(|&(group, count)| (count, std::cmp::Reverse(group)))
+                .map_or(EMPTY_CELL, |(&group, _)| group)
+        })
+        .collect()
+}
+
+/// The fraction of grid cells that match, `None` if neither map has any terrain.
+fn cell_similarity(a: &[u16], b: &[u16]) -> Option<f64> {
+    let used = a
+        .iter()
+        .zip(b)
+        .filter(|&(x, y)| *x != EMPTY_CELL || *y != EMPTY_CELL)
+        .count();
+    let same = a
+        .iter()
+        .zip(b)
+        .filter(|&(x, y)| *x != EMPTY_CELL && x == y)
+        .count();
+    (used > 0).then(|| same as f64 / used as f64)
+}
+
+/// The MinHash signature of a set of features: for each of the hash functions, the smallest
+/// hash of any feature. Empty sets get an empty signature.
+fn minhash<T: AsRef<[u8]>>(features: impl IntoIterator<Item = T>) -> Vec<u64> {
+    let mut signature = Vec::new();
+    for feature in features {
+        let hash = fnv1a(feature.as_ref());
+        if signature.is_empty() {
+            signature = vec![u64::MAX; MINHASH_SIZE];
+        }
+        for (i, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(splitmix64(hash ^ splitmix64(i as u64)));
+        }
+    }
+    signature
+}
+
+/// Estimates the Jaccard similarity of the two sets, `None` if both are empty.
+fn minhash_similarity(a: &[u64], b: &[u64]) -> Option<f64> {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => None,
+        (true, false) | (false, true) => Some(0.0),
+        (false, false) => {
+            let same = a.iter().zip(b).filter(|(x, y)| x == y).count();
+            Some(same as f64 / a.len().max(b.len()) as f64)
+        }
+    }
+}
+
+/// 64 bit FNV-1a. Fingerprints are meant to be stored, so they can't use `std`'s hasher, which
+/// is allowed to change.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &x| {
+        (hash ^ x as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e3779b97f4a7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
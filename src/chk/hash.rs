@@ -0,0 +1,200 @@
+use super::model::Chk;
+use super::section::SectionName;
+use super::types::Le;
+use super::types::Location;
+use super::types::Sprite;
+use super::types::Unit;
+use super::types::UNIT_TYPE_COUNT;
+use sha2::Digest;
+use sha2::Sha256;
+
+/// Mixed into every hash, so that a future encoding can never collide with this one.
+const ENCODING: &[u8] = b"bwmpq canonical hash v1";
+
+/// Where the unit names start in UNIS and UNIx.
+const UNIT_NAMES_OFFSET: usize = UNIT_TYPE_COUNT * 14;
+
+/// A SHA-256 of what the map is, as a lowercase hex string, rather than of how its CHK is laid
+/// out.
+///
+/// Section order, duplicate sections, junk sections, padding, the editor-only sections (ISOM,
+/// TILE, DD2, IVER, IVE2, IOWN, TYPE, VCOD) and the string table layout don't change the hash:
+/// strings are hashed by content wherever something refers to them, and strings nothing refers
+/// to aren't hashed. Only the unit, upgrade and tech sections the map's version actually uses
+/// are hashed. Terrain, fog, units, doodads, locations and triggers are hashed as they are, in
+/// order. Maps that read the string table through EUDs can play differently and still hash the
+/// same.
+///
+/// The hash of a given CHK never changes between versions of this crate. If what is hashed has
+/// to change, that will be a new function and this one will keep its behaviour.
+pub fn canonical_hash(chk: &Chk) -> String {
+    let mut hasher = Hasher {
+        chk,
+        sha: Sha256::new(),
+    };
+    hasher.sha.update(ENCODING);
+
+    hasher.record(b"VER ", vec![chk.is_expansion() as u8]);
+    if let Some(era) = chk.section(b"ERA ") {
+        // StarCraft only looks at the low 3 bits.
+        hasher.record(b"ERA ", vec![(Le::new(era).u16() & 7) as u8]);
+    }
+    hasher.fixed(b"DIM ", 4);
+    if let Some(mtxm) = chk.mtxm() {
+        hasher.record(b"MTXM", mtxm.iter().flat_map(|x| x.to_le_bytes()).collect());
+    }
+    if let Some(mask) = chk.mask() {
+        let (width, height) = chk.tile_dimensions();
+        hasher.record(b"MASK", padded(mask, width * height));
+    }
+
+    hasher.fixed(b"OWNR", 12);
+    hasher.fixed(b"SIDE", 12);
+    hasher.fixed(b"COLR", 8);
+    hasher.fixed(b"CRGB", 32);
+    if let Some(forces) = chk.forces() {
+        let mut out = forces.player_forces.to_vec();
+        for name in forces.names {
+            hasher.string(&mut out, name as u32);
+        }
+        out.extend(forces.flags);
+        hasher.record(b"FORC", out);
+    }
+    if let Some(sprp) = chk.scenario_properties() {
+        let mut out = Vec::new();
+        hasher.string(&mut out, sprp.name as u32);
+        hasher.string(&mut out, sprp.description as u32);
+        hasher.record(b"SPRP", out);
+    }
+
+    hasher.records(b"UNIT", Unit::SIZE);
+    hasher.records(b"THG2", Sprite::SIZE);
+
+    hasher.fixed(b"PUNI", 5700);
+    hasher.fixed(b"UPRP", 1280);
+    hasher.fixed(b"UPUS", 64);
+    let settings: [(&SectionName, usize); 5] = if chk.is_expansion() {
+        [
+            (b"UNIx", 4168),
+            (b"UPGx", 794),
+            (b"TECx", 396),
+            (b"PUPx", 2318),
+            (b"PTEx", 1672),
+        ]
+    } else {
+        [
+            (b"UNIS", 4048),
+            (b"UPGS", 598),
+            (b"TECS", 216),
+            (b"UPGR", 1748),
+            (b"PTEC", 912),
+        ]
+    };
+    for (name, size) in settings {
+        let Some(data) = chk.section(name) else {
+            continue;
+        };
+        let mut out = padded(data, size);
+        if name == b"UNIx" || name == b"UNIS" {
+            let names = &mut out[UNIT_NAMES_OFFSET..UNIT_NAMES_OFFSET + UNIT_TYPE_COUNT * 2];
+            let ids = Le::new(names).u16s(UNIT_TYPE_COUNT);
+            names.fill(0);
+            for id in ids {
+                hasher.string(&mut out, id as u32);
+            }
+        }
+        hasher.record(name, out);
+    }
+
+    let mut out = Vec::new();
+    for location in chk.locations() {
+        let unnamed = Location {
+            name: 0,
+            ..location.clone()
+        };
+        out.extend(unnamed.to_bytes());
+        hasher.string(&mut out, location.name as u32);
+    }
+    hasher.record(b"MRGN", out);
+
+    for (name, ids) in [(b"WAV ", chk.wavs()), (b"SWNM", chk.switch_names())] {
+        if let Some(ids) = ids {
+            let mut out = Vec::new();
+            for id in ids {
+                hasher.string(&mut out, id);
+            }
+            hasher.record(name, out);
+        }
+    }
+
+    for (name, triggers) in [
+        (b"TRIG", chk.triggers()),
+        (b"MBRF", chk.briefing_triggers()),
+    ] {
+        let mut out = Vec::new();
+        for mut trigger in triggers {
+            // Unused action slots keep whatever numbers they have, like everything StarCraft
+            // doesn't read as a string.
+            let mut strings = Vec::new();
+            for action in trigger.actions.iter_mut().filter(|x| x.action != 0) {
+                hasher.string(&mut strings, std::mem::take(&mut action.string));
+                hasher.string(&mut strings, std::mem::take(&mut action.wav));
+            }
+            out.extend(trigger.to_bytes());
+            out.extend(strings);
+        }
+        hasher.record(name, out);
+    }
+
+    format!("{:x}", hasher.sha.finalize())
+}
+
+struct Hasher<'a> {
+    chk: &'a Chk,
+    sha: Sha256,
+}
+
+impl Hasher<'_> {
+    /// Hashes one tagged, length prefixed record, so that no two sequences of records hash
+    /// the same bytes.
+    fn record(&mut self, tag: &SectionName, data: Vec<u8>) {
+        self.sha.update(tag);
+        self.sha.update((data.len() as u64).to_le_bytes());
+        self.sha.update(data);
+    }
+
+    /// Hashes a fixed size section, cut or zero padded to the size StarCraft reads.
+    fn fixed(&mut self, name: &SectionName, size: usize) {
+        if let Some(data) = self.chk.section(name) {
+            self.record(name, padded(data, size));
+        }
+    }
+
+    /// Hashes a section of fixed size records, without a partial record at the end.
+    fn records(&mut self, name: &SectionName, size: usize) {
+        if let Some(data) = self.chk.section(name) {
+            self.record(name, data[..data.len() / size * size].to_vec());
+        }
+    }
+
+    /// Writes a string reference: the string itself if `id` names one, the bare number if not.
+    fn string(&self, out: &mut Vec<u8>, id: u32) {
+        match self.chk.string(id) {
+            Some(string) => {
+                out.push(1);
+                out.extend((string.len() as u32).to_le_bytes());
+                out.extend(string);
+            }
+            None => {
+                out.push(0);
+                out.extend(id.to_le_bytes());
+            }
+        }
+    }
+}
+
+fn padded(data: &[u8], size: usize) -> Vec<u8> {
+    let mut out = data[..data.len().min(size)].to_vec();
+    out.resize(size, 0);
+    out
+}
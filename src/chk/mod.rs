@@ -0,0 +1,44 @@
+//! Parsing of `staredit\scenario.chk`, the file inside a map archive that holds the map itself.
+
+mod fingerprint;
+mod hash;
+mod model;
+mod section;
+mod strings;
+mod types;
+mod write;
+
+pub use fingerprint::fingerprint;
+pub use fingerprint::similarity;
+pub use fingerprint::Fingerprint;
+pub use hash::canonical_hash;
+pub use model::Chk;
+pub use section::duplicate_rule;
+pub use section::section;
+pub use section::sections;
+pub use section::DuplicateRule;
+pub use section::SectionName;
+pub use section::Sections;
+pub use strings::Encoding;
+pub use strings::MapString;
+pub use strings::Span;
+pub use types::Action;
+pub use types::Condition;
+pub use types::CustomColors;
+pub use types::Forces;
+pub use types::Location;
+pub use types::Owner;
+pub use types::Race;
+pub use types::ScenarioProperties;
+pub use types::Sprite;
+pub use types::TechAvailability;
+pub use types::TechSettings;
+pub use types::Tileset;
+pub use types::Trigger;
+pub use types::Unit;
+pub use types::UnitAvailability;
+pub use types::UnitProperties;
+pub use types::UnitSettings;
+pub use types::UpgradeLevels;
+pub use types::UpgradeSettings;
+pub use types::Vcod;
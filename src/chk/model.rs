@@ -0,0 +1,405 @@
+use super::section::duplicate_rule;
+use super::section::sections;
+use super::section::DuplicateRule;
+use super::section::SectionName;
+use super::types::CustomColors;
+use super::types::Forces;
+use super::types::Le;
+use super::types::Location;
+use super::types::Owner;
+use super::types::Race;
+use super::types::ScenarioProperties;
+use super::types::Sprite;
+use super::types::TechAvailability;
+use super::types::TechSettings;
+use super::types::Tileset;
+use super::types::Trigger;
+use super::types::Unit;
+use super::types::UnitAvailability;
+use super::types::UnitProperties;
+use super::types::UnitSettings;
+use super::types::UpgradeLevels;
+use super::types::UpgradeSettings;
+use super::types::Vcod;
+use super::types::PLAYER_COUNT;
+
+/// The largest map StarCraft loads, in tiles. Tile sections are never read past this, whatever
+/// DIM says.
+const MAX_DIMENSION: usize = 256;
+
+/// A parsed `scenario.chk`.
+///
+/// Duplicate sections are combined once, when parsing, the way StarCraft combines them (see
+/// `duplicate_rule`), so every section appears exactly once, in the order it first appeared.
+/// Typed accessors decode sections on demand. Fixed size sections that are too short read as
+/// if they were padded with zeroes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chk {
+    sections: Vec<(SectionName, Vec<u8>)>,
+}
+
+impl Chk {
+    /// Parses a CHK file. This never fails: anything StarCraft would skip over is skipped over,
+    /// and missing sections show up as `None` or empty lists from the accessors.
+    pub fn parse(chk: &[u8]) -> Chk {
+        let mut parsed = Chk::default();
+
+        for (name, data) in sections(chk) {
+            match parsed.sections.iter_mut().find(|(x, _)| *x == name) {
+                None => parsed.sections.push((name, data.to_vec())),
+                Some((_, previous)) => match duplicate_rule(&name) {
+                    DuplicateRule::Overwrite => *previous = data.to_vec(),
+                    DuplicateRule::PartialOverwrite => {
+                        if previous.len() < data.len() {
+                            previous.resize(data.len(), 0);
+                        }
+                        previous[..data.len()].copy_from_slice(data);
+                    }
+                    DuplicateRule::Append => previous.extend_from_slice(data),
+                },
+            }
+        }
+
+        parsed
+    }
+
+    /// The combined data of section `name`.
+    pub fn section(&self, name: &SectionName) -> Option<&[u8]> {
+        self.sections
+            .iter()
+            .find(|(x, _)| x == name)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    /// Every section, in the order they first appeared.
+    pub fn sections(&self) -> impl Iterator<Item = (&SectionName, &[u8])> {
+        self.sections
+            .iter()
+            .map(|(name, data)| (name, data.as_slice()))
+    }
+
+    /// Replaces the data of section `name`, adding it at the end if it doesn't exist yet.
+    pub fn set_section(&mut self, name: SectionName, data: Vec<u8>) {
+        match self.sections.iter_mut().find(|(x, _)| *x == name) {
+            Some((_, previous)) => *previous = data,
+            None => self.sections.push((name, data)),
+        }
+    }
+
+    /// Removes section `name`, returning its data.
+    pub fn remove_section(&mut self, name: &SectionName) -> Option<Vec<u8>> {
+        let index = self.sections.iter().position(|(x, _)| x == name)?;
+        Some(self.sections.remove(index).1)
+    }
+
+    /// The format version from VER: 59 for StarCraft, 63 for hybrid, 205 for Brood War,
+    /// 64 and 206 for their Remastered counterparts.
+    pub fn version(&self) -> Option<u16> {
+        self.section(b"VER ").map(|x| Le::new(x).u16())
+    }
+
+    /// Whether Brood War reads the expansion variants (UNIx, UPGx, TECx, PUPx, PTEx) of the
+    /// unit, upgrade and tech sections instead of the original ones.
+    pub fn is_expansion(&self) -> bool {
+        self.version().is_some_and(|x| x == 63 || x >= 205)
+    }
+
+    pub fn vcod(&self) -> Option<Vcod> {
+        self.section(b"VCOD").map(Vcod::parse)
+    }
+
+    pub fn owners(&self) -> Option<[Owner; PLAYER_COUNT]> {
+        let mut r = Le::new(self.section(b"OWNR")?);
+        Some(std::array::from_fn(|_| Owner::from_raw(r.u8())))
+    }
+
+    pub fn sides(&self) -> Option<[Race; PLAYER_COUNT]> {
+        let mut r = Le::new(self.section(b"SIDE")?);
+        Some(std::array::from_fn(|_| Race::from_raw(r.u8())))
+    }
+
+    pub fn tileset(&self) -> Option<Tileset> {
+        self.section(b"ERA ")
+            .map(|x| Tileset::from_raw(Le::new(x).u16()))
+    }
+
+    /// Width and height in tiles, from DIM.
+    pub fn dimensions(&self) -> Option<(u16, u16)> {
+        let mut r = Le::new(self.section(b"DIM ")?);
+        Some((r.u16(), r.u16()))
+    }
+
+    /// Width and height from DIM, each cut to 256 tiles. This is the area `mtxm`, `tiles` and
+    /// MASK cover.
+    pub(crate) fn tile_dimensions(&self) -> (usize, usize) {
+        let (width, height) = self.dimensions().unwrap_or((0, 0));
+        (
+            (width as usize).min(MAX_DIMENSION),
+            (height as usize).min(MAX_DIMENSION),
+        )
+    }
+
+    /// The tiles StarCraft draws, row by row, from MTXM. Maps over 256 tiles wide or high are
+    /// read as if they were 256 tiles.
+    pub fn mtxm(&self) -> Option<Vec<u16>> {
+        self.tile_section(b"MTXM")
+    }
+
+    /// The tiles the editor placed, row by row, from TILE.
+    pub fn tiles(&self) -> Option<Vec<u16>> {
+        self.tile_section(b"TILE")
+    }
+
+    /// The isometric terrain values used by editors, from ISOM.
+    pub fn isom(&self) -> Option<Vec<u16>> {
+        let data = self.section(b"ISOM")?;
+        Some(Le::new(data).u16s(data.len() / 2))
+    }
+
+    /// Fog of war per tile, one bit per player, from MASK.
+    pub fn mask(&self) -> Option<&[u8]> {
+        self.section(b"MASK")
+    }
+
+    pub fn units(&self) -> Vec<Unit> {
+        self.records(b"UNIT", Unit::SIZE, Unit::parse)
+    }
+
+    pub fn sprites(&self) -> Vec<Sprite> {
+        self.records(b"THG2", Sprite::SIZE, Sprite::parse)
+    }
+
+    /// Unit property slots referenced by triggers, from UPRP.
+    pub fn unit_properties(&self) -> Vec<UnitProperties> {
+        self.records(b"UPRP", UnitProperties::SIZE, UnitProperties::parse)
+    }
+
+    /// Which UPRP slots are in use, from UPUS.
+    pub fn unit_properties_used(&self) -> Option<Vec<bool>> {
+        self.section(b"UPUS").map(|x| Le::new(x).bools(64))
+    }
+
+    /// Every location slot, from MRGN. Triggers refer to these by index + 1.
+    pub fn locations(&self) -> Vec<Location> {
+        self.records(b"MRGN", Location::SIZE, Location::parse)
+    }
+
+    pub fn triggers(&self) -> Vec<Trigger> {
+        self.records(b"TRIG", Trigger::SIZE, Trigger::parse)
+    }
+
+    /// Mission briefing triggers, from MBRF.
+    pub fn briefing_triggers(&self) -> Vec<Trigger> {
+        self.records(b"MBRF", Trigger::SIZE, Trigger::parse)
+    }
+
+    pub fn scenario_properties(&self) -> Option<ScenarioProperties> {
+        self.section(b"SPRP").map(ScenarioProperties::parse)
+    }
+
+    pub fn forces(&self) -> Option<Forces> {
+        self.section(b"FORC").map(Forces::parse)
+    }
+
+    /// String ids of the WAV files the map uses, from WAV.
+    pub fn wavs(&self) -> Option<Vec<u32>> {
+        self.section(b"WAV ").map(|x| Le::new(x).u32s(512))
+    }
+
+    /// String ids of the switch names, from SWNM.
+    pub fn switch_names(&self) -> Option<Vec<u32>> {
+        self.section(b"SWNM").map(|x| Le::new(x).u32s(256))
+    }
+
+    /// Player colours, from COLR.
+    pub fn colors(&self) -> Option<[u8; 8]> {
+        self.section(b"COLR").map(|x| Le::new(x).bytes())
+    }
+
+    pub fn custom_colors(&self) -> Option<CustomColors> {
+        self.section(b"CRGB").map(CustomColors::parse)
+    }
+
+    /// Unit stats, from UNIx for expansion maps and UNIS otherwise.
+    pub fn unit_settings(&self) -> Option<UnitSettings> {
+        if self.is_expansion() {
+            self.section(b"UNIx").map(|x| UnitSettings::parse(x, 130))
+        } else {
+            self.section(b"UNIS").map(|x| UnitSettings::parse(x, 100))
+        }
+    }
+
+    /// Upgrade costs, from UPGx for expansion maps and UPGS otherwise.
+    pub fn upgrade_settings(&self) -> Option<UpgradeSettings> {
+        if self.is_expansion() {
+            self.section(b"UPGx")
+                .map(|x| UpgradeSettings::parse(x, 61, true))
+        } else {
+            self.section(b"UPGS")
+                .map(|x| UpgradeSettings::parse(x, 46, false))
+        }
+    }
+
+    /// Tech costs, from TECx for expansion maps and TECS otherwise.
+    pub fn tech_settings(&self) -> Option<TechSettings> {
+        if self.is_expansion() {
+            self.section(b"TECx").map(|x| TechSettings::parse(x, 44))
+        } else {
+            self.section(b"TECS").map(|x| TechSettings::parse(x, 24))
+        }
+    }
+
+    pub fn unit_availability(&self) -> Option<UnitAvailability> {
+        self.section(b"PUNI").map(UnitAvailability::parse)
+    }
+
+    /// Upgrade levels, from PUPx for expansion maps and UPGR otherwise.
+    pub fn upgrade_levels(&self) -> Option<UpgradeLevels> {
+        if self.is_expansion() {
+            self.section(b"PUPx").map(|x| UpgradeLevels::parse(x, 61))
+        } else {
+            self.section(b"UPGR").map(|x| UpgradeLevels::parse(x, 46))
+        }
+    }
+
+    /// Tech availability, from PTEx for expansion maps and PTEC otherwise.
+    pub fn tech_availability(&self) -> Option<TechAvailability> {
+        if self.is_expansion() {
+            self.section(b"PTEx")
+                .map(|x| TechAvailability::parse(x, 44))
+        } else {
+            self.section(b"PTEC")
+                .map(|x| TechAvailability::parse(x, 24))
+        }
+    }
+
+    /// The number of string slots. STRx, the Remastered table with 32 bit offsets, wins over STR.
+    pub fn string_count(&self) -> usize {
+        match self.string_table() {
+            Some((data, true)) => Le::new(data).u32() as usize,
+            Some((data, false)) => Le::new(data).u16() as usize,
+            None => 0,
+        }
+    }
+
+    /// The raw bytes of string `id`, up to the terminating NUL. Ids start at 1, 0 means no string.
+    pub fn string(&self, id: u32) -> Option<&[u8]> {
+        let (data, extended) = self.string_table()?;
+        if id == 0 || id as usize > self.string_count() {
+            return None;
+        }
+
+        let mut r = Le::new(data);
+        let offset = if extended {
+            r.skip(id as usize * 4);
+            r.u32() as usize
+        } else {
+            r.skip(id as usize * 2);
+            r.u16() as usize
+        };
+
+        let string = data.get(offset..)?;
+        let len = string.iter().position(|&x| x == 0).unwrap_or(string.len());
+        Some(&string[..len])
+    }
+
+    /// Adds a string at the end of the string table and returns its id, or `None` if STR can't
+    /// address any more data. The string is written to STRx if the map has one, STR otherwise.
+    ///
+    /// Every existing id keeps its text. Strings are not deduplicated.
+    pub fn add_string(&mut self, string: &[u8]) -> Option<u32> {
+        let (name, width) = match self.string_table() {
+            Some((_, true)) => (*b"STRx", 4),
+            _ => (*b"STR ", 2),
+        };
+        let data = self.section(&name).unwrap_or_default();
+
+        let count = self.string_count();
+        let mut r = Le::new(data);
+        r.skip(width);
+        let offsets: Vec<usize> = (0..count)
+            .map(|_| match width {
+                4 => r.u32() as usize,
+                _ => r.u16() as usize,
+            })
+            .collect();
+
+        // The offset table grows by one slot, which moves the data after it. Offsets that point
+        // into the old offset table get a copy of their text, since those bytes are rewritten.
+        let header_len = width * (count + 1);
+        let mut body = data.get(header_len..).unwrap_or_default().to_vec();
+        let mut new_offsets = Vec::with_capacity(count + 1);
+        for (id, &offset) in offsets.iter().enumerate() {
+            if offset >= header_len {
+                new_offsets.push(offset + width);
+            } else {
+                new_offsets.push(header_len + width + body.len());
+                body.extend(self.string(id as u32 + 1).unwrap_or_default());
+                body.push(0);
+            }
+        }
+        new_offsets.push(header_len + width + body.len());
+        body.extend(string);
+        body.push(0);
+
+        let mut table = Vec::with_capacity(header_len + width + body.len());
+        if width == 4 {
+            table.extend((count as u32 + 1).to_le_bytes());
+            for offset in new_offsets {
+                table.extend(u32::try_from(offset).ok()?.to_le_bytes());
+            }
+        } else {
+            table.extend(u16::try_from(count + 1).ok()?.to_le_bytes());
+            for offset in new_offsets {
+                table.extend(u16::try_from(offset).ok()?.to_le_bytes());
+            }
+        }
+        table.extend(body);
+
+        self.set_section(name, table);
+        Some(count as u32 + 1)
+    }
+
+    /// Puts a location in the first unused MRGN slot and returns its id, or `None` if every slot
+    /// is taken. Slot 64, "Anywhere", is never handed out.
+    pub fn add_location(&mut self, location: Location) -> Option<u32> {
+        let capacity = if self.is_expansion() { 255 } else { 64 };
+
+        let mut locations = self.locations();
+        if locations.len() < capacity {
+            locations.resize(capacity, Location::default());
+        }
+
+        let index = locations
+            .iter()
+            .enumerate()
+            .position(|(i, x)| i != 63 && *x == Location::default())?;
+        locations[index] = location;
+
+        self.set_section(
+            *b"MRGN",
+            locations.iter().flat_map(Location::to_bytes).collect(),
+        );
+        Some(index as u32 + 1)
+    }
+
+    fn string_table(&self) -> Option<(&[u8], bool)> {
+        self.section(b"STRx")
+            .map(|x| (x, true))
+            .or_else(|| self.section(b"STR ").map(|x| (x, false)))
+    }
+
+    /// Reads a width * height tile section. StarCraft zero fills what the section doesn't cover.
+    fn tile_section(&self, name: &SectionName) -> Option<Vec<u16>> {
+        let data = self.section(name)?;
+        let (width, height) = self.tile_dimensions();
+        Some(Le::new(data).u16s(width * height))
+    }
+
+    /// Splits a section into fixed size records, dropping a partial record at the end.
+    fn records<T>(&self, name: &SectionName, size: usize, parse: fn(&[u8]) -> T) -> Vec<T> {
+        self.section(name)
+            .map(|data| data.chunks_exact(size).map(parse).collect())
+            .unwrap_or_default()
+    }
+}
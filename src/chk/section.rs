@@ -0,0 +1,134 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// The four byte tag at the start of every section, e.g. `*b"VER "`.
+pub type SectionName = [u8; 4];
+
+/// An iterator over the raw sections of a CHK file, in the order StarCraft reads them.
+///
+/// Each section is an 8 byte header, a name and a signed 32 bit size, followed by its data.
+/// StarCraft reads them with a few rules that map protectors lean on:
+/// - A negative size has no data and moves the read position backwards by that many bytes
+///   (measured from the end of the header), so sections can be hidden inside other sections.
+/// - A section whose size runs past the end of the file is cut short at the end of the file.
+/// - Fewer than 8 bytes left over at the end are junk and ignored.
+///
+/// Unlike StarCraft, iteration also ends once the sections read add up to four times the size
+/// of the file. Jumps into the data of earlier sections can otherwise yield the same
+/// bytes over and over, quadratic in the size of the file.
+///
+/// Duplicate sections are all yielded; see `section` for how they combine.
+#[derive(Debug, Clone)]
+pub struct Sections<'a> {
+    chk: &'a [u8],
+    pos: usize,
+    /// Positions jumped back to, so that a chain of negative sizes that loops ends the iteration.
+    jumped_to: HashSet<usize>,
+    /// Bytes read so far, headers included.
+    visited: usize,
+}
+
+/// How many times over `Sections` reads the file before giving up. A file read straight through
+/// is read once, hidden sections add a little to that.
+const MAX_VISITS: usize = 4;
+
+/// Iterates over the sections of a CHK file. See `Sections`.
+pub fn sections(chk: &[u8]) -> Sections<'_> {
+    Sections {
+        chk,
+        pos: 0,
+        jumped_to: HashSet::new(),
+        visited: 0,
+    }
+}
+
+impl<'a> Iterator for Sections<'a> {
+    type Item = (SectionName, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos + 8 > self.chk.len() {
+                return None;
+            }
+
+            let name: SectionName = self.chk[self.pos..self.pos + 4].try_into().unwrap();
+            let size = i32::from_le_bytes(self.chk[self.pos + 4..self.pos + 8].try_into().unwrap());
+            let start = self.pos + 8;
+
+            self.visited += 8;
+            if self.visited > self.chk.len().saturating_mul(MAX_VISITS) {
+                return None;
+            }
+
+            if size < 0 {
+                // PROTECTION: Negative sizes jump backwards. A jump to before the start of the file ends it.
+                let next = start.checked_sub(size.unsigned_abs() as usize)?;
+                if !self.jumped_to.insert(next) {
+                    return None;
+                }
+                self.pos = next;
+                continue;
+            }
+
+            // PROTECTION: Sections running past the end of the file are truncated rather than rejected.
+            let end = start.saturating_add(size as usize).min(self.chk.len());
+            self.pos = end;
+
+            self.visited += end - start;
+            if self.visited > self.chk.len().saturating_mul(MAX_VISITS) {
+                return None;
+            }
+
+            return Some((name, &self.chk[start..end]));
+        }
+    }
+}
+
+/// What StarCraft does when the same section appears more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateRule {
+    /// The last section wins.
+    Overwrite,
+    /// Each section is copied over the start of the previous data, so a shorter later section
+    /// leaves the tail of an earlier one in place.
+    PartialOverwrite,
+    /// Every section is processed in turn, so their contents add up.
+    Append,
+}
+
+/// Returns how duplicates of section `name` are combined.
+pub fn duplicate_rule(name: &SectionName) -> DuplicateRule {
+    match name {
+        b"MTXM" | b"STR " | b"STRx" => DuplicateRule::PartialOverwrite,
+        b"UNIT" | b"THG2" | b"TRIG" | b"MBRF" => DuplicateRule::Append,
+        _ => DuplicateRule::Overwrite,
+    }
+}
+
+/// Returns the data of section `name` as StarCraft sees it, with all duplicates combined
+/// according to `duplicate_rule`. Only borrows from `chk` when a single section contributes.
+pub fn section<'a>(chk: &'a [u8], name: &SectionName) -> Option<Cow<'a, [u8]>> {
+    let rule = duplicate_rule(name);
+
+    let mut data: Option<Cow<'a, [u8]>> = None;
+    for (_, x) in sections(chk).filter(|(x, _)| x == name) {
+        data = Some(match (data, rule) {
+            (None, _) | (Some(_), DuplicateRule::Overwrite) => Cow::Borrowed(x),
+            (Some(previous), DuplicateRule::PartialOverwrite) => {
+                let mut previous = previous.into_owned();
+                if previous.len() < x.len() {
+                    previous.resize(x.len(), 0);
+                }
+                previous[..x.len()].copy_from_slice(x);
+                Cow::Owned(previous)
+            }
+            (Some(previous), DuplicateRule::Append) => {
+                let mut previous = previous.into_owned();
+                previous.extend_from_slice(x);
+                Cow::Owned(previous)
+            }
+        });
+    }
+
+    data
+}
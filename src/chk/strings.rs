@@ -0,0 +1,171 @@
+use super::model::Chk;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The text encodings map strings are found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    /// Written by Remastered editors.
+    Utf8,
+    /// Korean, written by Korean versions of the classic editors.
+    Cp949,
+    /// Western European, written by everything else.
+    Cp1252,
+}
+
+impl Encoding {
+    /// Guesses the encoding a set of strings was written in. Valid UTF-8 wins, then CP949 if the
+    /// strings decode cleanly as CP949 and mostly come out as Hangul, then CP1252 which can
+    /// decode anything.
+    ///
+    /// Pass a single string to detect the encoding of just that string.
+    pub fn detect(strings: &[&[u8]]) -> Encoding {
+        if strings.iter().all(|x| std::str::from_utf8(x).is_ok()) {
+            return Encoding::Utf8;
+        }
+
+        let mut hangul = 0usize;
+        let mut other = 0usize;
+        for string in strings {
+            let (decoded, had_errors) = encoding_rs::EUC_KR.decode_without_bom_handling(string);
+            if had_errors {
+                return Encoding::Cp1252;
+            }
+
+            for c in decoded.chars().filter(|c| !c.is_ascii()) {
+                if is_hangul(c) {
+                    hangul += 1;
+                } else {
+                    other += 1;
+                }
+            }
+        }
+
+        if hangul >= other {
+            Encoding::Cp949
+        } else {
+            Encoding::Cp1252
+        }
+    }
+
+    pub(crate) fn decode(&self, bytes: &[u8]) -> String {
+        let encoding = match self {
+            Encoding::Utf8 => encoding_rs::UTF_8,
+            Encoding::Cp949 => encoding_rs::EUC_KR,
+            Encoding::Cp1252 => encoding_rs::WINDOWS_1252,
+        };
+
+        encoding.decode_without_bom_handling(bytes).0.into_owned()
+    }
+
+    /// Characters the encoding can't represent come out as HTML numeric character references,
+    /// so check them with `can_encode` first where that matters.
+    pub(crate) fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Cp949 => encoding_rs::EUC_KR.encode(text).0.into_owned(),
+            Encoding::Cp1252 => encoding_rs::WINDOWS_1252.encode(text).0.into_owned(),
+        }
+    }
+
+    pub(crate) fn can_encode(&self, c: char) -> bool {
+        let encoding = match self {
+            Encoding::Utf8 => return true,
+            Encoding::Cp949 => encoding_rs::EUC_KR,
+            Encoding::Cp1252 => encoding_rs::WINDOWS_1252,
+        };
+
+        !encoding.encode(c.encode_utf8(&mut [0; 4])).2
+    }
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{AC00}'..='\u{D7A3}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}')
+}
+
+/// A map string with StarCraft's control bytes interpreted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapString {
+    /// The text with colour and alignment bytes removed. Tabs and line breaks are kept.
+    pub text: String,
+    /// The text split wherever the colour changes.
+    pub spans: Vec<Span>,
+}
+
+/// A run of text drawn in one colour.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    /// The colour control byte in effect (0x01-0x08, 0x0E-0x11, 0x14-0x1F), `None` for the default colour.
+    pub color: Option<u8>,
+    pub text: String,
+}
+
+impl MapString {
+    /// Decodes a raw string. Control bytes are below 0x20 and can't be part of a multibyte
+    /// sequence in any of the encodings, so the string is split on them before decoding.
+    pub fn decode(bytes: &[u8], encoding: Encoding) -> MapString {
+        let mut spans: Vec<Span> = Vec::new();
+        let mut color = None;
+
+        let mut start = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            if byte >= 0x20 || matches!(byte, b'\t' | b'\n' | b'\r') {
+                continue;
+            }
+
+            push_span(&mut spans, color, encoding.decode(&bytes[start..i]));
+            start = i + 1;
+
+            // 0x12 and 0x13 right align and center the line, 0x0B and 0x0C are not colours either.
+            if !matches!(byte, 0x0B | 0x0C | 0x12 | 0x13) {
+                color = Some(byte);
+            }
+        }
+        push_span(&mut spans, color, encoding.decode(&bytes[start..]));
+
+        MapString {
+            text: spans.iter().map(|x| x.text.as_str()).collect(),
+            spans,
+        }
+    }
+}
+
+fn push_span(spans: &mut Vec<Span>, color: Option<u8>, text: String) {
+    if text.is_empty() {
+        return;
+    }
+
+    match spans.last_mut() {
+        Some(last) if last.color == color => last.text.push_str(&text),
+        _ => spans.push(Span { color, text }),
+    }
+}
+
+impl Chk {
+    /// Guesses the encoding of the whole string table. See `Encoding::detect`.
+    pub fn encoding(&self) -> Encoding {
+        let strings: Vec<&[u8]> = (1..=self.string_count() as u32)
+            .filter_map(|id| self.string(id))
+            .collect();
+
+        Encoding::detect(&strings)
+    }
+
+    /// Decodes string `id` using the encoding of the whole string table.
+    pub fn decoded_string(&self, id: u32) -> Option<MapString> {
+        self.string(id)
+            .map(|x| MapString::decode(x, self.encoding()))
+    }
+
+    /// Decodes every non-empty string, detecting the encoding of the string table once.
+    pub fn decoded_strings(&self) -> Vec<(u32, MapString)> {
+        let encoding = self.encoding();
+
+        (1..=self.string_count() as u32)
+            .filter_map(|id| {
+                let string = self.string(id).filter(|x| !x.is_empty())?;
+                Some((id, MapString::decode(string, encoding)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,680 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+pub(crate) const PLAYER_COUNT: usize = 12;
+pub(crate) const UNIT_TYPE_COUNT: usize = 228;
+
+/// Little endian reads over a section. Reading past the end yields zeroes, which is how short
+/// fixed size sections are treated.
+pub(crate) struct Le<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Le<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Le<'a> {
+        Le { data, pos: 0 }
+    }
+
+    pub(crate) fn u8(&mut self) -> u8 {
+        let x = self.data.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        x
+    }
+
+    pub(crate) fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+
+    pub(crate) fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.u8(), self.u8(), self.u8(), self.u8()])
+    }
+
+    pub(crate) fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        std::array::from_fn(|_| self.u8())
+    }
+
+    pub(crate) fn bools(&mut self, count: usize) -> Vec<bool> {
+        (0..count).map(|_| self.u8() != 0).collect()
+    }
+
+    pub(crate) fn u8s(&mut self, count: usize) -> Vec<u8> {
+        (0..count).map(|_| self.u8()).collect()
+    }
+
+    pub(crate) fn u16s(&mut self, count: usize) -> Vec<u16> {
+        (0..count).map(|_| self.u16()).collect()
+    }
+
+    pub(crate) fn u32s(&mut self, count: usize) -> Vec<u32> {
+        (0..count).map(|_| self.u32()).collect()
+    }
+
+    pub(crate) fn skip(&mut self, count: usize) {
+        self.pos += count;
+    }
+}
+
+/// Who controls a player slot, from OWNR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Owner {
+    Inactive,
+    ComputerGame,
+    OccupiedByHuman,
+    RescuePassive,
+    Unused,
+    Computer,
+    Human,
+    Neutral,
+    Closed,
+    Other(u8),
+}
+
+impl Owner {
+    pub fn from_raw(raw: u8) -> Owner {
+        match raw {
+            0 => Owner::Inactive,
+            1 => Owner::ComputerGame,
+            2 => Owner::OccupiedByHuman,
+            3 => Owner::RescuePassive,
+            4 => Owner::Unused,
+            5 => Owner::Computer,
+            6 => Owner::Human,
+            7 => Owner::Neutral,
+            8 => Owner::Closed,
+            x => Owner::Other(x),
+        }
+    }
+}
+
+/// The race of a player slot, from SIDE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Race {
+    Zerg,
+    Terran,
+    Protoss,
+    Independent,
+    Neutral,
+    UserSelectable,
+    Random,
+    Inactive,
+    Other(u8),
+}
+
+impl Race {
+    pub fn from_raw(raw: u8) -> Race {
+        match raw {
+            0 => Race::Zerg,
+            1 => Race::Terran,
+            2 => Race::Protoss,
+            3 => Race::Independent,
+            4 => Race::Neutral,
+            5 => Race::UserSelectable,
+            6 => Race::Random,
+            7 => Race::Inactive,
+            x => Race::Other(x),
+        }
+    }
+}
+
+/// The map's tileset, from ERA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tileset {
+    Badlands,
+    SpacePlatform,
+    Installation,
+    Ashworld,
+    Jungle,
+    Desert,
+    Arctic,
+    Twilight,
+}
+
+impl Tileset {
+    /// StarCraft only looks at the low three bits, so every value maps to a tileset.
+    pub fn from_raw(raw: u16) -> Tileset {
+        match raw & 7 {
+            0 => Tileset::Badlands,
+            1 => Tileset::SpacePlatform,
+            2 => Tileset::Installation,
+            3 => Tileset::Ashworld,
+            4 => Tileset::Jungle,
+            5 => Tileset::Desert,
+            6 => Tileset::Arctic,
+            _ => Tileset::Twilight,
+        }
+    }
+
+    /// The tileset's file name, as used for the CV5/VX4/VR4/WPE files.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Tileset::Badlands => "badlands",
+            Tileset::SpacePlatform => "platform",
+            Tileset::Installation => "install",
+            Tileset::Ashworld => "ashworld",
+            Tileset::Jungle => "jungle",
+            Tileset::Desert => "desert",
+            Tileset::Arctic => "ice",
+            Tileset::Twilight => "twilight",
+        }
+    }
+}
+
+/// The verification code, from VCOD.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vcod {
+    pub seeds: Vec<u32>,
+    pub opcodes: [u8; 16],
+}
+
+impl Vcod {
+    pub(crate) fn parse(data: &[u8]) -> Vcod {
+        let mut r = Le::new(data);
+        Vcod {
+            seeds: r.u32s(256),
+            opcodes: r.bytes(),
+        }
+    }
+}
+
+/// A preplaced unit, from UNIT.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unit {
+    pub class_instance: u32,
+    pub x: u16,
+    pub y: u16,
+    pub unit_id: u16,
+    pub relation_type: u16,
+    pub valid_special_properties: u16,
+    pub valid_elements: u16,
+    pub owner: u8,
+    pub hit_points: u8,
+    pub shields: u8,
+    pub energy: u8,
+    pub resources: u32,
+    pub hangar: u16,
+    pub state_flags: u16,
+    pub unused: u32,
+    pub related_unit: u32,
+}
+
+impl Unit {
+    pub(crate) const SIZE: usize = 36;
+
+    pub(crate) fn parse(data: &[u8]) -> Unit {
+        let mut r = Le::new(data);
+        Unit {
+            class_instance: r.u32(),
+            x: r.u16(),
+            y: r.u16(),
+            unit_id: r.u16(),
+            relation_type: r.u16(),
+            valid_special_properties: r.u16(),
+            valid_elements: r.u16(),
+            owner: r.u8(),
+            hit_points: r.u8(),
+            shields: r.u8(),
+            energy: r.u8(),
+            resources: r.u32(),
+            hangar: r.u16(),
+            state_flags: r.u16(),
+            unused: r.u32(),
+            related_unit: r.u32(),
+        }
+    }
+}
+
+/// A preplaced sprite or doodad, from THG2.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sprite {
+    /// A unit id if `flags` has the unit bit (0x1000) set, a sprite id otherwise.
+    pub id: u16,
+    pub x: u16,
+    pub y: u16,
+    pub owner: u8,
+    pub unused: u8,
+    pub flags: u16,
+}
+
+impl Sprite {
+    pub(crate) const SIZE: usize = 10;
+
+    pub(crate) fn parse(data: &[u8]) -> Sprite {
+        let mut r = Le::new(data);
+        Sprite {
+            id: r.u16(),
+            x: r.u16(),
+            y: r.u16(),
+            owner: r.u8(),
+            unused: r.u8(),
+            flags: r.u16(),
+        }
+    }
+}
+
+/// Unit properties referenced by "Create Units With Properties" actions, from UPRP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitProperties {
+    pub valid_special_properties: u16,
+    pub valid_elements: u16,
+    pub owner: u8,
+    pub hit_points: u8,
+    pub shields: u8,
+    pub energy: u8,
+    pub resources: u32,
+    pub hangar: u16,
+    pub flags: u16,
+    pub unused: u32,
+}
+
+impl UnitProperties {
+    pub(crate) const SIZE: usize = 20;
+
+    pub(crate) fn parse(data: &[u8]) -> UnitProperties {
+        let mut r = Le::new(data);
+        UnitProperties {
+            valid_special_properties: r.u16(),
+            valid_elements: r.u16(),
+            owner: r.u8(),
+            hit_points: r.u8(),
+            shields: r.u8(),
+            energy: r.u8(),
+            resources: r.u32(),
+            hangar: r.u16(),
+            flags: r.u16(),
+            unused: r.u32(),
+        }
+    }
+}
+
+/// A location, from MRGN. Unused locations are all zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    /// String id of the location's name, 0 for none.
+    pub name: u16,
+    pub elevation_flags: u16,
+}
+
+impl Location {
+    pub(crate) const SIZE: usize = 20;
+
+    pub(crate) fn parse(data: &[u8]) -> Location {
+        let mut r = Le::new(data);
+        Location {
+            left: r.u32(),
+            top: r.u32(),
+            right: r.u32(),
+            bottom: r.u32(),
+            name: r.u16(),
+            elevation_flags: r.u16(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [
+            self.left.to_le_bytes().as_slice(),
+            &self.top.to_le_bytes(),
+            &self.right.to_le_bytes(),
+            &self.bottom.to_le_bytes(),
+            &self.name.to_le_bytes(),
+            &self.elevation_flags.to_le_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// A trigger or mission briefing trigger, from TRIG or MBRF. All 16 condition and 64 action
+/// slots are kept, including the unused ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<Action>,
+    pub execution_flags: u32,
+    /// Which players (0-11), forces (18-21) and groups the trigger runs for.
+    pub players: [u8; 28],
+}
+
+impl Default for Trigger {
+    /// A trigger with every slot unused, that runs for nobody.
+    fn default() -> Trigger {
+        Trigger {
+            conditions: vec![Condition::default(); Trigger::CONDITION_COUNT],
+            actions: vec![Action::default(); Trigger::ACTION_COUNT],
+            execution_flags: 0,
+            players: [0; 28],
+        }
+    }
+}
+
+impl Trigger {
+    pub(crate) const SIZE: usize = 2400;
+    pub(crate) const CONDITION_COUNT: usize = 16;
+    pub(crate) const ACTION_COUNT: usize = 64;
+
+    pub(crate) fn parse(data: &[u8]) -> Trigger {
+        let mut r = Le::new(data);
+        Trigger {
+            conditions: (0..Trigger::CONDITION_COUNT)
+                .map(|_| Condition {
+                    location: r.u32(),
+                    group: r.u32(),
+                    quantity: r.u32(),
+                    unit_type: r.u16(),
+                    comparison: r.u8(),
+                    condition: r.u8(),
+                    resource_type: r.u8(),
+                    flags: r.u8(),
+                    mask: r.u16(),
+                })
+                .collect(),
+            actions: (0..Trigger::ACTION_COUNT)
+                .map(|_| Action {
+                    location: r.u32(),
+                    string: r.u32(),
+                    wav: r.u32(),
+                    time: r.u32(),
+                    group: r.u32(),
+                    number: r.u32(),
+                    unit_type: r.u16(),
+                    action: r.u8(),
+                    modifier: r.u8(),
+                    flags: r.u8(),
+                    padding: r.u8(),
+                    mask: r.u16(),
+                })
+                .collect(),
+            execution_flags: r.u32(),
+            players: r.bytes(),
+        }
+    }
+
+    /// The 2400 byte record. Missing condition and action slots are written as unused ones.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Trigger::SIZE);
+
+        let unused = Condition::default();
+        for i in 0..Trigger::CONDITION_COUNT {
+            let c = self.conditions.get(i).unwrap_or(&unused);
+            out.extend(c.location.to_le_bytes());
+            out.extend(c.group.to_le_bytes());
+            out.extend(c.quantity.to_le_bytes());
+            out.extend(c.unit_type.to_le_bytes());
+            out.extend([c.comparison, c.condition, c.resource_type, c.flags]);
+            out.extend(c.mask.to_le_bytes());
+        }
+
+        let unused = Action::default();
+        for i in 0..Trigger::ACTION_COUNT {
+            let a = self.actions.get(i).unwrap_or(&unused);
+            out.extend(a.location.to_le_bytes());
+            out.extend(a.string.to_le_bytes());
+            out.extend(a.wav.to_le_bytes());
+            out.extend(a.time.to_le_bytes());
+            out.extend(a.group.to_le_bytes());
+            out.extend(a.number.to_le_bytes());
+            out.extend(a.unit_type.to_le_bytes());
+            out.extend([a.action, a.modifier, a.flags, a.padding]);
+            out.extend(a.mask.to_le_bytes());
+        }
+
+        out.extend(self.execution_flags.to_le_bytes());
+        out.extend(self.players);
+        out
+    }
+}
+
+/// One trigger condition. `condition` is the opcode, 0 means the slot is unused.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+    pub location: u32,
+    pub group: u32,
+    pub quantity: u32,
+    pub unit_type: u16,
+    pub comparison: u8,
+    pub condition: u8,
+    /// Resource type, score type or switch id depending on the condition.
+    pub resource_type: u8,
+    pub flags: u8,
+    pub mask: u16,
+}
+
+/// One trigger action. `action` is the opcode, 0 means the slot is unused.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Action {
+    pub location: u32,
+    pub string: u32,
+    pub wav: u32,
+    pub time: u32,
+    pub group: u32,
+    /// A second group, a number or a destination location depending on the action.
+    pub number: u32,
+    /// Unit type, score type, alliance status and so on depending on the action.
+    pub unit_type: u16,
+    pub action: u8,
+    /// Quantity, modifier or switch state depending on the action.
+    pub modifier: u8,
+    pub flags: u8,
+    pub padding: u8,
+    pub mask: u16,
+}
+
+/// The map's name and description, from SPRP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScenarioProperties {
+    /// String id of the map name.
+    pub name: u16,
+    /// String id of the map description.
+    pub description: u16,
+}
+
+impl ScenarioProperties {
+    pub(crate) fn parse(data: &[u8]) -> ScenarioProperties {
+        let mut r = Le::new(data);
+        ScenarioProperties {
+            name: r.u16(),
+            description: r.u16(),
+        }
+    }
+}
+
+/// Force assignments, from FORC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Forces {
+    /// The force (0-3) each of the first 8 players is in.
+    pub player_forces: [u8; 8],
+    /// String ids of the force names.
+    pub names: [u16; 4],
+    /// Random start location, allies, allied victory and shared vision bits per force.
+    pub flags: [u8; 4],
+}
+
+impl Forces {
+    pub(crate) fn parse(data: &[u8]) -> Forces {
+        let mut r = Le::new(data);
+        Forces {
+            player_forces: r.bytes(),
+            names: [r.u16(), r.u16(), r.u16(), r.u16()],
+            flags: r.bytes(),
+        }
+    }
+}
+
+/// Unit stat overrides, from UNIS or UNIx. Every list is indexed by unit or weapon id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitSettings {
+    pub use_defaults: Vec<bool>,
+    pub hit_points: Vec<u32>,
+    pub shields: Vec<u16>,
+    pub armor: Vec<u8>,
+    pub build_time: Vec<u16>,
+    pub minerals: Vec<u16>,
+    pub gas: Vec<u16>,
+    /// String ids of custom unit names.
+    pub names: Vec<u16>,
+    pub base_damage: Vec<u16>,
+    pub upgrade_damage: Vec<u16>,
+}
+
+impl UnitSettings {
+    pub(crate) fn parse(data: &[u8], weapon_count: usize) -> UnitSettings {
+        let mut r = Le::new(data);
+        UnitSettings {
+            use_defaults: r.bools(UNIT_TYPE_COUNT),
+            hit_points: r.u32s(UNIT_TYPE_COUNT),
+            shields: r.u16s(UNIT_TYPE_COUNT),
+            armor: r.u8s(UNIT_TYPE_COUNT),
+            build_time: r.u16s(UNIT_TYPE_COUNT),
+            minerals: r.u16s(UNIT_TYPE_COUNT),
+            gas: r.u16s(UNIT_TYPE_COUNT),
+            names: r.u16s(UNIT_TYPE_COUNT),
+            base_damage: r.u16s(weapon_count),
+            upgrade_damage: r.u16s(weapon_count),
+        }
+    }
+}
+
+/// Upgrade cost overrides, from UPGS or UPGx. Every list is indexed by upgrade id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradeSettings {
+    pub use_defaults: Vec<bool>,
+    pub mineral_base: Vec<u16>,
+    pub mineral_factor: Vec<u16>,
+    pub gas_base: Vec<u16>,
+    pub gas_factor: Vec<u16>,
+    pub time_base: Vec<u16>,
+    pub time_factor: Vec<u16>,
+}
+
+impl UpgradeSettings {
+    /// UPGx has a padding byte after the flags to align the rest, UPGS does not.
+    pub(crate) fn parse(data: &[u8], upgrade_count: usize, padded: bool) -> UpgradeSettings {
+        let mut r = Le::new(data);
+        let use_defaults = r.bools(upgrade_count);
+        if padded {
+            r.skip(1);
+        }
+        UpgradeSettings {
+            use_defaults,
+            mineral_base: r.u16s(upgrade_count),
+            mineral_factor: r.u16s(upgrade_count),
+            gas_base: r.u16s(upgrade_count),
+            gas_factor: r.u16s(upgrade_count),
+            time_base: r.u16s(upgrade_count),
+            time_factor: r.u16s(upgrade_count),
+        }
+    }
+}
+
+/// Tech cost overrides, from TECS or TECx. Every list is indexed by tech id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TechSettings {
+    pub use_defaults: Vec<bool>,
+    pub minerals: Vec<u16>,
+    pub gas: Vec<u16>,
+    pub time: Vec<u16>,
+    pub energy: Vec<u16>,
+}
+
+impl TechSettings {
+    pub(crate) fn parse(data: &[u8], tech_count: usize) -> TechSettings {
+        let mut r = Le::new(data);
+        TechSettings {
+            use_defaults: r.bools(tech_count),
+            minerals: r.u16s(tech_count),
+            gas: r.u16s(tech_count),
+            time: r.u16s(tech_count),
+            energy: r.u16s(tech_count),
+        }
+    }
+}
+
+/// Which units each player can build, from PUNI. Per player lists are indexed `[player][unit]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitAvailability {
+    pub available: Vec<Vec<bool>>,
+    pub global_available: Vec<bool>,
+    pub use_global: Vec<Vec<bool>>,
+}
+
+impl UnitAvailability {
+    pub(crate) fn parse(data: &[u8]) -> UnitAvailability {
+        let mut r = Le::new(data);
+        UnitAvailability {
+            available: (0..PLAYER_COUNT)
+                .map(|_| r.bools(UNIT_TYPE_COUNT))
+                .collect(),
+            global_available: r.bools(UNIT_TYPE_COUNT),
+            use_global: (0..PLAYER_COUNT)
+                .map(|_| r.bools(UNIT_TYPE_COUNT))
+                .collect(),
+        }
+    }
+}
+
+/// Upgrade levels per player, from UPGR or PUPx. Per player lists are indexed `[player][upgrade]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradeLevels {
+    pub max_level: Vec<Vec<u8>>,
+    pub start_level: Vec<Vec<u8>>,
+    pub global_max_level: Vec<u8>,
+    pub global_start_level: Vec<u8>,
+    pub use_global: Vec<Vec<bool>>,
+}
+
+impl UpgradeLevels {
+    pub(crate) fn parse(data: &[u8], upgrade_count: usize) -> UpgradeLevels {
+        let mut r = Le::new(data);
+        UpgradeLevels {
+            max_level: (0..PLAYER_COUNT).map(|_| r.u8s(upgrade_count)).collect(),
+            start_level: (0..PLAYER_COUNT).map(|_| r.u8s(upgrade_count)).collect(),
+            global_max_level: r.u8s(upgrade_count),
+            global_start_level: r.u8s(upgrade_count),
+            use_global: (0..PLAYER_COUNT).map(|_| r.bools(upgrade_count)).collect(),
+        }
+    }
+}
+
+/// Tech availability per player, from PTEC or PTEx. Per player lists are indexed `[player][tech]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TechAvailability {
+    pub available: Vec<Vec<bool>>,
+    pub researched: Vec<Vec<bool>>,
+    pub global_available: Vec<bool>,
+    pub global_researched: Vec<bool>,
+    pub use_global: Vec<Vec<bool>>,
+}
+
+impl TechAvailability {
+    pub(crate) fn parse(data: &[u8], tech_count: usize) -> TechAvailability {
+        let mut r = Le::new(data);
+        TechAvailability {
+            available: (0..PLAYER_COUNT).map(|_| r.bools(tech_count)).collect(),
+            researched: (0..PLAYER_COUNT).map(|_| r.bools(tech_count)).collect(),
+            global_available: r.bools(tech_count),
+            global_researched: r.bools(tech_count),
+            use_global: (0..PLAYER_COUNT).map(|_| r.bools(tech_count)).collect(),
+        }
+    }
+}
+
+/// Remastered player colours, from CRGB.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomColors {
+    pub rgb: [[u8; 3]; 8],
+    /// 0 random, 1 player's choice, 2 the custom `rgb` value, 3 the COLR colour id.
+    pub select: [u8; 8],
+}
+
+impl CustomColors {
+    pub(crate) fn parse(data: &[u8]) -> CustomColors {
+        let mut r = Le::new(data);
+        CustomColors {
+            rgb: std::array::from_fn(|_| r.bytes()),
+            select: r.bytes(),
+        }
+    }
+}
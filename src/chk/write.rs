@@ -0,0 +1,176 @@
+use super::model::Chk;
+use super::section::SectionName;
+use super::types::UnitSettings;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+/// Known sections in the order the editors write them, with the size of the fixed size ones.
+const CANONICAL: &[(&SectionName, Option<usize>)] = &[
+    (b"TYPE", Some(4)),
+    (b"VER ", Some(2)),
+    (b"IVER", Some(2)),
+    (b"IVE2", Some(2)),
+    (b"VCOD", Some(1040)),
+    (b"IOWN", Some(12)),
+    (b"OWNR", Some(12)),
+    (b"ERA ", Some(2)),
+    (b"DIM ", Some(4)),
+    (b"SIDE", Some(12)),
+    (b"MTXM", None),
+    (b"PUNI", Some(5700)),
+    (b"UPGR", Some(1748)),
+    (b"PTEC", Some(912)),
+    (b"UNIT", None),
+    (b"ISOM", None),
+    (b"TILE", None),
+    (b"DD2 ", None),
+    (b"THG2", None),
+    (b"MASK", None),
+    (b"STR ", None),
+    (b"UPRP", Some(1280)),
+    (b"UPUS", Some(64)),
+    (b"MRGN", None),
+    (b"TRIG", None),
+    (b"MBRF", None),
+    (b"SPRP", Some(4)),
+    (b"FORC", Some(20)),
+    (b"WAV ", Some(2048)),
+    (b"UNIS", Some(4048)),
+    (b"UPGS", Some(598)),
+    (b"TECS", Some(216)),
+    (b"SWNM", Some(1024)),
+    (b"COLR", Some(8)),
+    (b"PUPx", Some(2318)),
+    (b"PTEx", Some(1672)),
+    (b"UNIx", Some(4168)),
+    (b"UPGx", Some(794)),
+    (b"TECx", Some(396)),
+    (b"CRGB", Some(32)),
+    (b"STRx", None),
+];
+
+impl Chk {
+    /// Writes the CHK back out.
+    ///
+    /// Every section is written once, known sections first in the order the editors use and
+    /// unknown ones after them, verbatim and in their original order. Fixed size sections that
+    /// are too short are padded with zeroes, which is how StarCraft reads them anyway. Sections
+    /// whose names aren't printable ASCII are protector junk and are dropped.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut write = |name: &SectionName, data: &[u8], size: Option<usize>| {
+            let padding = size.map_or(0, |x| x.saturating_sub(data.len()));
+            out.extend(name);
+            out.extend(((data.len() + padding) as u32).to_le_bytes());
+            out.extend(data);
+            out.resize(out.len() + padding, 0);
+        };
+
+        for (name, size) in CANONICAL {
+            if let Some(data) = self.section(name) {
+                write(name, data, *size);
+            }
+        }
+
+        for (name, data) in self.sections() {
+            let known = CANONICAL.iter().any(|(x, _)| *x == name);
+            let junk = name.iter().any(|x| !(0x20..0x7F).contains(x));
+            if !known && !junk {
+                write(name, data, None);
+            }
+        }
+
+        out
+    }
+
+    /// Rewrites the string table so that unused strings are empty and identical strings share
+    /// their bytes. String ids don't change, so nothing that refers to them needs rewriting.
+    ///
+    /// A string counts as used if SPRP, FORC, MRGN, UNIS, UNIx, WAV, SWNM or any trigger or
+    /// briefing action refers to it. Maps that read the string table through EUDs may rely on
+    /// more than that, so this is not done by `to_bytes`.
+    pub fn compact_strings(&mut self) {
+        let (name, width) = match self.section(b"STRx") {
+            Some(_) => (*b"STRx", 4),
+            None if self.section(b"STR ").is_some() => (*b"STR ", 2),
+            None => return,
+        };
+
+        let used = self.string_references();
+        let count = self.string_count();
+
+        // Offset 0 of the data is the empty string every unused id points to.
+        let header_len = width * (count + 1);
+        let mut body = vec![0u8];
+        let mut placed: HashMap<&[u8], usize> = HashMap::new();
+        let mut offsets = Vec::with_capacity(count);
+        for id in 1..=count as u32 {
+            let string = match self.string(id) {
+                Some(string) if used.contains(&id) && !string.is_empty() => string,
+                _ => {
+                    offsets.push(header_len);
+                    continue;
+                }
+            };
+
+            let offset = *placed.entry(string).or_insert_with(|| {
+                body.extend(string);
+                body.push(0);
+                header_len + body.len() - string.len() - 1
+            });
+            offsets.push(offset);
+        }
+
+        let mut table = Vec::with_capacity(header_len + body.len());
+        if width == 4 {
+            table.extend((count as u32).to_le_bytes());
+            offsets
+                .iter()
+                .for_each(|&x| table.extend((x as u32).to_le_bytes()));
+        } else {
+            // Only possible if the original table relied on overlapping strings.
+            if header_len + body.len() > u16::MAX as usize + 1 {
+                return;
+            }
+            table.extend((count as u16).to_le_bytes());
+            offsets
+                .iter()
+                .for_each(|&x| table.extend((x as u16).to_le_bytes()));
+        }
+        table.extend(body);
+
+        self.set_section(name, table);
+    }
+
+    /// Ids of every string a section StarCraft reads refers to.
+    pub(crate) fn string_references(&self) -> BTreeSet<u32> {
+        let mut used = BTreeSet::new();
+
+        if let Some(sprp) = self.scenario_properties() {
+            used.extend([sprp.name as u32, sprp.description as u32]);
+        }
+        if let Some(forces) = self.forces() {
+            used.extend(forces.names.map(u32::from));
+        }
+        used.extend(self.locations().iter().map(|x| x.name as u32));
+
+        for (name, weapon_count) in [(b"UNIS", 100), (b"UNIx", 130)] {
+            if let Some(data) = self.section(name) {
+                let settings = UnitSettings::parse(data, weapon_count);
+                used.extend(settings.names.iter().map(|&x| x as u32));
+            }
+        }
+
+        used.extend(self.wavs().unwrap_or_default());
+        used.extend(self.switch_names().unwrap_or_default());
+
+        for trigger in self.triggers().iter().chain(&self.briefing_triggers()) {
+            for action in trigger.actions.iter().filter(|x| x.action != 0) {
+                used.extend([action.string, action.wav]);
+            }
+        }
+
+        used.remove(&0);
+        used
+    }
+}
@@ -0,0 +1,113 @@
+//! Storm's IMA ADPCM variant, used for WAV files stored in maps.
+
+const INITIAL_ADPCM_STEP_INDEX: i32 = 0x2C;
+
+const NEXT_STEP_TABLE: [i32; 32] = [
+    -1, 0, -1, 4, -1, 2, -1, 6, -1, 1, -1, 5, -1, 3, -1, 7, -1, 1, -1, 5, -1, 3, -1, 7, -1, 2, -1,
+    4, -1, 6, -1, 8,
+];
+
+const STEP_SIZE_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+fn next_step_index(step_index: i32, encoded_sample: u8) -> i32 {
+    (step_index + NEXT_STEP_TABLE[(encoded_sample & 0x1F) as usize]).clamp(0, 88)
+}
+
+fn decode_sample(predicted: i32, encoded_sample: u8, step_size: i32, mut difference: i32) -> i32 {
+    for bit in 0..6 {
+        if encoded_sample & (1 << bit) != 0 {
+            difference += step_size >> bit;
+        }
+    }
+
+    if encoded_sample & 0x40 != 0 {
+        (predicted - difference).max(-32768)
+    } else {
+        (predicted + difference).min(32767)
+    }
+}
+
+/// Decompresses 16-bit samples for `channel_count` interleaved channels, producing at most `expected_size` bytes.
+pub(crate) fn decompress(input: &[u8], expected_size: usize, channel_count: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(super::capacity_hint(input.len(), expected_size));
+    let write = |out: &mut Vec<u8>, sample: i32| {
+        if out.len() + 2 > expected_size {
+            return false;
+        }
+        out.extend_from_slice(&(sample as i16).to_le_bytes());
+        true
+    };
+
+    // The first byte is always zero, the second one is the bit shift.
+    if input.len() < 2 {
+        return out;
+    }
+    let bit_shift = input[1] as u32;
+    let mut input = &input[2..];
+
+    let mut predicted = [0i32; 2];
+    let mut step_indexes = [INITIAL_ADPCM_STEP_INDEX; 2];
+
+    for predicted in predicted.iter_mut().take(channel_count) {
+        if input.len() < 2 {
+            return out;
+        }
+        *predicted = i16::from_le_bytes([input[0], input[1]]) as i32;
+        input = &input[2..];
+
+        if !write(&mut out, *predicted) {
+            return out;
+        }
+    }
+
+    let mut channel = channel_count - 1;
+    for &encoded_sample in input {
+        channel = (channel + 1) % channel_count;
+
+        if encoded_sample & 0x80 != 0 {
+            match encoded_sample & 0x7F {
+                // Repeat the previous sample.
+                0 => {
+                    if step_indexes[channel] != 0 {
+                        step_indexes[channel] -= 1;
+                    }
+                    if !write(&mut out, predicted[channel]) {
+                        return out;
+                    }
+                }
+                // Adjust the step index, the next byte is for the same channel.
+                1 => {
+                    step_indexes[channel] = (step_indexes[channel] + 8).min(0x58);
+                    channel = (channel + 1) % channel_count;
+                }
+                2 => {
+                    step_indexes[channel] = (step_indexes[channel] - 8).max(0);
+                    channel = (channel + 1) % channel_count;
+                }
+                _ => {
+                    channel = (channel + 1) % channel_count;
+                }
+            }
+        } else {
+            let step_size = STEP_SIZE_TABLE[step_indexes[channel] as usize];
+            predicted[channel] = decode_sample(
+                predicted[channel],
+                encoded_sample,
+                step_size,
+                step_size.checked_shr(bit_shift).unwrap_or(0),
+            );
+            if !write(&mut out, predicted[channel]) {
+                return out;
+            }
+            step_indexes[channel] = next_step_index(step_indexes[channel], encoded_sample);
+        }
+    }
+
+    out
+}
@@ -0,0 +1,216 @@
+//! PKWare Data Compression Library "explode", ported from Mark Adler's blast.c.
+
+use crate::error::Error;
+use crate::error::Result;
+use std::sync::OnceLock;
+
+pub(super) const MAXBITS: usize = 13;
+
+// Bit lengths of the literal, length and distance codes, run-length encoded as in blast.c.
+const LITLEN: [u8; 98] = [
+    11, 124, 8, 7, 28, 7, 188, 13, 76, 4, 10, 8, 12, 10, 12, 10, 8, 23, 8, 9, 7, 6, 7, 8, 7, 6, 55,
+    8, 23, 24, 12, 11, 7, 9, 11, 12, 6, 7, 22, 5, 7, 24, 6, 11, 9, 6, 7, 22, 7, 11, 38, 7, 9, 8,
+    25, 11, 8, 11, 9, 12, 8, 12, 5, 38, 5, 38, 5, 11, 7, 5, 6, 21, 6, 10, 53, 8, 7, 24, 10, 27, 44,
+    253, 253, 253, 252, 252, 252, 13, 12, 45, 12, 45, 12, 61, 12, 45, 44, 173,
+];
+pub(super) const LENLEN: [u8; 6] = [2, 35, 36, 53, 38, 23];
+pub(super) const DISTLEN: [u8; 7] = [2, 20, 53, 230, 247, 151, 248];
+
+pub(super) const LEN_BASE: [u16; 16] = [3, 2, 4, 5, 6, 7, 8, 9, 10, 12, 16, 24, 40, 72, 136, 264];
+pub(super) const LEN_EXTRA: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+/// A canonical huffman code. Codes are stored bit-inverted in the stream, `decode` takes care of that.
+struct Huffman {
+    count: [u16; MAXBITS + 1],
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn construct(rep: &[u8]) -> Huffman {
+        let lengths = code_lengths(rep);
+
+        let mut count = [0u16; MAXBITS + 1];
+        for &len in &lengths {
+            count[len as usize] += 1;
+        }
+
+        let mut offs = [0u16; MAXBITS + 1];
+        for len in 1..MAXBITS {
+            offs[len + 1] = offs[len] + count[len];
+        }
+
+        let mut symbol = vec![0u16; lengths.len()];
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbol[offs[len as usize] as usize] = sym as u16;
+                offs[len as usize] += 1;
+            }
+        }
+
+        Huffman { count, symbol }
+    }
+}
+
+/// Expands run-length encoded code lengths: each byte is a length in the low nibble and a repeat count minus one in the high nibble.
+pub(super) fn code_lengths(rep: &[u8]) -> Vec<u8> {
+    let mut lengths = Vec::new();
+    for &x in rep {
+        let left = (x >> 4) as usize + 1;
+        lengths.extend(std::iter::repeat_n(x & 15, left));
+    }
+    lengths
+}
+
+struct Tables {
+    lit: Huffman,
+    len: Huffman,
+    dist: Huffman,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+
+    TABLES.get_or_init(|| Tables {
+        lit: Huffman::construct(&LITLEN),
+        len: Huffman::construct(&LENLEN),
+        dist: Huffman::construct(&DISTLEN),
+    })
+}
+
+struct BitReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    bitbuf: u32,
+    bitcnt: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, need: u32) -> Option<u32> {
+        let mut val = self.bitbuf;
+        while self.bitcnt < need {
+            let byte = *self.input.get(self.pos)?;
+            self.pos += 1;
+            val |= (byte as u32) << self.bitcnt;
+            self.bitcnt += 8;
+        }
+
+        self.bitbuf = val >> need;
+        self.bitcnt -= need;
+
+        Some(val & ((1 << need) - 1))
+    }
+
+    fn decode(&mut self, h: &Huffman) -> Option<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for len in 1..=MAXBITS {
+            code |= (self.bits(1)? ^ 1) as i32;
+            let count = h.count[len] as i32;
+            if code < first + count {
+                return Some(h.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+/// Decompresses `input`, stopping at the end code or once `expected_size` bytes have been produced.
+pub(crate) fn explode(input: &[u8], expected_size: usize) -> Result<Vec<u8>> {
+    let tables = tables();
+
+    let mut s = BitReader {
+        input,
+        pos: 0,
+        bitbuf: 0,
+        bitcnt: 0,
+    };
+
+    let Some(lit) = s.bits(8) else {
+        return Err(Error::Corrupt("PKWare stream is empty".to_owned()));
+    };
+    if lit > 1 {
+        return Err(Error::Corrupt(format!(
+            "PKWare stream has an invalid literal mode: {lit}"
+        )));
+    }
+
+    let Some(dict) = s.bits(8) else {
+        return Err(Error::Corrupt("PKWare stream is truncated".to_owned()));
+    };
+    if !(4..=6).contains(&dict) {
+        return Err(Error::Corrupt(format!(
+            "PKWare stream has an invalid dictionary size: {dict}"
+        )));
+    }
+
+    let mut out = Vec::with_capacity(super::capacity_hint(input.len(), expected_size));
+
+    while out.len() < expected_size {
+        let Some(is_copy) = s.bits(1) else {
+            break;
+        };
+
+        if is_copy == 1 {
+            let Some(symbol) = s.decode(&tables.len) else {
+                return Err(Error::Corrupt(
+                    "PKWare stream has an invalid length code".to_owned(),
+                ));
+            };
+            let Some(extra) = s.bits(LEN_EXTRA[symbol as usize] as u32) else {
+                break;
+            };
+            let len = LEN_BASE[symbol as usize] as usize + extra as usize;
+            if len == 519 {
+                break;
+            }
+
+            let symbol = if len == 2 { 2 } else { dict };
+            let Some(dist) = s.decode(&tables.dist) else {
+                return Err(Error::Corrupt(
+                    "PKWare stream has an invalid distance code".to_owned(),
+                ));
+            };
+            let Some(low) = s.bits(symbol) else {
+                break;
+            };
+            let dist = (((dist as u32) << symbol) + low + 1) as usize;
+
+            if dist > out.len() {
+                return Err(Error::Corrupt(format!(
+                    "PKWare stream refers back too far. distance: {dist}, position: {}",
+                    out.len()
+                )));
+            }
+
+            let start = out.len() - dist;
+            for i in 0..len.min(expected_size - out.len()) {
+                out.push(out[start + i]);
+            }
+        } else {
+            let literal = if lit == 1 {
+                let Some(x) = s.decode(&tables.lit) else {
+                    return Err(Error::Corrupt(
+                        "PKWare stream has an invalid literal code".to_owned(),
+                    ));
+                };
+                x as u32
+            } else {
+                let Some(x) = s.bits(8) else {
+                    break;
+                };
+                x
+            };
+
+            out.push(literal as u8);
+        }
+    }
+
+    Ok(out)
+}
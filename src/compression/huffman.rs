@@ -0,0 +1,275 @@
+//! Storm's adaptive huffman coder, used together with ADPCM for WAV files stored in maps.
+//!
+//! The first byte of a stream picks a table of initial byte weights. Each byte is coded by its
+//! path from the root, starting with the bit nearest the root, and the tree is rebalanced after
+//! every byte so that it keeps fitting the data. Bytes with no weight in the table are escaped
+//! with 0x101 followed by the byte itself, and 0x100 ends the stream.
+//!
+//! Like Storm, the tree is kept as a list of items sorted by weight, heaviest first. The two
+//! children of an item are always next to each other in it, the lighter one second.
+
+use crate::error::Error;
+use crate::error::Result;
+
+const END_OF_STREAM: u16 = 0x100;
+const NEW_VALUE: u16 = 0x101;
+
+/// Storm never holds more items than this, enough for every byte and both control values.
+const MAX_ITEMS: usize = 0x203;
+
+/// The initial weights of compression type 0: every byte once, and 0 and 1 ten times.
+const WEIGHTS_0: [u8; 0x100] = {
+    let mut weights = [1; 0x100];
+    weights[0] = 0x0A;
+    weights[1] = 0x0A;
+    weights
+};
+
+/// The weight tables by compression type. Storm has nine, and only type 0's is ported so far.
+/// WAVs use types 6 to 8, picked by their ADPCM quality.
+fn weights(compression_type: u8) -> Result<&'static [u8; 0x100]> {
+    match compression_type {
+        0 => Ok(&WEIGHTS_0),
+        1..=8 => Err(Error::Unsupported(format!(
+            "Huffman compression type {compression_type}"
+        ))),
+        _ => Err(Error::Corrupt(format!(
+            "Huffman compression type {compression_type}"
+        ))),
+    }
+}
+
+struct Item {
+    value: u16,
+    weight: u32,
+    parent: Option<usize>,
+    /// The lighter child, the heavier one is right before it in the list.
+    child_lo: Option<usize>,
+}
+
+struct Tree {
+    items: Vec<Item>,
+    /// Indices into `items`, heaviest first.
+    list: Vec<usize>,
+    /// Where each item is in `list`.
+    position: Vec<usize>,
+    by_value: [Option<usize>; 0x102],
+}
+
+impl Tree {
+    fn build(weights: &[u8; 0x100]) -> Tree {
+        let mut tree = Tree {
+            items: Vec::with_capacity(MAX_ITEMS),
+            list: Vec::with_capacity(MAX_ITEMS),
+            position: Vec::with_capacity(MAX_ITEMS),
+            by_value: [None; 0x102],
+        };
+
+        // Storm puts an item at the front if it is at least as heavy as anything before it, and
+        // otherwise after the last item that is at least as heavy.
+        let mut max_weight = 0;
+        let mut place = |tree: &mut Tree, item: usize, search_from: usize| {
+            let weight = tree.items[item].weight;
+            if weight >= max_weight {
+                max_weight = weight;
+                tree.insert(item, 0);
+            } else {
+                let at = tree.list[..search_from]
+                    .iter()
+                    .rposition(|&x| tree.items[x].weight >= weight)
+                    .map_or(0, |x| x + 1);
+                tree.insert(item, at);
+            }
+        };
+
+        for (value, &weight) in weights.iter().enumerate() {
+            if weight != 0 {
+                let item = tree.new_item(value as u16, weight as u32);
+                tree.by_value[value] = Some(item);
+                let end = tree.list.len();
+                place(&mut tree, item, end);
+            }
+        }
+
+        for value in [END_OF_STREAM, NEW_VALUE] {
+            let item = tree.new_item(value, 1);
+            tree.by_value[value as usize] = Some(item);
+            tree.insert(item, tree.list.len());
+        }
+
+        // Pair items off from the lightest up, each pair getting a parent.
+        let mut lo_position = tree.list.len() - 1;
+        while lo_position > 0 {
+            let (lo, hi) = (tree.list[lo_position], tree.list[lo_position - 1]);
+            let parent = tree.new_item(0, tree.items[lo].weight + tree.items[hi].weight);
+            tree.items[lo].parent = Some(parent);
+            tree.items[hi].parent = Some(parent);
+            tree.items[parent].child_lo = Some(lo);
+            place(&mut tree, parent, lo_position - 1);
+
+            if tree.position[hi] == 0 {
+                break;
+            }
+            lo_position = tree.position[hi] - 1;
+        }
+
+        tree
+    }
+
+    fn new_item(&mut self, value: u16, weight: u32) -> usize {
+        self.items.push(Item {
+            value,
+            weight,
+            parent: None,
+            child_lo: None,
+        });
+        self.position.push(usize::MAX);
+        self.items.len() - 1
+    }
+
+    fn insert(&mut self, item: usize, at: usize) {
+        self.list.insert(at, item);
+        for (position, &x) in self.list.iter().enumerate().skip(at) {
+            self.position[x] = position;
+        }
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u16> {
+        let mut item = self.list[0];
+        while let Some(lo) = self.items[item].child_lo {
+            item = if bits.next()? {
+                self.list[self.position[lo] - 1]
+            } else {
+                lo
+            };
+        }
+        Ok(self.items[item].value)
+    }
+
+    /// Splits the lightest item into itself and `value`, which starts out with no weight.
+    fn add_value(&mut self, value: u8) -> Result<()> {
+        if self.items.len() + 2 > MAX_ITEMS || self.by_value[value as usize].is_some() {
+            return Err(Error::Corrupt(format!(
+                "Huffman stream adds {value:#04x} twice"
+            )));
+        }
+
+        let last = *self.list.last().unwrap();
+        let hi = self.new_item(self.items[last].value, self.items[last].weight);
+        let lo = self.new_item(value as u16, 0);
+        self.items[hi].parent = Some(last);
+        self.items[lo].parent = Some(last);
+        self.items[last].child_lo = Some(lo);
+        self.by_value[self.items[hi].value as usize] = Some(hi);
+        self.by_value[value as usize] = Some(lo);
+        self.insert(hi, self.list.len());
+        self.insert(lo, self.list.len());
+
+        self.increment(lo);
+        Ok(())
+    }
+
+    /// Adds one to the weight of `item` and everything above it. An item that gets heavier than
+    /// the ones before it trades places with the first of those, subtree and all.
+    fn increment(&mut self, item: usize) {
+        let mut item = Some(item);
+        while let Some(x) = item {
+            self.items[x].weight += 1;
+            let weight = self.items[x].weight;
+
+            let mut leader = self.position[x];
+            while leader > 0 && self.items[self.list[leader - 1]].weight < weight {
+                leader -= 1;
+            }
+            if leader != self.position[x] {
+                self.swap(x, self.list[leader]);
+            }
+
+            item = self.items[x].parent;
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        let (parent_a, parent_b) = (self.items[a].parent, self.items[b].parent);
+        let a_is_lo = parent_a.is_some_and(|x| self.items[x].child_lo == Some(a));
+        let b_is_lo = parent_b.is_some_and(|x| self.items[x].child_lo == Some(b));
+        if a_is_lo {
+            self.items[parent_a.unwrap()].child_lo = Some(b);
+        }
+        if b_is_lo {
+            self.items[parent_b.unwrap()].child_lo = Some(a);
+        }
+        self.items[a].parent = parent_b;
+        self.items[b].parent = parent_a;
+
+        let (position_a, position_b) = (self.position[a], self.position[b]);
+        self.list.swap(position_a, position_b);
+        self.position[a] = position_b;
+        self.position[b] = position_a;
+    }
+}
+
+/// Reads bits from the lowest of each byte up.
+struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn next(&mut self) -> Result<bool> {
+        let byte = self
+            .input
+            .get(self.position / 8)
+            .ok_or_else(|| Error::Corrupt("Huffman stream is truncated".to_owned()))?;
+        let bit = byte >> (self.position % 8) & 1 != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let mut byte = 0;
+        for i in 0..8 {
+            byte |= (self.next()? as u8) << i;
+        }
+        Ok(byte)
+    }
+}
+
+pub(super) fn decompress(input: &[u8], expected_size: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(super::capacity_hint(input.len(), expected_size));
+    if expected_size == 0 {
+        return Ok(out);
+    }
+
+    let mut bits = BitReader { input, position: 0 };
+    let compression_type = bits.byte()?;
+    let mut tree = Tree::build(weights(compression_type)?);
+    // Type 0 counts every byte, the others only count a byte when it is first added.
+    let count_every_byte = compression_type == 0;
+
+    loop {
+        let value = match tree.decode(&mut bits)? {
+            END_OF_STREAM => break,
+            NEW_VALUE => {
+                let value = bits.byte()?;
+                tree.add_value(value)?;
+                if !count_every_byte {
+                    tree.increment(tree.by_value[value as usize].unwrap());
+                }
+                value
+            }
+            value => value as u8,
+        };
+
+        out.push(value);
+        if out.len() >= expected_size {
+            break;
+        }
+
+        if count_every_byte {
+            tree.increment(tree.by_value[value as usize].unwrap());
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,176 @@
+//! PKWare Data Compression Library "implode", the inverse of `explode`. Literals are always
+//! written uncoded and the dictionary is always 4096 bytes, both of which every exploder accepts.
+
+use super::explode::code_lengths;
+use super::explode::DISTLEN;
+use super::explode::LENLEN;
+use super::explode::LEN_BASE;
+use super::explode::LEN_EXTRA;
+use super::explode::MAXBITS;
+use std::sync::OnceLock;
+
+const DICT_BITS: u32 = 6;
+const WINDOW: usize = 64 << DICT_BITS;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 518;
+/// How many earlier positions with the same 3 byte prefix are tried for each match.
+const MAX_CHAIN: usize = 64;
+/// The length that marks the end of the stream.
+const END_LENGTH: usize = 519;
+
+/// Canonical codes, as `(code, length)` per symbol, in the order `explode` assigns them.
+fn codes(rep: &[u8]) -> Vec<(u16, u8)> {
+    let lengths = code_lengths(rep);
+    let mut codes = vec![(0, 0); lengths.len()];
+
+    let mut code = 0u16;
+    for len in 1..=MAXBITS as u8 {
+        for (symbol, _) in lengths.iter().enumerate().filter(|(_, &x)| x == len) {
+            codes[symbol] = (code, len);
+            code += 1;
+        }
+        code <<= 1;
+    }
+
+    codes
+}
+
+struct Codes {
+    len: Vec<(u16, u8)>,
+    dist: Vec<(u16, u8)>,
+}
+
+fn tables() -> &'static Codes {
+    static CODES: OnceLock<Codes> = OnceLock::new();
+
+    CODES.get_or_init(|| Codes {
+        len: codes(&LENLEN),
+        dist: codes(&DISTLEN),
+    })
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bitbuf: u32,
+    bitcnt: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, least significant first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bitbuf |= value << self.bitcnt;
+        self.bitcnt += count;
+        while self.bitcnt >= 8 {
+            self.out.push(self.bitbuf as u8);
+            self.bitbuf >>= 8;
+            self.bitcnt -= 8;
+        }
+    }
+
+    /// Writes a huffman code most significant bit first and inverted, which is how `explode` reads them.
+    fn code(&mut self, (code, len): (u16, u8)) {
+        for i in (0..len).rev() {
+            self.bits(((code >> i) & 1) as u32 ^ 1, 1);
+        }
+    }
+
+    fn length(&mut self, len: usize) {
+        let symbol = (0..LEN_BASE.len())
+            .find(|&i| {
+                let base = LEN_BASE[i] as usize;
+                len >= base && len < base + (1 << LEN_EXTRA[i])
+            })
+            .unwrap();
+
+        self.bits(1, 1);
+        self.code(tables().len[symbol]);
+        self.bits(
+            (len - LEN_BASE[symbol] as usize) as u32,
+            LEN_EXTRA[symbol] as u32,
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bitcnt > 0 {
+            self.out.push(self.bitbuf as u8);
+        }
+        self.out
+    }
+}
+
+fn hash(data: &[u8]) -> usize {
+    ((data[0] as usize) << 8 ^ (data[1] as usize) << 4 ^ data[2] as usize) & 0xFFF
+}
+
+/// Compresses `input` into a stream `explode` can read, end marker included.
+pub(crate) fn implode(input: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter {
+        out: vec![0, DICT_BITS as u8],
+        bitbuf: 0,
+        bitcnt: 0,
+    };
+
+    // Most recent position for each hash, and the previous position with the same hash for
+    // each position in the window.
+    let mut head = vec![usize::MAX; 0x1000];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + MIN_MATCH <= input.len() {
+            let h = hash(&input[pos..]);
+            prev[pos % WINDOW] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < input.len() {
+        let max = MAX_MATCH.min(input.len() - pos);
+        let (mut best_len, mut best_dist) = (0, 0);
+
+        if max >= MIN_MATCH {
+            let mut candidate = head[hash(&input[pos..])];
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || pos - candidate > WINDOW {
+                    break;
+                }
+
+                let len = (0..max)
+                    .take_while(|&i| input[candidate + i] == input[pos + i])
+                    .count();
+                if len > best_len {
+                    (best_len, best_dist) = (len, pos - candidate);
+                    if len == max {
+                        break;
+                    }
+                }
+
+                let next = prev[candidate % WINDOW];
+                if next >= candidate {
+                    break;
+                }
+                candidate = next;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            w.length(best_len);
+            let dist = (best_dist - 1) as u32;
+            w.code(tables().dist[(dist >> DICT_BITS) as usize]);
+            w.bits(dist & ((1 << DICT_BITS) - 1), DICT_BITS);
+
+            for i in pos..pos + best_len {
+                insert(i, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            w.bits(0, 1);
+            w.bits(input[pos] as u32, 8);
+
+            insert(pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+
+    w.length(END_LENGTH);
+    w.finish()
+}
@@ -0,0 +1,105 @@
+//! Compression and decompression of MPQ sectors. Each compressed sector starts with a mask byte
+//! telling which algorithms were applied, and they are undone in the reverse order they were
+//! applied in.
+
+mod adpcm;
+mod explode;
+mod huffman;
+mod implode;
+
+use crate::error::Error;
+use crate::error::Result;
+use std::io::Read;
+use std::io::Write;
+
+pub(crate) const MPQ_COMPRESSION_HUFFMANN: u8 = 0x01;
+pub(crate) const MPQ_COMPRESSION_ZLIB: u8 = 0x02;
+pub(crate) const MPQ_COMPRESSION_PKWARE: u8 = 0x08;
+pub(crate) const MPQ_COMPRESSION_BZIP2: u8 = 0x10;
+pub(crate) const MPQ_COMPRESSION_ADPCM_MONO: u8 = 0x40;
+pub(crate) const MPQ_COMPRESSION_ADPCM_STEREO: u8 = 0x80;
+
+/// The most that is reserved up front for a decompressed sector, whatever its declared size.
+const MAX_CAPACITY_HINT: usize = 1 << 20;
+
+/// How much to reserve for output that should be `expected_size` bytes long. The size comes from
+/// the block table, so it is only trusted as far as the input could plausibly expand. The output
+/// still grows past this if it has to.
+fn capacity_hint(input_len: usize, expected_size: usize) -> usize {
+    expected_size
+        .min(input_len.saturating_mul(8))
+        .min(MAX_CAPACITY_HINT)
+}
+
+/// Undoes an implode-only sector (`MPQ_FILE_IMPLODE`), which has no mask byte.
+pub(crate) fn explode(input: &[u8], expected_size: usize) -> Result<Vec<u8>> {
+    explode::explode(input, expected_size)
+}
+
+/// Implodes a sector for `MPQ_FILE_IMPLODE`. There is no mask byte.
+pub(crate) fn implode(input: &[u8]) -> Vec<u8> {
+    implode::implode(input)
+}
+
+/// Compresses a sector for `MPQ_FILE_COMPRESS` with zlib, mask byte included.
+pub(crate) fn compress_zlib(input: &[u8]) -> Vec<u8> {
+    let mut encoder =
+        flate2::write::ZlibEncoder::new(vec![MPQ_COMPRESSION_ZLIB], flate2::Compression::best());
+    encoder.write_all(input).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Undoes a multi-compressed sector (`MPQ_FILE_COMPRESS`).
+pub(crate) fn decompress(input: &[u8], expected_size: usize) -> Result<Vec<u8>> {
+    let Some((&mask, data)) = input.split_first() else {
+        return Err(Error::Corrupt("Compressed sector is empty".to_owned()));
+    };
+
+    let known = MPQ_COMPRESSION_HUFFMANN
+        | MPQ_COMPRESSION_ZLIB
+        | MPQ_COMPRESSION_PKWARE
+        | MPQ_COMPRESSION_BZIP2
+        | MPQ_COMPRESSION_ADPCM_MONO
+        | MPQ_COMPRESSION_ADPCM_STEREO;
+    if mask & !known != 0 {
+        return Err(Error::Unsupported(format!("Compression mask: {mask:#04x}")));
+    }
+
+    let mut data = data.to_vec();
+
+    if mask & MPQ_COMPRESSION_BZIP2 != 0 {
+        let mut out = Vec::with_capacity(capacity_hint(data.len(), expected_size));
+        bzip2::read::BzDecoder::new(data.as_slice())
+            .take(expected_size as u64)
+            .read_to_end(&mut out)
+            .map_err(|err| Error::Corrupt(format!("bzip2: {err}")))?;
+        data = out;
+    }
+
+    if mask & MPQ_COMPRESSION_PKWARE != 0 {
+        data = explode::explode(&data, expected_size)?;
+    }
+
+    if mask & MPQ_COMPRESSION_ZLIB != 0 {
+        let mut out = Vec::with_capacity(capacity_hint(data.len(), expected_size));
+        flate2::read::ZlibDecoder::new(data.as_slice())
+            .take(expected_size as u64)
+            .read_to_end(&mut out)
+            .map_err(|err| Error::Corrupt(format!("zlib: {err}")))?;
+        data = out;
+    }
+
+    if mask & MPQ_COMPRESSION_HUFFMANN != 0 {
+        data = huffman::decompress(&data, expected_size)?;
+    }
+
+    if mask & MPQ_COMPRESSION_ADPCM_STEREO != 0 {
+        data = adpcm::decompress(&data, expected_size, 2);
+    }
+
+    if mask & MPQ_COMPRESSION_ADPCM_MONO != 0 {
+        data = adpcm::decompress(&data, expected_size, 1);
+    }
+
+    Ok(data)
+}
@@ -0,0 +1,117 @@
+//! The MPQ hashing and encryption primitives, shared by everything that has to read or write raw archive data.
+
+use std::sync::OnceLock;
+
+pub(crate) const MPQ_HASH_TABLE_INDEX: u32 = 0x000;
+pub(crate) const MPQ_HASH_NAME_A: u32 = 0x100;
+pub(crate) const MPQ_HASH_NAME_B: u32 = 0x200;
+pub(crate) const MPQ_HASH_FILE_KEY: u32 = 0x300;
+
+fn crypt_table() -> &'static [u32; 0x500] {
+    static CRYPT_TABLE: OnceLock<[u32; 0x500]> = OnceLock::new();
+
+    CRYPT_TABLE.get_or_init(|| {
+        let mut table = [0u32; 0x500];
+        let mut seed: u32 = 0x00100001;
+
+        for index1 in 0..0x100 {
+            let mut index2 = index1;
+            for _ in 0..5 {
+                seed = (seed * 125 + 3) % 0x2AAAAB;
+                let temp1 = (seed & 0xFFFF) << 0x10;
+
+                seed = (seed * 125 + 3) % 0x2AAAAB;
+                let temp2 = seed & 0xFFFF;
+
+                table[index2] = temp1 | temp2;
+                index2 += 0x100;
+            }
+        }
+
+        table
+    })
+}
+
+/// Hashes a file name the way Storm does: case-insensitive, with `/` treated as `\`.
+pub(crate) fn hash_string(name: &[u8], hash_type: u32) -> u32 {
+    let table = crypt_table();
+
+    let mut seed1: u32 = 0x7FED7FED;
+    let mut seed2: u32 = 0xEEEEEEEE;
+
+    for &ch in name {
+        let ch = match ch {
+            b'/' => b'\\',
+            ch => ch.to_ascii_uppercase(),
+        } as u32;
+
+        seed1 = table[(hash_type + ch) as usize] ^ seed1.wrapping_add(seed2);
+        seed2 = ch
+            .wrapping_add(seed1)
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+    }
+
+    seed1
+}
+
+/// The key a file is encrypted with. Only the part of the name after the last `\` counts.
+pub(crate) fn file_key(name: &[u8], byte_offset: u32, file_size: u32, fix_key: bool) -> u32 {
+    let plain_name = name
+        .rsplit(|&ch| ch == b'\\' || ch == b'/')
+        .next()
+        .unwrap_or(name);
+
+    let key = hash_string(plain_name, MPQ_HASH_FILE_KEY);
+    if fix_key {
+        key.wrapping_add(byte_offset) ^ file_size
+    } else {
+        key
+    }
+}
+
+/// Decrypts whole little-endian dwords in place. Trailing bytes that don't make up a dword are left as they are.
+pub(crate) fn decrypt_block(data: &mut [u8], key: u32) {
+    let table = crypt_table();
+
+    let mut seed1 = key;
+    let mut seed2: u32 = 0xEEEEEEEE;
+
+    for chunk in data.chunks_exact_mut(4) {
+        seed2 = seed2.wrapping_add(table[0x400 + (seed1 & 0xFF) as usize]);
+
+        let value = u32::from_le_bytes(chunk.try_into().unwrap()) ^ seed1.wrapping_add(seed2);
+
+        seed1 = ((!seed1 << 0x15).wrapping_add(0x11111111)) | (seed1 >> 0x0B);
+        seed2 = value
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Encrypts whole little-endian dwords in place, the inverse of `decrypt_block`.
+pub(crate) fn encrypt_block(data: &mut [u8], key: u32) {
+    let table = crypt_table();
+
+    let mut seed1 = key;
+    let mut seed2: u32 = 0xEEEEEEEE;
+
+    for chunk in data.chunks_exact_mut(4) {
+        seed2 = seed2.wrapping_add(table[0x400 + (seed1 & 0xFF) as usize]);
+
+        let value = u32::from_le_bytes(chunk.try_into().unwrap());
+        let encrypted = value ^ seed1.wrapping_add(seed2);
+
+        seed1 = ((!seed1 << 0x15).wrapping_add(0x11111111)) | (seed1 >> 0x0B);
+        seed2 = value
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+
+        chunk.copy_from_slice(&encrypted.to_le_bytes());
+    }
+}
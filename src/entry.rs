@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+pub(crate) const MPQ_FILE_IMPLODE: u32 = 0x00000100;
+pub(crate) const MPQ_FILE_COMPRESS: u32 = 0x00000200;
+pub(crate) const MPQ_FILE_ENCRYPTED: u32 = 0x00010000;
+pub(crate) const MPQ_FILE_FIX_KEY: u32 = 0x00020000;
+pub(crate) const MPQ_FILE_SINGLE_UNIT: u32 = 0x01000000;
+pub(crate) const MPQ_FILE_DELETE_MARKER: u32 = 0x02000000;
+pub(crate) const MPQ_FILE_SECTOR_CRC: u32 = 0x04000000;
+pub(crate) const MPQ_FILE_EXISTS: u32 = 0x80000000;
+
+/// The locales `Resolution::LocaleProbe` tries, in order. Some maps put decoy files (usually
+/// scenario.chk) at other locales, and trying a lot of them finds the real one.
+///
+/// StarCraft itself takes the first matching entry in the hash chain, which is what
+/// `Resolution::HashChain` does. The default should switch over once that has proven itself on
+/// more maps than the test corpus.
+pub(crate) const LOCALES: [u16; 14] = [
+    0x404, 0x405, 0x407, 0x409, 0x40a, 0x40c, 0x410, 0x411, 0x412, 0x415, 0x416, 0x419, 0x809, 0,
+];
+
+/// One file stored in an archive, as described by its hash table and block table entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MpqEntry {
+    /// `None` when the name is not in the listfile and could not be recovered.
+    pub name: Option<String>,
+    pub hash_index: u32,
+    pub block_index: u32,
+    pub locale: u16,
+    pub platform: u8,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub flags: MpqEntryFlags,
+}
+
+/// The block table flags of an entry, both raw and decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MpqEntryFlags {
+    pub raw: u32,
+    pub compressed: bool,
+    pub imploded: bool,
+    pub encrypted: bool,
+    pub fix_key: bool,
+    pub single_unit: bool,
+    pub delete_marker: bool,
+}
+
+impl MpqEntryFlags {
+    pub fn from_raw(raw: u32) -> MpqEntryFlags {
+        MpqEntryFlags {
+            raw,
+            compressed: raw & MPQ_FILE_COMPRESS != 0,
+            imploded: raw & MPQ_FILE_IMPLODE != 0,
+            encrypted: raw & MPQ_FILE_ENCRYPTED != 0,
+            fix_key: raw & MPQ_FILE_FIX_KEY != 0,
+            single_unit: raw & MPQ_FILE_SINGLE_UNIT != 0,
+            delete_marker: raw & MPQ_FILE_DELETE_MARKER != 0,
+        }
+    }
+}
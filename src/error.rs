@@ -0,0 +1,101 @@
+use std::fmt;
+
+/// Everything that can go wrong while reading an archive.
+///
+/// This implements `std::error::Error`, so it converts into `anyhow::Error` with `?`.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// `MpqArchive` can't hand the file name to StormLib: it is not UTF-8 or it contains a NUL.
+    /// The pure-Rust reader takes any name.
+    #[cfg(feature = "stormlib")]
+    InvalidFilename(String),
+    /// The data is not an MPQ archive.
+    NotAnMpq(String),
+    /// There is no usable entry for this file in the archive.
+    FileNotFound(String),
+    /// There is an entry for this file, but it is stored at a different locale than the one asked
+    /// for. Map protectors plant these to throw off extractors.
+    DecoyLocale {
+        filename: String,
+        requested: u32,
+        found: u32,
+    },
+    /// The file is larger than we are willing to read.
+    FileTooBig {
+        filename: String,
+        size: u64,
+    },
+    /// The archive tables or the file data are damaged.
+    Corrupt(String),
+    /// The archive uses a format version or compression method that is not supported.
+    Unsupported(String),
+    Io(std::io::Error),
+    /// A StormLib call made by `MpqArchive` failed with an error code that has no more specific
+    /// variant.
+    #[cfg(feature = "stormlib")]
+    StormLib {
+        function: &'static str,
+        code: u32,
+    },
+    /// The code handling the file panicked, with the panic's message. Only `batch::process`
+    /// catches panics.
+    Panicked(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "stormlib")]
+            Error::InvalidFilename(filename) => write!(f, "Invalid filename: {filename}"),
+            Error::NotAnMpq(filename) => write!(f, "Not an MPQ archive: {filename}"),
+            Error::FileNotFound(filename) => write!(f, "File not found in archive: {filename}"),
+            Error::DecoyLocale {
+                filename,
+                requested,
+                found,
+            } => write!(
+                f,
+                "File is stored at a different locale. filename: {filename}, requested: {requested:#x}, found: {found:#x}"
+            ),
+            Error::FileTooBig { filename, size } => {
+                write!(f, "File is too big. filename: {filename}, size: {size}")
+            }
+            Error::Corrupt(reason) => write!(f, "Archive is corrupt: {reason}"),
+            Error::Unsupported(reason) => write!(f, "Unsupported: {reason}"),
+            Error::Io(err) => write!(f, "I/O error: {err}"),
+            #[cfg(feature = "stormlib")]
+            Error::StormLib { function, code } => {
+                write!(f, "{function} failed. GetLastError: {code}")
+            }
+            Error::Panicked(message) => write!(f, "Panicked: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+/// Lets `MpqFile` report archive errors through `std::io::Read`.
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        match err {
+            Error::Io(err) => err,
+            err => std::io::Error::other(err),
+        }
+    }
+}
@@ -1,7 +1,48 @@
+pub mod batch;
+pub mod chk;
+mod compression;
+mod crypto;
+mod entry;
+mod error;
+#[cfg(feature = "stormlib")]
 mod mpq;
+mod protection;
+pub mod reader;
+pub mod render;
+mod sanitize;
+mod summary;
+pub mod trigedit;
+pub mod writer;
 
 #[cfg(test)]
 mod test;
 
-pub use mpq::get_chk_from_mpq_filename;
-pub use mpq::get_chk_from_mpq_in_memory;
+pub use entry::MpqEntry;
+pub use entry::MpqEntryFlags;
+pub use error::Error;
+#[cfg(feature = "stormlib")]
+pub use mpq::extract_file;
+#[cfg(feature = "stormlib")]
+pub use mpq::MpqArchive;
+pub use protection::analyze_protection;
+pub use protection::ChkCandidate;
+pub use protection::ProtectionReport;
+pub use protection::ProtectionTrick;
+pub use reader::get_chk_from_mpq_filename;
+pub use reader::get_chk_from_mpq_in_memory;
+pub use reader::MpqFile;
+pub use reader::MpqReader;
+pub use reader::ReaderOptions;
+pub use reader::Resolution;
+pub use reader::ResolvedEntry;
+pub use sanitize::sanitize;
+pub use summary::summarize;
+pub use summary::GameType;
+pub use summary::MapSummary;
+pub use summary::PlayerSlot;
+pub use summary::UnitCount;
+pub use writer::replace_file;
+pub use writer::Compression;
+pub use writer::FileOptions;
+pub use writer::MpqWriter;
+pub use writer::WriterOptions;
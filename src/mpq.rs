@@ -1,105 +1,282 @@
-use anyhow::anyhow;
-use anyhow::bail;
-use anyhow::Result;
+use crate::entry::MpqEntry;
+use crate::entry::MpqEntryFlags;
+use crate::entry::LOCALES;
+use crate::error::Error;
+use crate::error::Result;
 use lazy_static::lazy_static;
 use scopeguard::defer;
 use std::ffi::c_void;
+use std::ffi::CStr;
 use std::ffi::CString;
-use std::fs::remove_file;
-use std::fs::File;
-use std::io::Write;
 use std::mem::size_of;
+use std::mem::zeroed;
 use std::path::Path;
+use std::ptr::null;
+use std::ptr::null_mut;
 use std::sync::Mutex;
+use stormlib_bindings::_SFileInfoClass_SFileInfoLocale;
+use stormlib_bindings::_SFileInfoClass_SFileMpqHashTable;
+use stormlib_bindings::_SFileInfoClass_SFileMpqHashTableSize;
 use stormlib_bindings::SFileCloseArchive;
 use stormlib_bindings::SFileCloseFile;
+use stormlib_bindings::SFileFindClose;
+use stormlib_bindings::SFileFindFirstFile;
+use stormlib_bindings::SFileFindNextFile;
 use stormlib_bindings::SFileGetFileInfo;
 use stormlib_bindings::SFileGetFileSize;
 use stormlib_bindings::SFileOpenFileEx;
 use stormlib_bindings::SFileReadFile;
 use stormlib_bindings::SFileSetLocale;
-use stormlib_bindings::_SFileInfoClass_SFileInfoLocale;
+use stormlib_bindings::ERROR_BAD_FORMAT;
+use stormlib_bindings::ERROR_FILE_CORRUPT;
+use stormlib_bindings::ERROR_FILE_NOT_FOUND;
 use stormlib_bindings::ERROR_HANDLE_EOF;
+use stormlib_bindings::ERROR_NO_MORE_FILES;
+use stormlib_bindings::SFILE_FIND_DATA;
 use stormlib_bindings::SFILE_INVALID_SIZE;
 use stormlib_bindings::STREAM_FLAG_READ_ONLY;
 use stormlib_bindings::{GetLastError, SFileOpenArchive, HANDLE};
-use tracing::info;
 use tracing::{error, instrument};
-use uuid::Uuid;
-
-#[instrument(level = "trace", skip_all)]
-pub fn get_chk_from_mpq_filename<T: AsRef<Path>>(filename: T) -> Result<Vec<u8>> {
-    info!(
-        "Extracting scenario.chk. filename: {}",
-        filename.as_ref().to_string_lossy()
-    );
-
-    lazy_static! {
-        // This is really not the rust way to do things but stormlib_bindings is internally not threadsafe so what we can do.
-        static ref LOCK: Mutex<()> = Mutex::new(());
+
+lazy_static! {
+    // This is really not the rust way to do things but stormlib_bindings is internally not
+    // threadsafe so what we can do. Extraction that needs to scale across threads should go
+    // through `MpqReader` instead, which has no global state.
+    static ref LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An open StormLib archive handle. The archive is closed when this is dropped.
+///
+/// StormLib is not thread safe, so every call on every archive in the process is serialised
+/// behind one lock.
+pub struct MpqArchive {
+    handle: HANDLE,
+    filename: String,
+    max_file_size: Option<u64>,
+}
+
+// The handle is only ever used while holding LOCK.
+unsafe impl Send for MpqArchive {}
+
+impl MpqArchive {
+    #[instrument(level = "trace", skip_all)]
+    pub fn open<T: AsRef<Path>>(filename: T) -> Result<MpqArchive> {
+        let filename = filename.as_ref().to_str().ok_or_else(|| {
+            Error::InvalidFilename(filename.as_ref().to_string_lossy().into_owned())
+        })?;
+
+        let cstr =
+            CString::new(filename).map_err(|_| Error::InvalidFilename(filename.to_owned()))?;
+
+        let _lock = LOCK.lock().unwrap();
+        unsafe {
+            let mut mpq_handle = null_mut() as HANDLE;
+            if !SFileOpenArchive(
+                cstr.as_ptr(),
+                0,
+                STREAM_FLAG_READ_ONLY,
+                &mut mpq_handle as *mut _,
+            ) {
+                let code = GetLastError();
+                return Err(match code {
+                    ERROR_BAD_FORMAT => Error::NotAnMpq(filename.to_owned()),
+                    ERROR_FILE_CORRUPT => {
+                        Error::Corrupt(format!("SFileOpenArchive failed. filename: {filename}"))
+                    }
+                    _ => Error::Io(std::io::Error::from_raw_os_error(code as i32)),
+                });
+            }
+
+            Ok(MpqArchive {
+                handle: mpq_handle,
+                filename: filename.to_owned(),
+                max_file_size: None,
+            })
+        }
     }
 
-    let cstr = CString::new(
-        filename
-            .as_ref()
-            .to_str()
-            .ok_or(anyhow!("Could not convert filename to str"))?,
-    )?;
+    /// Refuse to read files whose declared size is larger than `max_file_size`, before allocating
+    /// anything for them.
+    pub fn set_max_file_size(&mut self, max_file_size: Option<u64>) {
+        self.max_file_size = max_file_size;
+    }
 
-    let _lock = LOCK.lock().unwrap();
-    unsafe {
-        let mut mpq_handle = 0 as HANDLE;
-        if !SFileOpenArchive(
-            cstr.as_ptr(),
-            0,
-            STREAM_FLAG_READ_ONLY,
-            &mut mpq_handle as *mut _,
-        ) {
-            bail!(
-                "SFileOpenArchive. GetLastError: {}, filename: {}",
-                GetLastError(),
-                filename.as_ref().to_string_lossy()
-            );
+    /// Reads a file out of the archive, skipping entries planted at unexpected locales.
+    #[instrument(level = "trace", skip(self))]
+    pub fn read_file(&self, filename: &str) -> Result<Vec<u8>> {
+        let _lock = LOCK.lock().unwrap();
+
+        let mut error = Error::FileNotFound(filename.to_owned());
+
+        for locale in LOCALES {
+            match self.read_file_with_locale(filename, locale as u32) {
+                Ok(x) => return Ok(x),
+                Err(Error::FileNotFound(_) | Error::DecoyLocale { .. }) => {}
+                Err(err) => {
+                    if matches!(error, Error::FileNotFound(_)) {
+                        error = err;
+                    }
+                }
+            }
         }
 
-        defer! {
-            if !SFileCloseArchive(mpq_handle) {
-                error!(
-                    "{:?}",
-                    anyhow!(
-                        "SFileCloseArchive. GetLastError: {}, filename: {}",
+        Err(error)
+    }
+
+    /// Returns true if `read_file` would find an entry for `filename`.
+    #[instrument(level = "trace", skip(self))]
+    pub fn has_file(&self, filename: &str) -> bool {
+        let _lock = LOCK.lock().unwrap();
+
+        LOCALES
+            .into_iter()
+            .any(|locale| self.with_file(filename, locale as u32, |_| Ok(())).is_ok())
+    }
+
+    /// Lists every file in the archive. Names come from the archive's `(listfile)`, entries that
+    /// are not named there have `name: None`.
+    #[instrument(level = "trace", skip(self))]
+    pub fn entries(&self) -> Result<impl Iterator<Item = MpqEntry>> {
+        let _lock = LOCK.lock().unwrap();
+
+        let hash_table = self.hash_table()?;
+
+        let mut entries = Vec::new();
+        unsafe {
+            let mask = CString::new("*").unwrap();
+            let mut find_data: SFILE_FIND_DATA = zeroed();
+
+            let find_handle =
+                SFileFindFirstFile(self.handle, mask.as_ptr(), &mut find_data, null());
+            if find_handle.is_null() {
+                let last_error = GetLastError();
+                if last_error == ERROR_NO_MORE_FILES {
+                    return Ok(entries.into_iter());
+                }
+
+                return Err(Error::StormLib {
+                    function: "SFileFindFirstFile",
+                    code: last_error,
+                });
+            }
+
+            defer! {
+                if !SFileFindClose(find_handle) {
+                    error!(
+                        "SFileFindClose. GetLastError: {}, filename: {}",
                         GetLastError(),
-                        filename.as_ref().to_string_lossy()
-                    )
-                );
+                        self.filename
+                    );
+                }
+            };
+
+            loop {
+                let name = CStr::from_ptr(find_data.cFileName.as_ptr())
+                    .to_string_lossy()
+                    .into_owned();
+
+                // The platform is not part of the find data, so take it straight from the hash
+                // entry.
+                let platform = hash_table
+                    .get(find_data.dwHashIndex as usize * 16 + 10)
+                    .copied()
+                    .unwrap_or(0);
+
+                entries.push(MpqEntry {
+                    name: if is_pseudo_name(&name) {
+                        None
+                    } else {
+                        Some(name)
+                    },
+                    hash_index: find_data.dwHashIndex,
+                    block_index: find_data.dwBlockIndex,
+                    locale: find_data.lcLocale as u16,
+                    platform,
+                    compressed_size: find_data.dwCompSize,
+                    uncompressed_size: find_data.dwFileSize,
+                    flags: MpqEntryFlags::from_raw(find_data.dwFileFlags),
+                });
+
+                if !SFileFindNextFile(find_handle, &mut find_data) {
+                    let last_error = GetLastError();
+                    if last_error != ERROR_NO_MORE_FILES {
+                        return Err(Error::StormLib {
+                            function: "SFileFindNextFile",
+                            code: last_error,
+                        });
+                    }
+
+                    break;
+                }
             }
-        };
+        }
 
-        let try_map_with_locale = |filename: &str, locale| {
-            let cstr = CString::new(filename)?;
+        Ok(entries.into_iter())
+    }
+
+    /// Returns the raw (decrypted) hash table, 16 bytes per entry. Must be called with LOCK held.
+    fn hash_table(&self) -> Result<Vec<u8>> {
+        unsafe {
+            let mut hash_table_size = 0u32;
+            if !SFileGetFileInfo(
+                self.handle,
+                _SFileInfoClass_SFileMpqHashTableSize,
+                &mut hash_table_size as *mut _ as *mut c_void,
+                size_of::<u32>() as u32,
+                null_mut(),
+            ) {
+                return Err(last_error(
+                    "SFileGetFileInfo(SFileMpqHashTableSize)",
+                    &self.filename,
+                ));
+            }
+
+            let mut hash_table = vec![0u8; hash_table_size as usize * 16];
+            if !SFileGetFileInfo(
+                self.handle,
+                _SFileInfoClass_SFileMpqHashTable,
+                hash_table.as_mut_ptr() as *mut c_void,
+                hash_table.len() as u32,
+                null_mut(),
+            ) {
+                return Err(last_error(
+                    "SFileGetFileInfo(SFileMpqHashTable)",
+                    &self.filename,
+                ));
+            }
+
+            Ok(hash_table)
+        }
+    }
 
+    /// Opens `filename` at exactly `locale` and hands the file handle to `f`. Must be called with
+    /// LOCK held.
+    fn with_file<R>(
+        &self,
+        filename: &str,
+        locale: u32,
+        f: impl FnOnce(HANDLE) -> Result<R>,
+    ) -> Result<R> {
+        let cstr =
+            CString::new(filename).map_err(|_| Error::InvalidFilename(filename.to_owned()))?;
+
+        unsafe {
             SFileSetLocale(locale);
-            let mut archive_file_handle = 0 as HANDLE;
+            let mut archive_file_handle = null_mut() as HANDLE;
             if !SFileOpenFileEx(
-                mpq_handle,
+                self.handle,
                 cstr.as_ptr(),
                 0,
                 &mut archive_file_handle as *mut _,
             ) {
-                bail!(
-                    "SFileOpenFileEx. GetLastError: {}, filename: {filename}, locale: {locale}",
-                    GetLastError()
-                );
+                return Err(last_error("SFileOpenFileEx", filename));
             }
 
             defer! {
                 if !SFileCloseFile(archive_file_handle) {
                     error!(
-                        "{:?}",
-                        anyhow!(
-                            "SFileCloseFile. GetLastError: {}, filename: {filename}, locale: {locale}",
-                            GetLastError()
-                        )
+                        "SFileCloseFile. GetLastError: {}, filename: {filename}, locale: {locale}",
+                        GetLastError()
                     );
                 }
             };
@@ -110,98 +287,118 @@ pub fn get_chk_from_mpq_filename<T: AsRef<Path>>(filename: T) -> Result<Vec<u8>>
                 _SFileInfoClass_SFileInfoLocale,
                 &mut gotten_locale as *mut _ as *mut c_void,
                 size_of::<u32>() as u32,
-                0 as *mut _,
+                null_mut(),
             ) {
-                bail!(
-                    "SFileGetFileInfo. GetLastError: {}, filename: {filename}, locale: {locale}",
-                    GetLastError()
-                );
+                return Err(last_error("SFileGetFileInfo(SFileInfoLocale)", filename));
             }
 
             if gotten_locale != locale {
-                bail!("not found");
+                return Err(Error::DecoyLocale {
+                    filename: filename.to_owned(),
+                    requested: locale,
+                    found: gotten_locale,
+                });
             }
 
-            let file_size_low;
+            f(archive_file_handle)
+        }
+    }
+
+    /// Reads `filename` at exactly `locale`, tolerating archives that report EOF early. Must be
+    /// called with LOCK held.
+    fn read_file_with_locale(&self, filename: &str, locale: u32) -> Result<Vec<u8>> {
+        self.with_file(filename, locale, |archive_file_handle| unsafe {
             let mut file_size_high: u32 = 0;
 
-            file_size_low = SFileGetFileSize(archive_file_handle, &mut file_size_high as *mut _);
+            let file_size_low =
+                SFileGetFileSize(archive_file_handle, &mut file_size_high as *mut _);
 
             if file_size_low == SFILE_INVALID_SIZE {
-                bail!(
-                    "SFileGetFileSize. GetLastError: {}, filename: {filename}, locale: {locale}",
-                    GetLastError()
-                );
+                return Err(last_error("SFileGetFileSize", filename));
             }
 
-            if file_size_high != 0 {
-                bail!(
-                    "SFileGetFileSize. File size too big. file_size_high: {file_size_high}, file_size_low: {file_size_low}",
-                );
+            let size = ((file_size_high as u64) << 32) | file_size_low as u64;
+            if file_size_high != 0 || self.max_file_size.is_some_and(|max| size > max) {
+                return Err(Error::FileTooBig {
+                    filename: filename.to_owned(),
+                    size,
+                });
             }
 
-            let mut chk_data: Vec<u8> = vec![0; file_size_low as usize];
+            let mut data: Vec<u8> = vec![0; file_size_low as usize];
 
             let mut size: u32 = 0;
             if !SFileReadFile(
                 archive_file_handle,
-                chk_data.as_mut_ptr() as *mut _,
-                chk_data.len() as u32,
+                data.as_mut_ptr() as *mut _,
+                data.len() as u32,
                 &mut size as *mut _,
-                0 as *mut _,
+                null_mut(),
             ) {
                 let last_error = GetLastError();
-                if last_error != ERROR_HANDLE_EOF || size == chk_data.len() as u32 {
-                    bail!(
-                        "SFileReadFile. GetLastError: {}, filename: {filename}, locale: {locale}",
-                        last_error,
-                    );
+                if last_error != ERROR_HANDLE_EOF || size == data.len() as u32 {
+                    return Err(match last_error {
+                        ERROR_FILE_CORRUPT => Error::Corrupt(format!(
+                            "SFileReadFile failed. filename: {filename}, locale: {locale}"
+                        )),
+                        code => Error::StormLib {
+                            function: "SFileReadFile",
+                            code,
+                        },
+                    });
                 }
             }
 
-            chk_data.resize(size as usize, 0);
-
-            Ok(chk_data)
-        };
+            data.resize(size as usize, 0);
 
-        let locales = [
-            0x404, 0x405, 0x407, 0x409, 0x40a, 0x40c, 0x410, 0x411, 0x412, 0x415, 0x416, 0x419,
-            0x809, 0,
-        ];
+            Ok(data)
+        })
+    }
+}
 
-        // PROTECTION: Some maps put fake scenario.chk files at different locales. Try to find the real one by trying a lot of them.
-        // TODO: Although this algorithm works for the existing test cases it does not feel correct. I suspect that when SC opens a file it just takes the first one it finds.
-        // So, in stormlib that would be the one with the lowest index. I won't implement that until doing some more research and confirming that is the case.
-        for locale in locales {
-            if let Ok(x) = try_map_with_locale("staredit\\scenario.chk", locale) {
-                return Ok(x);
+impl Drop for MpqArchive {
+    fn drop(&mut self) {
+        let _lock = LOCK.lock().unwrap();
+        unsafe {
+            if !SFileCloseArchive(self.handle) {
+                error!(
+                    "SFileCloseArchive. GetLastError: {}, filename: {}",
+                    GetLastError(),
+                    self.filename
+                );
             }
         }
-
-        bail!(
-            "Couldn't find scenario.chk the legit way: {}, file: {}",
-            GetLastError(),
-            filename.as_ref().to_string_lossy(),
-        );
     }
 }
 
-#[instrument(level = "trace", skip_all)]
-pub fn get_chk_from_mpq_in_memory(mpq: &[u8]) -> Result<Vec<u8>> {
-    // For stormlib to use the right hacks and fixes, it needs to see a file that ends in .scm or .scx
-    let path = format!("/tmp/{}.scx", Uuid::new_v4().as_simple().to_string());
+/// StormLib names files it can't find in the listfile `File00000123.xxx`.
+fn is_pseudo_name(name: &str) -> bool {
+    let Some(rest) = name.strip_prefix("File") else {
+        return false;
+    };
 
-    let mut file = File::create(&path)?;
+    rest.len() > 9
+        && rest.as_bytes()[..8].iter().all(u8::is_ascii_digit)
+        && rest.as_bytes()[8] == b'.'
+}
 
-    defer! {
-        if let Err(err) = remove_file(&path) {
-            error!("{:?}", err);
-        }
+/// Turns the last StormLib error into an `Error`, naming the causes callers care about.
+fn last_error(function: &'static str, filename: &str) -> Error {
+    match unsafe { GetLastError() } {
+        ERROR_FILE_NOT_FOUND => Error::FileNotFound(filename.to_owned()),
+        ERROR_FILE_CORRUPT => Error::Corrupt(format!("{function} failed. filename: {filename}")),
+        code => Error::StormLib { function, code },
     }
+}
 
-    file.write_all(mpq)?;
-
-    file.flush()?;
+/// Extracts `filename` from `archive`, only accepting the entry stored at exactly `locale`.
+///
+/// Unlike `MpqArchive::read_file`, this does not go looking through other locales, which makes it
+/// usable for files such as `(listfile)`, `(attributes)` or embedded WAVs where the caller knows
+/// which entry it wants.
+#[instrument(level = "trace", skip(archive))]
+pub fn extract_file(archive: &MpqArchive, filename: &str, locale: u32) -> Result<Vec<u8>> {
+    let _lock = LOCK.lock().unwrap();
 
-    get_chk_from_mpq_filename(&path)
+    archive.read_file_with_locale(filename, locale)
 }
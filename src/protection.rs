@@ -0,0 +1,277 @@
+//! Detection of the tricks map protectors use to break editors and extractors.
+
+use crate::chk;
+use crate::crypto::hash_string;
+use crate::crypto::MPQ_HASH_NAME_A;
+use crate::crypto::MPQ_HASH_NAME_B;
+use crate::entry::MpqEntryFlags;
+use crate::entry::MPQ_FILE_EXISTS;
+use crate::error::Result;
+use crate::reader::MpqReader;
+use crate::reader::ReaderOptions;
+use crate::reader::Resolution;
+use crate::reader::HASH_ENTRY_DELETED;
+use crate::reader::HASH_ENTRY_FREE;
+use crate::reader::MAP_MAX_FILE_SIZE;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Read;
+use std::io::Seek;
+use tracing::instrument;
+
+const CHK_FILENAME: &str = "staredit\\scenario.chk";
+
+/// Everything that looks like a `staredit\scenario.chk` in an archive, plus the other tricks that were found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectionReport {
+    /// Every hash table entry named `staredit\scenario.chk`, in hash table order.
+    pub chk_candidates: Vec<ChkCandidate>,
+    pub tricks: Vec<ProtectionTrick>,
+}
+
+impl ProtectionReport {
+    /// Returns true if any protection trick was found.
+    pub fn is_protected(&self) -> bool {
+        !self.tricks.is_empty()
+    }
+}
+
+/// One hash table entry named `staredit\scenario.chk`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChkCandidate {
+    pub hash_index: u32,
+    pub block_index: u32,
+    pub locale: u16,
+    pub platform: u8,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub flags: MpqEntryFlags,
+    /// Whether the entry can be reached by walking the hash chain from the name's bucket.
+    /// Entries that can't are invisible to StarCraft.
+    pub in_hash_chain: bool,
+    /// Whether the entry could be read and decompressed.
+    pub decompresses: bool,
+    /// Whether the decompressed data has the section layout of a CHK file.
+    pub parses_as_chk: bool,
+    /// Why the entry could not be read, if it couldn't.
+    pub error: Option<String>,
+    /// Whether this is the entry `get_chk_from_mpq_*` returns.
+    pub selected_by_locale_probe: bool,
+    /// Whether this is the entry `Resolution::HashChain` returns.
+    pub selected_by_hash_chain: bool,
+}
+
+/// A known way of making an archive hard to open for anything but StarCraft.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtectionTrick {
+    /// More than one entry is named `staredit\scenario.chk`.
+    DecoyChk { count: usize },
+    /// The header claims a size or format version that StarCraft ignores but strict readers reject.
+    BogusHeader {
+        header_size: u32,
+        format_version: u16,
+    },
+    /// The archive size in the header does not match the data that follows it.
+    BogusArchiveSize { declared: u32, actual: u64 },
+    /// The hash table is not a power of two in size, or it runs past the end of the file.
+    OversizedHashTable { declared: u32, present: u32 },
+    /// The block table runs past the end of the file.
+    OversizedBlockTable { declared: u32, present: u32 },
+    /// Two blocks share some of their bytes in the archive.
+    OverlappingBlocks { first: u32, second: u32 },
+    /// A block points outside of the file.
+    BlockPastEof { block_index: u32 },
+    /// There is no `(listfile)`, so file names can't be recovered.
+    MissingListfile,
+}
+
+impl<R: Read + Seek> MpqReader<R> {
+    /// Lists every `staredit\scenario.chk` candidate and checks the archive for known protection tricks.
+    #[instrument(level = "trace", skip(self))]
+    pub fn analyze_protection(&mut self) -> ProtectionReport {
+        let mut tricks = Vec::new();
+
+        let header = self.header;
+        if header.header_size != 32 || header.format_version != 0 {
+            tricks.push(ProtectionTrick::BogusHeader {
+                header_size: header.header_size,
+                format_version: header.format_version,
+            });
+        }
+
+        let actual_size = self.reader_len - self.archive_offset;
+        if header.archive_size as u64 != actual_size {
+            tricks.push(ProtectionTrick::BogusArchiveSize {
+                declared: header.archive_size,
+                actual: actual_size,
+            });
+        }
+
+        if !header.hash_table_size.is_power_of_two()
+            || (self.hash_table.len() as u32) < header.hash_table_size
+        {
+            tricks.push(ProtectionTrick::OversizedHashTable {
+                declared: header.hash_table_size,
+                present: self.hash_table.len() as u32,
+            });
+        }
+
+        if (self.block_table.len() as u32) < header.block_table_size {
+            tricks.push(ProtectionTrick::OversizedBlockTable {
+                declared: header.block_table_size,
+                present: self.block_table.len() as u32,
+            });
+        }
+
+        tricks.extend(self.block_layout_tricks());
+
+        if !self.has_file("(listfile)") {
+            tricks.push(ProtectionTrick::MissingListfile);
+        }
+
+        let chk_candidates = self.chk_candidates();
+        if chk_candidates.len() > 1 {
+            tricks.insert(
+                0,
+                ProtectionTrick::DecoyChk {
+                    count: chk_candidates.len(),
+                },
+            );
+        }
+
+        ProtectionReport {
+            chk_candidates,
+            tricks,
+        }
+    }
+
+    /// Every candidate is decompressed, so a decoy declaring a huge size is refused with
+    /// `FileTooBig` rather than decoded, whatever limit the archive was opened with.
+    fn chk_candidates(&mut self) -> Vec<ChkCandidate> {
+        let options = self.options;
+        self.options.max_file_size = Some(
+            options
+                .max_file_size
+                .map_or(MAP_MAX_FILE_SIZE, |x| x.min(MAP_MAX_FILE_SIZE)),
+        );
+        let candidates = self.chk_candidates_within_limit();
+        self.options = options;
+        candidates
+    }
+
+    fn chk_candidates_within_limit(&mut self) -> Vec<ChkCandidate> {
+        let name1 = hash_string(CHK_FILENAME.as_bytes(), MPQ_HASH_NAME_A);
+        let name2 = hash_string(CHK_FILENAME.as_bytes(), MPQ_HASH_NAME_B);
+
+        let in_chain: HashSet<usize> = self.hash_chain(CHK_FILENAME).collect();
+        let by_locale_probe = self
+            .read_file_resolved(CHK_FILENAME, Resolution::LocaleProbe)
+            .ok()
+            .map(|(entry, _)| entry.hash_index);
+        let by_hash_chain = self
+            .read_file_resolved(CHK_FILENAME, Resolution::HashChain)
+            .ok()
+            .map(|(entry, _)| entry.hash_index);
+
+        let hash_indexes: Vec<usize> = self
+            .hash_table
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| {
+                hash.name1 == name1
+                    && hash.name2 == name2
+                    && hash.block_index != HASH_ENTRY_FREE
+                    && hash.block_index != HASH_ENTRY_DELETED
+            })
+            .map(|(hash_index, _)| hash_index)
+            .collect();
+
+        hash_indexes
+            .into_iter()
+            .map(|hash_index| {
+                let hash = self.hash_table[hash_index];
+                let block = self.block_table.get(hash.block_index as usize).copied();
+
+                let (decompresses, parses_as_chk, error) =
+                    match self.read_hash_entry(CHK_FILENAME, hash_index) {
+                        Ok(data) => (
+                            true,
+                            chk::sections(&data).any(|(name, _)| &name == b"VER "),
+                            None,
+                        ),
+                        Err(err) => (false, false, Some(err.to_string())),
+                    };
+
+                ChkCandidate {
+                    hash_index: hash_index as u32,
+                    block_index: hash.block_index,
+                    locale: hash.locale,
+                    platform: hash.platform,
+                    compressed_size: block.map_or(0, |x| x.compressed_size),
+                    uncompressed_size: block.map_or(0, |x| x.file_size),
+                    flags: MpqEntryFlags::from_raw(block.map_or(0, |x| x.flags)),
+                    in_hash_chain: in_chain.contains(&hash_index),
+                    decompresses,
+                    parses_as_chk,
+                    error,
+                    selected_by_locale_probe: by_locale_probe == Some(hash_index as u32),
+                    selected_by_hash_chain: by_hash_chain == Some(hash_index as u32),
+                }
+            })
+            .collect()
+    }
+
+    /// Finds blocks that overlap each other or point past the end of the file.
+    fn block_layout_tricks(&self) -> Vec<ProtectionTrick> {
+        let mut tricks = Vec::new();
+
+        let mut blocks: Vec<(u64, u64, u32)> = self
+            .block_table
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.flags & MPQ_FILE_EXISTS != 0 && block.compressed_size != 0)
+            .map(|(block_index, block)| {
+                let start = self.archive_pos(block.offset);
+                (
+                    start,
+                    start + block.compressed_size as u64,
+                    block_index as u32,
+                )
+            })
+            .collect();
+
+        for &(_, end, block_index) in &blocks {
+            if end > self.reader_len {
+                tricks.push(ProtectionTrick::BlockPastEof { block_index });
+            }
+        }
+
+        blocks.sort();
+
+        let mut furthest: Option<(u64, u32)> = None;
+        for (start, end, block_index) in blocks {
+            if let Some((furthest_end, furthest_index)) = furthest {
+                if start < furthest_end {
+                    tricks.push(ProtectionTrick::OverlappingBlocks {
+                        first: furthest_index,
+                        second: block_index,
+                    });
+                }
+                if end <= furthest_end {
+                    continue;
+                }
+            }
+            furthest = Some((end, block_index));
+        }
+
+        tricks
+    }
+}
+
+/// Analyzes an in-memory map archive for protection tricks. See `MpqReader::analyze_protection`.
+#[instrument(level = "trace", skip_all)]
+pub fn analyze_protection(mpq: &[u8]) -> Result<ProtectionReport> {
+    Ok(MpqReader::from_bytes(mpq, ReaderOptions::map())?.analyze_protection())
+}
@@ -0,0 +1,858 @@
+//! A pure-Rust reader for MPQ format v1 archives, the format StarCraft maps are stored in.
+//!
+//! Unlike the StormLib backend this needs no global lock, no C toolchain and no temp files.
+
+use crate::compression;
+use crate::crypto::decrypt_block;
+use crate::crypto::file_key;
+use crate::crypto::hash_string;
+use crate::crypto::MPQ_HASH_FILE_KEY;
+use crate::crypto::MPQ_HASH_NAME_A;
+use crate::crypto::MPQ_HASH_NAME_B;
+use crate::crypto::MPQ_HASH_TABLE_INDEX;
+use crate::entry::MpqEntry;
+use crate::entry::MpqEntryFlags;
+use crate::entry::LOCALES;
+use crate::entry::MPQ_FILE_COMPRESS;
+use crate::entry::MPQ_FILE_ENCRYPTED;
+use crate::entry::MPQ_FILE_EXISTS;
+use crate::entry::MPQ_FILE_FIX_KEY;
+use crate::entry::MPQ_FILE_IMPLODE;
+use crate::entry::MPQ_FILE_SECTOR_CRC;
+use crate::entry::MPQ_FILE_SINGLE_UNIT;
+use crate::error::Error;
+use crate::error::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use tracing::info;
+use tracing::instrument;
+
+const ID_MPQ: &[u8; 4] = b"MPQ\x1A";
+const ID_MPQ_USERDATA: &[u8; 4] = b"MPQ\x1B";
+
+/// Sectors are `512 << sector_size_shift` bytes. StarCraft's editor writes 3, anything past 15
+/// (16MB sectors) only serves to make readers allocate huge buffers.
+const MAX_SECTOR_SIZE_SHIFT: u16 = 15;
+
+/// The `max_file_size` of `ReaderOptions::map`. Map files are nowhere near this big.
+pub(crate) const MAP_MAX_FILE_SIZE: u64 = 64 << 20;
+
+pub(crate) const HASH_ENTRY_FREE: u32 = 0xFFFFFFFF;
+pub(crate) const HASH_ENTRY_DELETED: u32 = 0xFFFFFFFE;
+
+/// How a file name is resolved to one of possibly several hash table entries carrying that name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    /// Try a fixed list of locales in order and take the first entry that reads successfully.
+    /// This is what `MpqArchive::read_file` and `get_chk_from_mpq_*` do.
+    #[default]
+    LocaleProbe,
+    /// Do what StarCraft's loader does: start at the name's hash bucket, skip deleted entries,
+    /// stop at the first empty one and take the first entry with a matching name, whatever its locale.
+    /// There is no fallback if that entry can't be read.
+    HashChain,
+}
+
+/// The hash table entry a file name was resolved to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedEntry {
+    pub hash_index: u32,
+    pub block_index: u32,
+    pub locale: u16,
+    pub platform: u8,
+}
+
+/// Options controlling how forgiving the reader is.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReaderOptions {
+    /// Accept the malformed archives StarCraft itself accepts: garbage header size and format
+    /// version, tables running past the end of the file and offsets that wrap around 4GB.
+    /// This is what StormLib turns on when it sees a `.scm` or `.scx` file name.
+    pub map_quirks: bool,
+    /// Refuse to read files whose declared uncompressed size is larger than this, before allocating anything for them.
+    pub max_file_size: Option<u64>,
+}
+
+impl ReaderOptions {
+    /// The options StarCraft maps should be opened with: map quirks, and files of up to 64MB.
+    pub fn map() -> ReaderOptions {
+        ReaderOptions {
+            map_quirks: true,
+            max_file_size: Some(MAP_MAX_FILE_SIZE),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Header {
+    pub(crate) header_size: u32,
+    pub(crate) archive_size: u32,
+    pub(crate) format_version: u16,
+    pub(crate) sector_size_shift: u16,
+    pub(crate) hash_table_pos: u32,
+    pub(crate) block_table_pos: u32,
+    pub(crate) hash_table_size: u32,
+    pub(crate) block_table_size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HashEntry {
+    pub(crate) name1: u32,
+    pub(crate) name2: u32,
+    pub(crate) locale: u16,
+    pub(crate) platform: u8,
+    pub(crate) block_index: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockEntry {
+    pub(crate) offset: u32,
+    pub(crate) compressed_size: u32,
+    pub(crate) file_size: u32,
+    pub(crate) flags: u32,
+}
+
+/// An MPQ archive read directly from any `Read + Seek` source.
+pub struct MpqReader<R> {
+    reader: R,
+    pub(crate) reader_len: u64,
+    pub(crate) options: ReaderOptions,
+    pub(crate) archive_offset: u64,
+    pub(crate) header: Header,
+    /// Only the part of the hash table that is actually present in the file. Anything past the end is treated as free.
+    pub(crate) hash_table: Vec<HashEntry>,
+    /// Only the part of the block table that is actually present in the file.
+    pub(crate) block_table: Vec<BlockEntry>,
+}
+
+impl MpqReader<BufReader<File>> {
+    #[instrument(level = "trace", skip_all)]
+    pub fn open<T: AsRef<Path>>(
+        filename: T,
+        options: ReaderOptions,
+    ) -> Result<MpqReader<BufReader<File>>> {
+        MpqReader::new(BufReader::new(File::open(filename)?), options)
+    }
+}
+
+impl<'a> MpqReader<Cursor<&'a [u8]>> {
+    pub fn from_bytes(
+        mpq: &'a [u8],
+        options: ReaderOptions,
+    ) -> Result<MpqReader<Cursor<&'a [u8]>>> {
+        MpqReader::new(Cursor::new(mpq), options)
+    }
+}
+
+impl<R: Read + Seek> MpqReader<R> {
+    #[instrument(level = "trace", skip_all)]
+    pub fn new(mut reader: R, options: ReaderOptions) -> Result<MpqReader<R>> {
+        let reader_len = reader.seek(SeekFrom::End(0))?;
+
+        let (archive_offset, header) = find_header(&mut reader, reader_len)?;
+
+        if !options.map_quirks {
+            if header.format_version != 0 {
+                return Err(Error::Unsupported(format!(
+                    "MPQ format version {}",
+                    header.format_version
+                )));
+            }
+
+            if header.header_size < 32 {
+                return Err(Error::Corrupt(format!(
+                    "MPQ header is too small: {}",
+                    header.header_size
+                )));
+            }
+        }
+
+        let mut archive = MpqReader {
+            reader,
+            reader_len,
+            options,
+            archive_offset,
+            header,
+            hash_table: Vec::new(),
+            block_table: Vec::new(),
+        };
+
+        let hash_table = archive.read_table(
+            header.hash_table_pos,
+            header.hash_table_size,
+            b"(hash table)",
+        )?;
+        archive.hash_table = hash_table
+            .chunks_exact(16)
+            .map(|x| HashEntry {
+                name1: u32::from_le_bytes(x[0..4].try_into().unwrap()),
+                name2: u32::from_le_bytes(x[4..8].try_into().unwrap()),
+                locale: u16::from_le_bytes(x[8..10].try_into().unwrap()),
+                platform: x[10],
+                block_index: u32::from_le_bytes(x[12..16].try_into().unwrap()),
+            })
+            .collect();
+
+        let block_table = archive.read_table(
+            header.block_table_pos,
+            header.block_table_size,
+            b"(block table)",
+        )?;
+        archive.block_table = block_table
+            .chunks_exact(16)
+            .map(|x| BlockEntry {
+                offset: u32::from_le_bytes(x[0..4].try_into().unwrap()),
+                compressed_size: u32::from_le_bytes(x[4..8].try_into().unwrap()),
+                file_size: u32::from_le_bytes(x[8..12].try_into().unwrap()),
+                flags: u32::from_le_bytes(x[12..16].try_into().unwrap()),
+            })
+            .collect();
+
+        Ok(archive)
+    }
+
+    /// Reads a file out of the archive, skipping entries planted at unexpected locales.
+    /// This picks the same entry as `MpqArchive::read_file` does.
+    pub fn read_file(&mut self, filename: &str) -> Result<Vec<u8>> {
+        self.read_file_resolved(filename, Resolution::LocaleProbe)
+            .map(|(_, data)| data)
+    }
+
+    /// Reads a file out of the archive using the given resolution mode, and reports which entry was used.
+    #[instrument(level = "trace", skip(self))]
+    pub fn read_file_resolved(
+        &mut self,
+        filename: &str,
+        resolution: Resolution,
+    ) -> Result<(ResolvedEntry, Vec<u8>)> {
+        match resolution {
+            Resolution::LocaleProbe => {
+                let mut error = Error::FileNotFound(filename.to_owned());
+
+                for locale in LOCALES {
+                    if let Some(hash_index) = self.find_entry(filename, locale) {
+                        match self.read_hash_entry(filename, hash_index) {
+                            Ok(x) => return Ok((self.resolved_entry(hash_index), x)),
+                            Err(err) => {
+                                if matches!(error, Error::FileNotFound(_)) {
+                                    error = err;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Err(error)
+            }
+            Resolution::HashChain => {
+                let Some(hash_index) = self
+                    .hash_chain(filename)
+                    .find(|&hash_index| self.has_block(hash_index))
+                else {
+                    return Err(Error::FileNotFound(filename.to_owned()));
+                };
+
+                let data = self.read_hash_entry(filename, hash_index)?;
+                Ok((self.resolved_entry(hash_index), data))
+            }
+        }
+    }
+
+    /// Returns true if the hash table has a usable entry for `filename` at any of the locales `read_file` looks at.
+    pub fn has_file(&self, filename: &str) -> bool {
+        LOCALES
+            .into_iter()
+            .any(|locale| self.find_entry(filename, locale).is_some())
+    }
+
+    /// Lists every file in the hash table. Names are recovered from the archive's `(listfile)` and
+    /// a few well known map file names.
+    #[instrument(level = "trace", skip(self))]
+    pub fn entries(&mut self) -> Result<impl Iterator<Item = MpqEntry>> {
+        let mut names = vec![
+            "(listfile)".to_owned(),
+            "(attributes)".to_owned(),
+            "(signature)".to_owned(),
+            "staredit\\scenario.chk".to_owned(),
+        ];
+
+        if let Ok(listfile) = self.read_file("(listfile)") {
+            names.extend(
+                String::from_utf8_lossy(&listfile)
+                    .split([';', '\r', '\n'])
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_owned),
+            );
+        }
+
+        let names: HashMap<(u32, u32), String> = names
+            .into_iter()
+            .map(|name| {
+                (
+                    (
+                        hash_string(name.as_bytes(), MPQ_HASH_NAME_A),
+                        hash_string(name.as_bytes(), MPQ_HASH_NAME_B),
+                    ),
+                    name,
+                )
+            })
+            .collect();
+
+        let entries: Vec<_> = self
+            .hash_table
+            .iter()
+            .enumerate()
+            .filter_map(|(hash_index, hash)| {
+                let block = self.block_table.get(hash.block_index as usize)?;
+                if block.flags & MPQ_FILE_EXISTS == 0 {
+                    return None;
+                }
+
+                Some(MpqEntry {
+                    name: names.get(&(hash.name1, hash.name2)).cloned(),
+                    hash_index: hash_index as u32,
+                    block_index: hash.block_index,
+                    locale: hash.locale,
+                    platform: hash.platform,
+                    compressed_size: block.compressed_size,
+                    uncompressed_size: block.file_size,
+                    flags: MpqEntryFlags::from_raw(block.flags),
+                })
+            })
+            .collect();
+
+        Ok(entries.into_iter())
+    }
+
+    /// Walks the hash chain for `filename` and returns the index of the first usable entry stored at exactly `locale`.
+    pub(crate) fn find_entry(&self, filename: &str, locale: u16) -> Option<usize> {
+        self.hash_chain(filename).find(|&hash_index| {
+            self.hash_table[hash_index].locale == locale && self.has_block(hash_index)
+        })
+    }
+
+    /// Returns true if hash table entry `hash_index` points at an existing block.
+    pub(crate) fn has_block(&self, hash_index: usize) -> bool {
+        self.block_table
+            .get(self.hash_table[hash_index].block_index as usize)
+            .is_some_and(|block| block.flags & MPQ_FILE_EXISTS != 0)
+    }
+
+    fn resolved_entry(&self, hash_index: usize) -> ResolvedEntry {
+        let hash = &self.hash_table[hash_index];
+        ResolvedEntry {
+            hash_index: hash_index as u32,
+            block_index: hash.block_index,
+            locale: hash.locale,
+            platform: hash.platform,
+        }
+    }
+
+    /// The indexes of all hash table entries named `filename`, in the order Storm probes them.
+    pub(crate) fn hash_chain<'b>(&'b self, filename: &str) -> impl Iterator<Item = usize> + 'b {
+        let name1 = hash_string(filename.as_bytes(), MPQ_HASH_NAME_A);
+        let name2 = hash_string(filename.as_bytes(), MPQ_HASH_NAME_B);
+
+        hash_probe(filename, self.header.hash_table_size as usize)
+            .map_while(move |hash_index| {
+                let hash = self.hash_table.get(hash_index)?;
+                if hash.block_index == HASH_ENTRY_FREE {
+                    return None;
+                }
+                Some((hash_index, hash))
+            })
+            .filter(move |(_, hash)| {
+                hash.name1 == name1 && hash.name2 == name2 && hash.block_index != HASH_ENTRY_DELETED
+            })
+            .map(|(hash_index, _)| hash_index)
+    }
+
+    /// Opens a file for streaming reads. The data is decoded one sector at a time as it is read and
+    /// only the current sector is kept. Single unit files are stored as one sector, so they are
+    /// decoded whole on the first read; `max_file_size` is what bounds those.
+    ///
+    /// With `Resolution::LocaleProbe` entries are skipped if they fail to open or their first sector
+    /// fails to decode. A decoy that only breaks further in is not noticed until it is read.
+    #[instrument(level = "trace", skip(self))]
+    pub fn open_file(&mut self, filename: &str, resolution: Resolution) -> Result<MpqFile<'_, R>> {
+        let layout = match resolution {
+            Resolution::LocaleProbe => {
+                let mut error = Error::FileNotFound(filename.to_owned());
+                let mut found = None;
+
+                for locale in LOCALES {
+                    if let Some(hash_index) = self.find_entry(filename, locale) {
+                        let layout = self.file_layout(filename, hash_index).and_then(|layout| {
+                            if layout.sector_count() > 0 {
+                                self.decode_sector(&layout, 0)?;
+                            }
+                            Ok(layout)
+                        });
+
+                        match layout {
+                            Ok(layout) => {
+                                found = Some(layout);
+                                break;
+                            }
+                            Err(err) => {
+                                if matches!(error, Error::FileNotFound(_)) {
+                                    error = err;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                found.ok_or(error)?
+            }
+            Resolution::HashChain => {
+                let Some(hash_index) = self
+                    .hash_chain(filename)
+                    .find(|&hash_index| self.has_block(hash_index))
+                else {
+                    return Err(Error::FileNotFound(filename.to_owned()));
+                };
+
+                self.file_layout(filename, hash_index)?
+            }
+        };
+
+        Ok(MpqFile {
+            archive: self,
+            layout,
+            pos: 0,
+            sector: None,
+        })
+    }
+
+    /// Reads the file that hash table entry `hash_index` points at. `filename` is needed to derive the encryption key.
+    pub(crate) fn read_hash_entry(&mut self, filename: &str, hash_index: usize) -> Result<Vec<u8>> {
+        let layout = self.file_layout(filename, hash_index)?;
+
+        let mut data = Vec::new();
+        for i in 0..layout.sector_count() {
+            data.extend(self.decode_sector(&layout, i)?);
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the file at `hash_index` as it is stored, decrypted but still compressed, along with
+    /// its block entry with the encryption and sector CRC flags cleared. The bytes no longer depend
+    /// on where the file is stored, so they can be copied into another archive as they are.
+    pub(crate) fn read_raw_hash_entry(
+        &mut self,
+        filename: &str,
+        hash_index: usize,
+    ) -> Result<(BlockEntry, Vec<u8>)> {
+        let layout = self.file_layout(filename, hash_index)?;
+        let mut block = layout.block;
+
+        let mut stored = self.read_at(layout.file_pos, block.compressed_size as usize)?;
+        if stored.len() < block.compressed_size as usize {
+            return Err(Error::Corrupt(format!(
+                "File runs past the end of the archive. filename: {filename}"
+            )));
+        }
+
+        if layout.key != 0 {
+            let is_single_unit = block.flags & MPQ_FILE_SINGLE_UNIT != 0;
+            if let (Some(offsets), false) = (&layout.sector_offsets, is_single_unit) {
+                let table_len = (offsets.len() * 4).min(stored.len());
+                decrypt_block(&mut stored[..table_len], layout.key.wrapping_sub(1));
+            }
+
+            for i in 0..layout.sector_count() {
+                let (start, end) = match &layout.sector_offsets {
+                    Some(offsets) => (offsets[i] as usize, offsets[i + 1] as usize),
+                    None => (
+                        i * layout.sector_size,
+                        ((i + 1) * layout.sector_size).min(block.file_size as usize),
+                    ),
+                };
+                let Some(sector) = stored.get_mut(start..end) else {
+                    return Err(Error::Corrupt(format!(
+                        "Sector offset table is corrupt. hash_index: {hash_index}, sector: {i}"
+                    )));
+                };
+                decrypt_block(sector, layout.key.wrapping_add(i as u32));
+            }
+        }
+
+        // The sector CRCs stay where they are, but nothing reads them without the flag.
+        block.flags &= !(MPQ_FILE_ENCRYPTED | MPQ_FILE_FIX_KEY | MPQ_FILE_SECTOR_CRC);
+        Ok((block, stored))
+    }
+
+    /// Works out where the sectors of the file at `hash_index` are. This reads the sector offset table but no file data.
+    fn file_layout(&mut self, filename: &str, hash_index: usize) -> Result<FileLayout> {
+        let Some(hash) = self.hash_table.get(hash_index) else {
+            return Err(Error::Corrupt(format!(
+                "Hash table index out of range. hash_index: {hash_index}"
+            )));
+        };
+
+        let block_index = hash.block_index;
+        let Some(block) = self.block_table.get(block_index as usize).copied() else {
+            return Err(Error::Corrupt(format!(
+                "Block table index out of range. block_index: {block_index}"
+            )));
+        };
+
+        if block.flags & MPQ_FILE_EXISTS == 0 {
+            return Err(Error::FileNotFound(filename.to_owned()));
+        }
+
+        if let Some(max_file_size) = self.options.max_file_size {
+            if block.file_size as u64 > max_file_size {
+                return Err(Error::FileTooBig {
+                    filename: filename.to_owned(),
+                    size: block.file_size as u64,
+                });
+            }
+        }
+
+        let key = if block.flags & MPQ_FILE_ENCRYPTED != 0 {
+            file_key(
+                filename.as_bytes(),
+                block.offset,
+                block.file_size,
+                block.flags & MPQ_FILE_FIX_KEY != 0,
+            )
+        } else {
+            0
+        };
+
+        let file_pos = self.archive_pos(block.offset);
+        let file_size = block.file_size as usize;
+
+        // Single unit files are stored as one big sector.
+        if block.flags & MPQ_FILE_SINGLE_UNIT != 0 {
+            return Ok(FileLayout {
+                hash_index,
+                block,
+                key,
+                file_pos,
+                sector_size: file_size,
+                sector_offsets: Some(vec![0, block.compressed_size]),
+            });
+        }
+
+        if self.header.sector_size_shift > MAX_SECTOR_SIZE_SHIFT {
+            return Err(Error::Corrupt(format!(
+                "Invalid sector size shift: {}",
+                self.header.sector_size_shift
+            )));
+        }
+        let sector_size = 512usize << self.header.sector_size_shift;
+        let sector_count = file_size.div_ceil(sector_size);
+
+        // Uncompressed files have no sector offset table, the sectors are simply laid out back to back.
+        let sector_offsets =
+            if block.flags & (MPQ_FILE_COMPRESS | MPQ_FILE_IMPLODE) != 0 && sector_count > 0 {
+                let entry_count = sector_count
+                    + 1
+                    + if block.flags & MPQ_FILE_SECTOR_CRC != 0 {
+                        1
+                    } else {
+                        0
+                    };
+                let mut table = self.read_at(file_pos, entry_count * 4)?;
+                if table.len() != entry_count * 4 {
+                    return Err(Error::Corrupt(format!(
+                        "Sector offset table is truncated. filename: {filename}"
+                    )));
+                }
+                if key != 0 {
+                    decrypt_block(&mut table, key.wrapping_sub(1));
+                }
+                Some(
+                    table
+                        .chunks_exact(4)
+                        .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+                        .collect(),
+                )
+            } else {
+                None
+            };
+
+        Ok(FileLayout {
+            hash_index,
+            block,
+            key,
+            file_pos,
+            sector_size,
+            sector_offsets,
+        })
+    }
+
+    /// Reads, decrypts and decompresses sector `i` of a file.
+    fn decode_sector(&mut self, layout: &FileLayout, i: usize) -> Result<Vec<u8>> {
+        let file_size = layout.block.file_size as usize;
+        let expected = layout.sector_size.min(file_size - i * layout.sector_size);
+
+        let (start, end) = match &layout.sector_offsets {
+            Some(offsets) => (offsets[i], offsets[i + 1]),
+            None => (
+                (i * layout.sector_size) as u32,
+                (i * layout.sector_size + expected) as u32,
+            ),
+        };
+        if end < start {
+            return Err(Error::Corrupt(format!(
+                "Sector offset table is corrupt. hash_index: {}, sector: {i}",
+                layout.hash_index
+            )));
+        }
+
+        let mut sector = self.read_at(layout.file_pos + start as u64, (end - start) as usize)?;
+        if sector.len() < (end - start) as usize {
+            return Err(Error::Corrupt(format!(
+                "Sector runs past the end of the archive. hash_index: {}, sector: {i}",
+                layout.hash_index
+            )));
+        }
+
+        if layout.key != 0 {
+            decrypt_block(&mut sector, layout.key.wrapping_add(i as u32));
+        }
+
+        let is_compressed = layout.block.flags & (MPQ_FILE_COMPRESS | MPQ_FILE_IMPLODE) != 0;
+        if is_compressed && sector.len() < expected {
+            decompress_sector(layout.block.flags, &sector, expected)
+        } else {
+            sector.truncate(expected);
+            Ok(sector)
+        }
+    }
+
+    /// Turns an archive-relative offset into a position in the underlying reader.
+    pub(crate) fn archive_pos(&self, offset: u32) -> u64 {
+        if self.options.map_quirks {
+            // PROTECTION: Offsets are 32 bit and some maps rely on them wrapping around.
+            (self.archive_offset as u32).wrapping_add(offset) as u64
+        } else {
+            self.archive_offset + offset as u64
+        }
+    }
+
+    /// Reads and decrypts a hash or block table. With map quirks, tables that run past the end of the file are cut short.
+    fn read_table(&mut self, pos: u32, entry_count: u32, key_name: &[u8]) -> Result<Vec<u8>> {
+        let pos = self.archive_pos(pos);
+        let len = entry_count as usize * 16;
+
+        let mut table = self.read_at(pos, len)?;
+        if table.len() < len && !self.options.map_quirks {
+            return Err(Error::Corrupt(format!(
+                "{} runs past the end of the archive",
+                String::from_utf8_lossy(key_name)
+            )));
+        }
+        table.truncate(table.len() / 16 * 16);
+
+        decrypt_block(&mut table, hash_string(key_name, MPQ_HASH_FILE_KEY));
+
+        Ok(table)
+    }
+
+    /// Reads up to `len` bytes at `pos`, returning fewer if the reader ends first.
+    fn read_at(&mut self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let available = self.reader_len.saturating_sub(pos);
+        let len = (len as u64).min(available) as usize;
+
+        let mut buf = vec![0; len];
+        self.reader.seek(SeekFrom::Start(pos))?;
+        self.reader.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+}
+
+/// Where the sectors of one file are and how to decode them.
+struct FileLayout {
+    hash_index: usize,
+    block: BlockEntry,
+    key: u32,
+    file_pos: u64,
+    sector_size: usize,
+    /// Sector boundaries relative to `file_pos`. `None` for uncompressed files, whose sectors are back to back.
+    sector_offsets: Option<Vec<u32>>,
+}
+
+impl FileLayout {
+    fn sector_count(&self) -> usize {
+        if self.block.file_size == 0 {
+            0
+        } else {
+            (self.block.file_size as usize).div_ceil(self.sector_size)
+        }
+    }
+}
+
+/// A file inside an archive, opened with `MpqReader::open_file`. Sectors are decoded as they are read
+/// and only the most recent one is kept around.
+pub struct MpqFile<'a, R> {
+    archive: &'a mut MpqReader<R>,
+    layout: FileLayout,
+    pos: u64,
+    /// The most recently decoded sector and its index.
+    sector: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> MpqFile<'_, R> {
+    /// The uncompressed size declared by the block table.
+    pub fn len(&self) -> u64 {
+        self.layout.block.file_size as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The hash table entry this file was opened from.
+    pub fn entry(&self) -> ResolvedEntry {
+        self.archive.resolved_entry(self.layout.hash_index)
+    }
+}
+
+impl<R: Read + Seek> Read for MpqFile<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.len() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let sector_size = self.layout.sector_size as u64;
+        let index = (self.pos / sector_size) as usize;
+        if self.sector.as_ref().map(|(i, _)| *i) != Some(index) {
+            let data = self.archive.decode_sector(&self.layout, index)?;
+            self.sector = Some((index, data));
+        }
+
+        let data = &self.sector.as_ref().unwrap().1;
+        let offset = (self.pos - index as u64 * sector_size) as usize;
+        // Uncompressed sectors can come up short, treat that as the end of the file.
+        if offset >= data.len() {
+            return Ok(0);
+        }
+
+        let len = buf.len().min(data.len() - offset);
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for MpqFile<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.len().checked_add_signed(x),
+            SeekFrom::Current(x) => self.pos.checked_add_signed(x),
+        };
+
+        let Some(pos) = pos else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+fn decompress_sector(flags: u32, data: &[u8], expected: usize) -> Result<Vec<u8>> {
+    let out = if flags & MPQ_FILE_IMPLODE != 0 {
+        compression::explode(data, expected)?
+    } else {
+        compression::decompress(data, expected)?
+    };
+
+    if out.len() != expected {
+        return Err(Error::Corrupt(format!(
+            "Sector decompressed to the wrong size. expected: {expected}, got: {}",
+            out.len()
+        )));
+    }
+
+    Ok(out)
+}
+
+/// Searches for the MPQ header on 512 byte boundaries, following a user data header if there is one.
+fn find_header<R: Read + Seek>(reader: &mut R, reader_len: u64) -> Result<(u64, Header)> {
+    let mut offset = 0u64;
+    while offset + 32 <= reader_len {
+        let mut buf = [0u8; 32];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut buf)?;
+
+        if &buf[0..4] == ID_MPQ_USERDATA {
+            let header_offset = u32::from_le_bytes(buf[8..12].try_into().unwrap()) as u64;
+            let archive_offset = offset + header_offset;
+            if archive_offset + 32 <= reader_len {
+                reader.seek(SeekFrom::Start(archive_offset))?;
+                reader.read_exact(&mut buf)?;
+                if &buf[0..4] == ID_MPQ {
+                    return Ok((archive_offset, parse_header(&buf)));
+                }
+            }
+        } else if &buf[0..4] == ID_MPQ {
+            return Ok((offset, parse_header(&buf)));
+        }
+
+        offset += 512;
+    }
+
+    Err(Error::NotAnMpq("no MPQ header found".to_owned()))
+}
+
+fn parse_header(buf: &[u8; 32]) -> Header {
+    Header {
+        header_size: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+        archive_size: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+        format_version: u16::from_le_bytes(buf[12..14].try_into().unwrap()),
+        sector_size_shift: u16::from_le_bytes(buf[14..16].try_into().unwrap()),
+        hash_table_pos: u32::from_le_bytes(buf[16..20].try_into().unwrap()),
+        block_table_pos: u32::from_le_bytes(buf[20..24].try_into().unwrap()),
+        hash_table_size: u32::from_le_bytes(buf[24..28].try_into().unwrap()),
+        block_table_size: u32::from_le_bytes(buf[28..32].try_into().unwrap()),
+    }
+}
+
+/// The hash table indexes Storm looks at for `filename`, in order, wrapping around once. Like
+/// Storm the start and each step are masked with `size - 1`, which for a size that isn't a power
+/// of two skips some slots.
+pub(crate) fn hash_probe(filename: &str, size: usize) -> impl Iterator<Item = usize> {
+    let mask = size.wrapping_sub(1);
+    let start = hash_string(filename.as_bytes(), MPQ_HASH_TABLE_INDEX) as usize & mask;
+    std::iter::successors(Some(start), move |&i| {
+        Some((i + 1) & mask).filter(|&x| x != start)
+    })
+    .take(size)
+}
+
+#[instrument(level = "trace", skip_all)]
+pub fn get_chk_from_mpq_filename<T: AsRef<Path>>(filename: T) -> Result<Vec<u8>> {
+    info!(
+        "Extracting scenario.chk. filename: {}",
+        filename.as_ref().to_string_lossy()
+    );
+
+    MpqReader::open(filename, ReaderOptions::map())?.read_file("staredit\\scenario.chk")
+}
+
+#[instrument(level = "trace", skip_all)]
+pub fn get_chk_from_mpq_in_memory(mpq: &[u8]) -> Result<Vec<u8>> {
+    MpqReader::from_bytes(mpq, ReaderOptions::map())?.read_file("staredit\\scenario.chk")
+}
@@ -0,0 +1,290 @@
+//! Map previews drawn from the terrain in a CHK and StarCraft's tileset files.
+//!
+//! Every tile in MTXM is a tile group and an index into it. CV5 turns that into a megatile,
+//! VX4 splits the megatile into 4 by 4 minitiles, VR4 holds the 8 by 8 palette indexes of every
+//! minitile and WPE is the palette. The files aren't shipped with this crate, they come from
+//! StarCraft's `tileset` directory.
+
+use crate::chk::Chk;
+use crate::chk::Tileset;
+use crate::error::Error;
+use crate::error::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+const CV5_ENTRY_SIZE: usize = 52;
+/// Where the 16 megatile ids start in a CV5 entry.
+const CV5_MEGATILES_OFFSET: usize = 20;
+const VX4_ENTRY_SIZE: usize = 32;
+const VR4_ENTRY_SIZE: usize = 64;
+const WPE_SIZE: usize = 1024;
+
+/// The largest map StarCraft supports, in tiles.
+const MAX_DIMENSION: usize = 256;
+
+const TILE_SIZE: usize = 32;
+const MINITILE_SIZE: usize = 8;
+
+/// The minimap colours of players 1 to 12. Anything past player 12 is drawn as player 12.
+const PLAYER_COLORS: [[u8; 3]; 12] = [
+    [244, 4, 4],
+    [12, 72, 204],
+    [44, 180, 148],
+    [136, 64, 156],
+    [248, 140, 20],
+    [112, 48, 20],
+    [204, 224, 208],
+    [252, 252, 56],
+    [8, 128, 8],
+    [252, 252, 124],
+    [236, 196, 176],
+    [64, 104, 212],
+];
+
+/// The four files that describe one tileset.
+#[derive(Debug, Clone)]
+pub struct TilesetFiles {
+    cv5: Vec<u8>,
+    vx4: Vec<u8>,
+    vr4: Vec<u8>,
+    wpe: Vec<u8>,
+}
+
+impl TilesetFiles {
+    /// Reads `<name>.cv5`, `<name>.vx4`, `<name>.vr4` and `<name>.wpe` for `tileset` from `dir`,
+    /// laid out the way StarCraft's `tileset` directory is. Names are matched ignoring case, as
+    /// StarCraft ships `AshWorld.cv5` and `Jungle.cv5` but `badlands.cv5`.
+    pub fn load<T: AsRef<Path>>(dir: T, tileset: Tileset) -> Result<TilesetFiles> {
+        let dir = dir.as_ref();
+        let names = std::fs::read_dir(dir)?
+            .map(|x| x.map(|x| x.file_name()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let read = |extension: &str| -> Result<Vec<u8>> {
+            let wanted = format!("{}.{extension}", tileset.file_name());
+            let name = names
+                .iter()
+                .find(|x| x.to_str().is_some_and(|x| x.eq_ignore_ascii_case(&wanted)))
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("{wanted} is not in {}", dir.display()),
+                    )
+                })?;
+            Ok(std::fs::read(dir.join(name))?)
+        };
+
+        TilesetFiles::from_bytes(read("cv5")?, read("vx4")?, read("vr4")?, read("wpe")?)
+    }
+
+    /// Only the palette is checked up front. Tiles that point past the end of the other files
+    /// are drawn black.
+    pub fn from_bytes(
+        cv5: Vec<u8>,
+        vx4: Vec<u8>,
+        vr4: Vec<u8>,
+        wpe: Vec<u8>,
+    ) -> Result<TilesetFiles> {
+        if wpe.len() < WPE_SIZE {
+            return Err(Error::Corrupt(format!(
+                "Palette is too short: {} bytes",
+                wpe.len()
+            )));
+        }
+
+        Ok(TilesetFiles { cv5, vx4, vr4, wpe })
+    }
+
+    /// The 32 by 32 RGB pixels of an MTXM tile, row by row.
+    fn tile_pixels(&self, tile: u16) -> Vec<u8> {
+        let mut pixels = vec![0; TILE_SIZE * TILE_SIZE * 3];
+
+        let (group, index) = ((tile >> 4) as usize, (tile & 0xF) as usize);
+        let Some(megatile) = read_u16(
+            &self.cv5,
+            group * CV5_ENTRY_SIZE + CV5_MEGATILES_OFFSET + index * 2,
+        ) else {
+            return pixels;
+        };
+
+        for minitile in 0..16 {
+            let Some(entry) =
+                read_u16(&self.vx4, megatile as usize * VX4_ENTRY_SIZE + minitile * 2)
+            else {
+                return pixels;
+            };
+            // The low bit flips the minitile horizontally.
+            let (vr4_index, flipped) = ((entry >> 1) as usize, entry & 1 != 0);
+            let Some(colors) = self
+                .vr4
+                .get(vr4_index * VR4_ENTRY_SIZE..(vr4_index + 1) * VR4_ENTRY_SIZE)
+            else {
+                continue;
+            };
+
+            let (left, top) = (minitile % 4 * MINITILE_SIZE, minitile / 4 * MINITILE_SIZE);
+            for y in 0..MINITILE_SIZE {
+                for x in 0..MINITILE_SIZE {
+                    let source = if flipped { MINITILE_SIZE - 1 - x } else { x };
+                    let color = colors[y * MINITILE_SIZE + source] as usize * 4;
+                    let target = ((top + y) * TILE_SIZE + left + x) * 3;
+                    pixels[target..target + 3].copy_from_slice(&self.wpe[color..color + 3]);
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+/// How big the image is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scale {
+    /// 32 pixels per tile, the way the map looks in game. A 256 by 256 map is 8192 pixels
+    /// square.
+    #[default]
+    Full,
+    /// One pixel per tile, the average colour of the tile.
+    Minimap,
+}
+
+/// What to draw.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    pub scale: Scale,
+    /// Draw every unit from UNIT as a square in its owner's colour.
+    pub units: bool,
+    /// Draw every sprite and doodad from THG2 as a smaller square in its owner's colour.
+    pub sprites: bool,
+}
+
+/// An 8 bit RGB image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Three bytes per pixel, row by row.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut x| x.write_image_data(&self.pixels))
+            .map_err(|err| Error::Io(std::io::Error::other(err)))?;
+
+        Ok(out)
+    }
+
+    /// Fills a square of side `size` centred on `(x, y)`, clipped to the image.
+    fn square(&mut self, x: usize, y: usize, size: usize, color: [u8; 3]) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (left, top) = (x.saturating_sub(size / 2), y.saturating_sub(size / 2));
+
+        for y in top..(top + size).min(height) {
+            for x in left..(left + size).min(width) {
+                let target = (y * width + x) * 3;
+                self.pixels[target..target + 3].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Draws the map's terrain from ERA, DIM and MTXM, and optionally its units and sprites.
+///
+/// `tileset` has to be the one the map uses, see `Chk::tileset`. Maps larger than 256 by 256
+/// tiles are refused as corrupt.
+pub fn render(chk: &Chk, tileset: &TilesetFiles, options: RenderOptions) -> Result<Image> {
+    let (width, height) = chk.dimensions().unwrap_or((0, 0));
+    let (width, height) = (width as usize, height as usize);
+    if width == 0 || height == 0 {
+        return Err(Error::Corrupt(format!(
+            "Map has no terrain. width: {width}, height: {height}"
+        )));
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(Error::Corrupt(format!(
+            "Map is too large. width: {width}, height: {height}"
+        )));
+    }
+    let tiles = chk.mtxm().unwrap_or_else(|| vec![0; width * height]);
+
+    let pixels_per_tile = match options.scale {
+        Scale::Full => TILE_SIZE,
+        Scale::Minimap => 1,
+    };
+    let mut image = Image {
+        width: (width * pixels_per_tile) as u32,
+        height: (height * pixels_per_tile) as u32,
+        pixels: vec![0; width * height * pixels_per_tile * pixels_per_tile * 3],
+    };
+
+    // Maps reuse a small number of tiles, so each one is only drawn once.
+    let mut cache: HashMap<u16, Vec<u8>> = HashMap::new();
+    let row_len = image.width as usize * 3;
+    // MTXM can be longer than DIM says, StarCraft ignores the rest.
+    for (i, &tile) in tiles.iter().take(width * height).enumerate() {
+        let pixels = cache.entry(tile).or_insert_with(|| {
+            let pixels = tileset.tile_pixels(tile);
+            match options.scale {
+                Scale::Full => pixels,
+                Scale::Minimap => average(&pixels).to_vec(),
+            }
+        });
+
+        let (x, y) = (i % width * pixels_per_tile, i / width * pixels_per_tile);
+        for (row, source) in pixels.chunks_exact(pixels_per_tile * 3).enumerate() {
+            let target = (y + row) * row_len + x * 3;
+            image.pixels[target..target + source.len()].copy_from_slice(source);
+        }
+    }
+
+    // Unit and sprite positions are in pixels, at full scale.
+    let position = |x: u16, y: u16| {
+        (
+            x as usize * pixels_per_tile / TILE_SIZE,
+            y as usize * pixels_per_tile / TILE_SIZE,
+        )
+    };
+    if options.sprites {
+        for sprite in chk.sprites() {
+            let (x, y) = position(sprite.x, sprite.y);
+            image.square(x, y, pixels_per_tile / 4 + 1, player_color(sprite.owner));
+        }
+    }
+    if options.units {
+        for unit in chk.units() {
+            let (x, y) = position(unit.x, unit.y);
+            image.square(x, y, pixels_per_tile / 2 + 1, player_color(unit.owner));
+        }
+    }
+
+    Ok(image)
+}
+
+fn player_color(owner: u8) -> [u8; 3] {
+    PLAYER_COLORS[(owner as usize).min(PLAYER_COLORS.len() - 1)]
+}
+
+fn average(pixels: &[u8]) -> [u8; 3] {
+    let mut sum = [0usize; 3];
+    for pixel in pixels.chunks_exact(3) {
+        for (sum, &x) in sum.iter_mut().zip(pixel) {
+            *sum += x as usize;
+        }
+    }
+
+    let count = (pixels.len() / 3).max(1);
+    sum.map(|x| (x / count) as u8)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
@@ -0,0 +1,129 @@
+//! Rebuilding protected maps as plain archives.
+
+use crate::chk::Chk;
+use crate::entry::LOCALES;
+use crate::error::Error;
+use crate::error::Result;
+use crate::reader::BlockEntry;
+use crate::reader::MpqReader;
+use crate::reader::ReaderOptions;
+use crate::reader::Resolution;
+use crate::writer::FileOptions;
+use crate::writer::MpqWriter;
+use crate::writer::WriterOptions;
+use std::io::Read;
+use std::io::Seek;
+use tracing::instrument;
+use tracing::warn;
+
+const CHK_FILENAME: &str = "staredit\\scenario.chk";
+
+enum File {
+    Data(Vec<u8>),
+    /// Still compressed, see `MpqReader::read_raw_hash_entry`.
+    Stored(BlockEntry, Vec<u8>),
+}
+
+/// Rewrites a map archive without any of the tricks protectors use, keeping everything
+/// StarCraft actually reads.
+///
+/// The CHK is the one `get_chk_from_mpq_*` returns, written back with `Chk::to_bytes`, so
+/// duplicate and junk sections are gone and the sections are in the usual order. Every other
+/// file is looked up the same way as the CHK, by probing locales, and only that copy is kept.
+/// Since protected maps rarely have a listfile, file names are taken from the listfile if there
+/// is one and from the sounds the CHK refers to. Files under any other name can't be found and
+/// are dropped.
+///
+/// Everything is stored at locale 0 with the default compression, except files this crate can't
+/// decompress yet, such as WAVs Huffman coded with a weight table other than 0. Those are copied
+/// still compressed, only decrypted, and the new archive then keeps the old one's sector size.
+#[instrument(level = "trace", skip_all)]
+pub fn sanitize(mpq: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = MpqReader::from_bytes(mpq, ReaderOptions::map())?;
+    let chk = Chk::parse(&reader.read_file(CHK_FILENAME)?);
+
+    let mut files = Vec::new();
+    for name in file_names(&mut reader, &chk)? {
+        match reader.read_file_resolved(&name, Resolution::LocaleProbe) {
+            Ok((_, data)) => files.push((name, File::Data(data))),
+            Err(Error::Unsupported(_)) => {
+                let hash_index = unsupported_entry(&mut reader, &name)?;
+                let (block, bytes) = reader.read_raw_hash_entry(&name, hash_index)?;
+                files.push((name, File::Stored(block, bytes)));
+            }
+            Err(Error::FileNotFound(_)) => {}
+            Err(err) => warn!("Dropping unreadable file. name: {name}, err: {err}"),
+        }
+    }
+
+    let mut options = WriterOptions::default();
+    if files.iter().any(|(_, x)| matches!(x, File::Stored(..))) {
+        options.sector_size_shift = reader.header.sector_size_shift;
+    }
+
+    let mut writer = MpqWriter::new(options);
+    writer.add_file(CHK_FILENAME, chk.to_bytes(), FileOptions::default());
+    for (name, file) in files {
+        match file {
+            File::Data(data) => writer.add_file(&name, data, FileOptions::default()),
+            File::Stored(block, bytes) => writer.add_stored_file(&name, block, bytes, 0),
+        }
+    }
+
+    writer.finish()
+}
+
+/// The entry the locale probe failed on with `Unsupported`. The probe reports the first error
+/// it runs into, so this is the first of the entries it looks at that can't be read.
+fn unsupported_entry<R: Read + Seek>(reader: &mut MpqReader<R>, name: &str) -> Result<usize> {
+    let candidates: Vec<usize> = LOCALES
+        .into_iter()
+        .filter_map(|locale| reader.find_entry(name, locale))
+        .collect();
+
+    candidates
+        .into_iter()
+        .find(|&x| reader.read_hash_entry(name, x).is_err())
+        .ok_or_else(|| Error::Corrupt(format!("{name} could be read after all")))
+}
+
+/// Every name the archive is known to use besides the CHK and the generated files, without
+/// duplicates. They are sorted so that the new archive doesn't depend on the old hash table.
+fn file_names<R: Read + Seek>(reader: &mut MpqReader<R>, chk: &Chk) -> Result<Vec<String>> {
+    let mut names: Vec<String> = reader.entries()?.filter_map(|x| x.name).collect();
+
+    let mut wav_ids = chk.wavs().unwrap_or_default();
+    for trigger in chk.triggers().iter().chain(&chk.briefing_triggers()) {
+        wav_ids.extend(
+            trigger
+                .actions
+                .iter()
+                .filter(|x| x.action != 0)
+                .map(|x| x.wav),
+        );
+    }
+
+    // MPQ names are hashed as bytes and the reader takes them as `&str`, so sounds whose names
+    // aren't UTF-8 can't be looked up.
+    names.extend(
+        wav_ids
+            .into_iter()
+            .filter(|&x| x != 0)
+            .filter_map(|x| chk.string(x))
+            .filter_map(|x| std::str::from_utf8(x).ok())
+            .map(str::to_owned),
+    );
+
+    let mut unique: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        let special = ["(listfile)", "(attributes)", "(signature)", CHK_FILENAME]
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(&name));
+        if !special && !unique.iter().any(|x| x.eq_ignore_ascii_case(&name)) {
+            unique.push(name);
+        }
+    }
+    unique.sort_by_key(|x| x.to_ascii_lowercase());
+
+    Ok(unique)
+}
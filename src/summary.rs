@@ -0,0 +1,188 @@
+//! A one call summary of a map for search indexes.
+
+use crate::chk::Chk;
+use crate::chk::Owner;
+use crate::chk::Race;
+use crate::chk::Tileset;
+use crate::error::Result;
+use crate::reader::get_chk_from_mpq_in_memory;
+use crate::trigedit::tables::UNITS;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+const PLAYABLE_SLOTS: usize = 8;
+
+const MINERAL_FIELDS: &[u16] = &[176, 177, 178];
+/// Vespene Geyser, Terran Refinery, Zerg Extractor and Protoss Assimilator.
+const GAS_SOURCES: &[u16] = &[188, 110, 149, 157];
+const START_LOCATION: u16 = 214;
+/// The VERs that released versions of StarCraft and Brood War, Remastered included, write and
+/// load.
+const RELEASED_VERSIONS: &[u16] = &[57, 59, 61, 63, 64, 75, 205, 206];
+
+/// Victory, Defeat and Preserve Trigger, the only actions in StarEdit's default triggers.
+const MELEE_ACTIONS: &[u8] = &[1, 2, 3];
+
+/// Everything a map listing needs to know about a map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapSummary {
+    /// The map name from SPRP, with colour bytes removed.
+    pub title: Option<String>,
+    /// The map description from SPRP, with colour bytes removed.
+    pub description: Option<String>,
+    pub tileset: Option<Tileset>,
+    pub width: u16,
+    pub height: u16,
+    /// Whether StarCraft reads the map as a Brood War map, from VER. See `Chk::is_expansion`.
+    pub expansion: bool,
+    pub game_type: GameType,
+    /// Human and computer slots among players 1 to 8.
+    pub player_count: usize,
+    pub human_slots: usize,
+    pub computer_slots: usize,
+    /// Every human and computer slot among players 1 to 8.
+    pub players: Vec<PlayerSlot>,
+    pub trigger_count: usize,
+    pub briefing_trigger_count: usize,
+    /// Locations that aren't blank, "Anywhere" included.
+    pub location_count: usize,
+    /// Strings that aren't empty.
+    pub string_count: usize,
+    /// Preplaced units per type, in unit id order.
+    pub units: Vec<UnitCount>,
+    /// Minerals in every preplaced mineral field.
+    pub minerals: u64,
+    /// Gas in every preplaced geyser and refinery.
+    pub gas: u64,
+}
+
+/// How a map is meant to be played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameType {
+    /// No triggers, or only triggers that end the game the way StarEdit's default ones do, a
+    /// start location and a VER StarCraft loads. Without the last two StarCraft can't start a
+    /// melee game on the map, so beta and WarCraft II maps are never melee. Which release the VER
+    /// is from doesn't matter, melee maps exist for all of them.
+    Melee,
+    UseMapSettings,
+}
+
+/// One of players 1 to 8, from OWNR, SIDE and FORC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerSlot {
+    /// 1 to 8.
+    pub player: u8,
+    pub owner: Owner,
+    pub race: Race,
+    /// The force, 0 to 3.
+    pub force: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitCount {
+    pub unit_id: u16,
+    /// The name StarCraft uses, `None` for ids past the end of the unit list.
+    pub name: Option<String>,
+    pub count: usize,
+}
+
+/// Summarises the map in an archive, reading the CHK the way `get_chk_from_mpq_in_memory` does.
+#[instrument(level = "trace", skip_all)]
+pub fn summarize(mpq: &[u8]) -> Result<MapSummary> {
+    let chk = Chk::parse(&get_chk_from_mpq_in_memory(mpq)?);
+    Ok(MapSummary::from_chk(&chk))
+}
+
+impl MapSummary {
+    pub fn from_chk(chk: &Chk) -> MapSummary {
+        let sprp = chk.scenario_properties();
+        let text = |id: Option<u16>| {
+            chk.decoded_string(id? as u32)
+                .map(|x| x.text)
+                .filter(|x| !x.is_empty())
+        };
+        let (width, height) = chk.dimensions().unwrap_or((0, 0));
+
+        let owners = chk.owners();
+        let sides = chk.sides();
+        let forces = chk.forces();
+        let players: Vec<PlayerSlot> = (0..PLAYABLE_SLOTS)
+            .filter_map(|i| {
+                let owner = owners?[i];
+                if !matches!(owner, Owner::Human | Owner::Computer) {
+                    return None;
+                }
+                Some(PlayerSlot {
+                    player: i as u8 + 1,
+                    owner,
+                    race: sides.map_or(Race::Inactive, |x| x[i]),
+                    force: forces.as_ref().map_or(0, |x| x.player_forces[i]),
+                })
+            })
+            .collect();
+        let human_slots = players.iter().filter(|x| x.owner == Owner::Human).count();
+
+        let triggers = chk.triggers();
+        let placed = chk.units();
+        let is_melee = triggers.iter().all(|trigger| {
+            trigger
+                .actions
+                .iter()
+                .all(|x| x.action == 0 || MELEE_ACTIONS.contains(&x.action))
+        }) && placed.iter().any(|x| x.unit_id == START_LOCATION)
+            && chk
+                .version()
+                .is_some_and(|x| RELEASED_VERSIONS.contains(&x));
+
+        let mut units: BTreeMap<u16, usize> = BTreeMap::new();
+        let (mut minerals, mut gas) = (0, 0);
+        for unit in &placed {
+            *units.entry(unit.unit_id).or_default() += 1;
+            if MINERAL_FIELDS.contains(&unit.unit_id) {
+                minerals += unit.resources as u64;
+            } else if GAS_SOURCES.contains(&unit.unit_id) {
+                gas += unit.resources as u64;
+            }
+        }
+
+        MapSummary {
+            title: text(sprp.as_ref().map(|x| x.name)),
+            description: text(sprp.as_ref().map(|x| x.description)),
+            tileset: chk.tileset(),
+            width,
+            height,
+            expansion: chk.is_expansion(),
+            game_type: if is_melee {
+                GameType::Melee
+            } else {
+                GameType::UseMapSettings
+            },
+            player_count: players.len(),
+            human_slots,
+            computer_slots: players.len() - human_slots,
+            players,
+            trigger_count: triggers.len(),
+            briefing_trigger_count: chk.briefing_triggers().len(),
+            location_count: chk
+                .locations()
+                .iter()
+                .filter(|x| **x != Default::default())
+                .count(),
+            string_count: (1..=chk.string_count() as u32)
+                .filter(|&id| chk.string(id).is_some_and(|x| !x.is_empty()))
+                .count(),
+            units: units
+                .into_iter()
+                .map(|(unit_id, count)| UnitCount {
+                    unit_id,
+                    name: UNITS.get(unit_id as usize).map(|x| x.to_string()),
+                    count,
+                })
+                .collect(),
+            minerals,
+            gas,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::get_chk_from_mpq_in_memory;
+#[cfg(feature = "stormlib")]
+use crate::MpqArchive;
 use anyhow::Result;
 use futures_util::{future::select_all, FutureExt};
 use reqwest::Version;
@@ -8,6 +10,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+mod corpus;
+
 fn hash(bytes: &[u8]) -> String {
     let mut hasher = sha2::Sha256::new();
 
@@ -16,6 +20,17 @@ fn hash(bytes: &[u8]) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// Opens an archive built in memory with StormLib, which only opens files. `name` is the file
+/// name to give it, and its extension decides whether StormLib allows map quirks.
+#[cfg(feature = "stormlib")]
+fn open_with_stormlib(mpq: &[u8], name: &str) -> crate::error::Result<MpqArchive> {
+    let path = std::env::temp_dir().join(format!("bwmpq-{}-{name}", std::process::id()));
+    std::fs::write(&path, mpq).unwrap();
+    let archive = MpqArchive::open(&path);
+    let _ = std::fs::remove_file(&path);
+    archive
+}
+
 async fn process_iter_async_concurrent<I, T, F, J, R, F2, H, Z>(
     mut iter: I,
     cloner: H,
@@ -41,7 +56,7 @@ where
             }
         }
 
-        if futs.len() == 0 {
+        if futs.is_empty() {
             break;
         }
 
@@ -77,14 +92,10 @@ async fn download_test_artifacts<'a, T: AsRef<Path>, I: Iterator<Item = &'a str>
         |(path, client), id| async move {
             let path = path.join(id);
 
-            match tokio::fs::read(&path).await {
-                Ok(data) => {
-                    if hash(data.as_slice()) != id {
-                    } else {
-                        return;
-                    }
+            if let Ok(data) = tokio::fs::read(&path).await {
+                if hash(data.as_slice()) == id {
+                    return;
                 }
-                Err(_) => {}
             }
 
             let url = format!("https://scmscx.com/api/maps/{}", id);
@@ -98,7 +109,7 @@ async fn download_test_artifacts<'a, T: AsRef<Path>, I: Iterator<Item = &'a str>
                 .unwrap();
             let bytes = response.bytes().await.unwrap();
 
-            assert!(bytes.len() > 0);
+            assert!(!bytes.is_empty());
             assert_eq!(hash(&bytes[..]), id);
 
             tokio::fs::write(path, &bytes[..]).await.unwrap();
@@ -109,8 +120,9 @@ async fn download_test_artifacts<'a, T: AsRef<Path>, I: Iterator<Item = &'a str>
     anyhow::Ok(())
 }
 
-#[tokio::test]
-async fn can_extract_chks() {
+/// Downloads every map in `MPQS` that isn't already in /tmp/artifacts and runs `func` on each,
+/// with the map's hash, the hash of its scenario.chk and its bytes.
+async fn for_each_map(mut func: impl FnMut(&'static str, &'static str, Vec<u8>)) {
     let dir = PathBuf::from("/tmp/artifacts");
 
     download_test_artifacts(&dir, MPQS.iter().map(|x| x.0))
@@ -118,15 +130,1738 @@ async fn can_extract_chks() {
         .unwrap();
 
     for (mpq_hash, chk_hash) in MPQS {
-        let mpq_path = dir.join(mpq_hash);
-        let mpq_data = tokio::fs::read(&mpq_path).await.unwrap();
-        let mpq_hash2 = hash(&mpq_data.as_slice());
+        let mpq_data = tokio::fs::read(dir.join(mpq_hash)).await.unwrap();
+        func(mpq_hash, chk_hash, mpq_data);
+    }
+}
+
+/// A CHK section with a size that matches its data.
+fn section(name: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    [name.as_slice(), &(data.len() as i32).to_le_bytes(), data].concat()
+}
+
+#[tokio::test]
+async fn can_extract_chks() {
+    for_each_map(|mpq_hash, chk_hash, mpq_data| {
+        let mpq_hash2 = hash(mpq_data.as_slice());
         let chk = get_chk_from_mpq_in_memory(mpq_data.as_slice()).unwrap();
         let chk_hash2 = hash(chk.as_slice());
 
-        assert_eq!(*mpq_hash, mpq_hash2);
-        assert_eq!(*chk_hash, chk_hash2);
+        assert_eq!(mpq_hash, mpq_hash2);
+        assert_eq!(chk_hash, chk_hash2);
+    })
+    .await;
+}
+
+#[cfg(feature = "stormlib")]
+#[test]
+fn can_extract_files_at_a_locale() {
+    use crate::{FileOptions, MpqWriter, WriterOptions};
+
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_file(
+        "staredit\\scenario.chk",
+        b"neutral".to_vec(),
+        FileOptions::default(),
+    );
+    writer.add_file(
+        "staredit\\scenario.chk",
+        b"english".to_vec(),
+        FileOptions {
+            locale: 0x409,
+            ..Default::default()
+        },
+    );
+    let archive = open_with_stormlib(&writer.finish().unwrap(), "locales.scx").unwrap();
+
+    assert_eq!(
+        crate::extract_file(&archive, "staredit\\scenario.chk", 0).unwrap(),
+        b"neutral"
+    );
+    assert_eq!(
+        crate::extract_file(&archive, "staredit\\scenario.chk", 0x409).unwrap(),
+        b"english"
+    );
+    // StormLib falls back to the neutral entry, which isn't the one asked for.
+    let err = crate::extract_file(&archive, "staredit\\scenario.chk", 0x407).unwrap_err();
+    assert!(
+        matches!(err, crate::Error::DecoyLocale { found: 0, .. }),
+        "{err:?}"
+    );
+    let err = crate::extract_file(&archive, "missing.txt", 0).unwrap_err();
+    assert!(matches!(err, crate::Error::FileNotFound(_)), "{err:?}");
+}
+
+#[cfg(feature = "stormlib")]
+#[tokio::test]
+async fn can_reuse_archive_handle() {
+    for_each_map(|mpq_hash, chk_hash, _| {
+        let archive = MpqArchive::open(PathBuf::from("/tmp/artifacts").join(mpq_hash)).unwrap();
+
+        assert!(archive.has_file("staredit\\scenario.chk"));
+        assert!(!archive.has_file("staredit\\does-not-exist.chk"));
+
+        for _ in 0..2 {
+            let chk = archive.read_file("staredit\\scenario.chk").unwrap();
+            assert_eq!(chk_hash, hash(chk.as_slice()));
+        }
+    })
+    .await;
+}
+
+#[cfg(feature = "stormlib")]
+#[tokio::test]
+async fn can_list_archive_entries() {
+    for_each_map(|mpq_hash, _, _| {
+        let archive = MpqArchive::open(PathBuf::from("/tmp/artifacts").join(mpq_hash)).unwrap();
+        let entries: Vec<_> = archive.entries().unwrap().collect();
+
+        assert!(!entries.is_empty());
+        for entry in entries {
+            if entry.name.as_deref() == Some("staredit\\scenario.chk") {
+                assert!(entry.uncompressed_size > 0);
+            }
+        }
+    })
+    .await;
+}
+
+#[cfg(feature = "stormlib")]
+#[tokio::test]
+async fn pure_rust_reader_matches_stormlib() {
+    use crate::{MpqReader, ReaderOptions};
+
+    for_each_map(|mpq_hash, chk_hash, mpq_data| {
+        let archive = MpqArchive::open(PathBuf::from("/tmp/artifacts").join(mpq_hash)).unwrap();
+        let mut reader = MpqReader::from_bytes(&mpq_data, ReaderOptions::map()).unwrap();
+
+        let chk = reader.read_file("staredit\\scenario.chk").unwrap();
+        assert_eq!(chk_hash, hash(chk.as_slice()), "mpq: {mpq_hash}");
+
+        let mut names: Vec<String> = reader.entries().unwrap().filter_map(|x| x.name).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let ours = match reader.read_file(&name) {
+                // Huffman coded WAVs use weight tables that aren't ported yet.
+                Err(crate::Error::Unsupported(_)) => continue,
+                x => x,
+            };
+            match (ours, archive.read_file(&name)) {
+                (Ok(ours), Ok(theirs)) => {
+                    assert!(ours == theirs, "mpq: {mpq_hash}, name: {name}")
+                }
+                (Err(_), Err(_)) => {}
+                (ours, theirs) => panic!(
+                    "mpq: {mpq_hash}, name: {name}, ours: {:?}, StormLib: {:?}",
+                    ours.map(|x| x.len()),
+                    theirs.map(|x| x.len())
+                ),
+            }
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn can_extract_chks_concurrently() {
+    let dir = PathBuf::from("/tmp/artifacts");
+
+    download_test_artifacts(&dir, MPQS.iter().map(|x| x.0))
+        .await
+        .unwrap();
+
+    std::thread::scope(|s| {
+        for chunk in MPQS.chunks(MPQS.len().div_ceil(8)) {
+            let dir = &dir;
+            s.spawn(move || {
+                for (mpq_hash, chk_hash) in chunk {
+                    let chk = crate::get_chk_from_mpq_filename(dir.join(mpq_hash)).unwrap();
+                    assert_eq!(*chk_hash, hash(chk.as_slice()));
+                }
+            });
+        }
+    });
+}
+
+#[tokio::test]
+async fn hash_chain_resolution_finds_the_real_chk() {
+    for_each_map(|mpq_hash, chk_hash, mpq_data| {
+        let mut archive =
+            crate::MpqReader::from_bytes(&mpq_data, crate::ReaderOptions::map()).unwrap();
+
+        let (_, chk) = archive
+            .read_file_resolved("staredit\\scenario.chk", crate::Resolution::HashChain)
+            .unwrap();
+
+        assert_eq!(chk_hash, hash(chk.as_slice()), "mpq: {mpq_hash}");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn can_analyze_protection() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let report = crate::analyze_protection(&mpq_data).unwrap();
+
+        let selected: Vec<_> = report
+            .chk_candidates
+            .iter()
+            .filter(|x| x.selected_by_locale_probe)
+            .collect();
+        assert_eq!(selected.len(), 1, "mpq: {mpq_hash}");
+        assert!(selected[0].parses_as_chk, "mpq: {mpq_hash}");
+        assert_eq!(
+            report.chk_candidates.len() > 1,
+            report
+                .tricks
+                .iter()
+                .any(|x| matches!(x, crate::ProtectionTrick::DecoyChk { .. })),
+            "mpq: {mpq_hash}"
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn can_stream_files() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    for_each_map(|mpq_hash, chk_hash, mpq_data| {
+        let mut archive =
+            crate::MpqReader::from_bytes(&mpq_data, crate::ReaderOptions::map()).unwrap();
+
+        let mut file = archive
+            .open_file("staredit\\scenario.chk", crate::Resolution::LocaleProbe)
+            .unwrap();
+
+        // Read in odd sized chunks so reads straddle sector boundaries.
+        let mut chk = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            let len = file.read(&mut buf).unwrap();
+            if len == 0 {
+                break;
+            }
+            chk.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(chk_hash, hash(chk.as_slice()), "mpq: {mpq_hash}");
+
+        let mid = chk.len() as u64 / 2;
+        file.seek(SeekFrom::Start(mid)).unwrap();
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, chk[mid as usize..], "mpq: {mpq_hash}");
+
+        let mut archive = crate::MpqReader::from_bytes(
+            &mpq_data,
+            crate::ReaderOptions {
+                max_file_size: Some(chk.len() as u64 - 1),
+                ..crate::ReaderOptions::map()
+            },
+        )
+        .unwrap();
+        // Decoys may fail first with a different error, but the real chk must be refused.
+        assert!(archive.read_file("staredit\\scenario.chk").is_err());
+    })
+    .await;
+}
+
+#[test]
+fn probes_hash_table_like_storm() {
+    use crate::reader::hash_probe;
+
+    // A power of two size visits every slot once, starting from the name's hash.
+    let probe: Vec<_> = hash_probe("staredit\\scenario.chk", 16).collect();
+    let mut sorted = probe.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..16).collect::<Vec<_>>());
+    assert!(probe.windows(2).all(|x| x[1] == (x[0] + 1) % 16));
+
+    // Any other size is masked with size - 1, like Storm, so 6 only ever reaches 0, 1, 4 and 5.
+    for name in ["staredit\\scenario.chk", "(listfile)", "(attributes)"] {
+        let probe: Vec<_> = hash_probe(name, 6).collect();
+        assert!(!probe.is_empty() && probe.iter().all(|&x| x & !5 == 0));
+        let mut sorted = probe.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), probe.len());
+    }
+
+    assert_eq!(hash_probe("(listfile)", 0).count(), 0);
+}
+
+#[test]
+fn refuses_huge_declared_sizes() {
+    use crate::entry::{MPQ_FILE_COMPRESS, MPQ_FILE_EXISTS, MPQ_FILE_SINGLE_UNIT};
+    use crate::reader::BlockEntry;
+    use crate::{MpqReader, MpqWriter, ReaderOptions, Resolution, WriterOptions};
+    use std::io::Read;
+
+    // A few bytes of zlib claiming to be a 3.75GB single unit file.
+    let bytes = crate::compression::compress_zlib(b"not very big");
+    let block = BlockEntry {
+        offset: 0,
+        compressed_size: bytes.len() as u32,
+        file_size: 0xF000_0000,
+        flags: MPQ_FILE_EXISTS | MPQ_FILE_COMPRESS | MPQ_FILE_SINGLE_UNIT,
+    };
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_stored_file("staredit\\scenario.chk", block, bytes, 0);
+    let mpq = writer.finish().unwrap();
+
+    let err = crate::get_chk_from_mpq_in_memory(&mpq).unwrap_err();
+    assert!(matches!(err, crate::Error::FileTooBig { .. }), "{err:?}");
+
+    // Without a limit the declared size is only a bound on the output, nothing that big is
+    // reserved up front.
+    let unlimited = ReaderOptions {
+        max_file_size: None,
+        ..ReaderOptions::map()
+    };
+    let mut reader = MpqReader::from_bytes(&mpq, unlimited).unwrap();
+    let mut file = reader
+        .open_file("staredit\\scenario.chk", Resolution::HashChain)
+        .unwrap();
+    let err = file.read(&mut [0; 16]).unwrap_err();
+    assert!(err.to_string().contains("wrong size"), "{err}");
+
+    // Analyzing protection never decompresses a candidate that big, even without a limit.
+    let candidate = &reader.analyze_protection().chk_candidates[0];
+    assert!(!candidate.decompresses);
+    assert!(
+        candidate.error.as_ref().unwrap().contains("too big"),
+        "{candidate:?}"
+    );
+
+    // 32MB sectors.
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_file("file", vec![1; 1024], Default::default());
+    let mut mpq = writer.finish().unwrap();
+    mpq[14..16].copy_from_slice(&16u16.to_le_bytes());
+    let err = MpqReader::from_bytes(&mpq, unlimited)
+        .unwrap()
+        .read_file("file")
+        .unwrap_err();
+    assert!(matches!(err, crate::Error::Corrupt(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn can_walk_chk_sections() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = get_chk_from_mpq_in_memory(&mpq_data).unwrap();
+
+        assert!(crate::chk::sections(&chk).count() > 0, "mpq: {mpq_hash}");
+        for name in [b"VER ", b"DIM ", b"ERA ", b"MTXM"] {
+            assert!(
+                crate::chk::section(&chk, name).is_some(),
+                "mpq: {mpq_hash}, section: {}",
+                String::from_utf8_lossy(name)
+            );
+        }
+    })
+    .await;
+}
+
+#[test]
+fn chk_sections_follow_starcraft_rules() {
+    fn section(name: &[u8; 4], size: i32, data: &[u8]) -> Vec<u8> {
+        [name.as_slice(), &size.to_le_bytes(), data].concat()
+    }
+
+    let chk = [
+        section(b"VER ", 2, &[205, 0]),
+        section(b"MTXM", 4, &[1, 1, 1, 1]),
+        section(b"UNIT", 2, &[7, 7]),
+        section(b"MTXM", 2, &[2, 2]),
+        section(b"UNIT", 2, &[8, 8]),
+        section(b"ERA ", 2, &[4, 0]),
+        section(b"ERA ", 2, &[5, 0]),
+        section(b"DIM ", 100, &[64, 0, 64, 0]),
+    ]
+    .concat();
+
+    let names: Vec<_> = crate::chk::sections(&chk).map(|(name, _)| name).collect();
+    assert_eq!(
+        names,
+        [*b"VER ", *b"MTXM", *b"UNIT", *b"MTXM", *b"UNIT", *b"ERA ", *b"ERA ", *b"DIM "]
+    );
+
+    assert_eq!(
+        crate::chk::section(&chk, b"MTXM").unwrap(),
+        [2, 2, 1, 1].as_slice()
+    );
+    assert_eq!(
+        crate::chk::section(&chk, b"UNIT").unwrap(),
+        [7, 7, 8, 8].as_slice()
+    );
+    assert_eq!(
+        crate::chk::section(&chk, b"ERA ").unwrap(),
+        [5, 0].as_slice()
+    );
+    assert_eq!(
+        crate::chk::section(&chk, b"DIM ").unwrap(),
+        [64, 0, 64, 0].as_slice()
+    );
+    assert!(crate::chk::section(&chk, b"TRIG").is_none());
+
+    // An ERA section hidden inside JUNK, reached by jumping backwards. Jumping there a second time ends the file.
+    let hidden = [
+        section(b"VER ", 2, &[205, 0]),
+        section(b"JUNK", 10, &section(b"ERA ", 2, &[4, 0])),
+        section(b"JMP ", -18, &[]),
+    ]
+    .concat();
+    let names: Vec<_> = crate::chk::sections(&hidden)
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, [*b"VER ", *b"JUNK", *b"ERA "]);
+
+    // Trailing bytes too short to be a section header are ignored.
+    let junk = [section(b"VER ", 2, &[205, 0]), vec![1, 2, 3]].concat();
+    assert_eq!(crate::chk::sections(&junk).count(), 1);
+
+    // Each UNIT runs up to a jump to the next UNIT's header, inside its own data. Read in full
+    // that is 1000 sections of 8000 bytes each, out of 16000 bytes.
+    let count = 1000;
+    let units = (0..count).map(|_| section(b"UNIT", 8 * count - 8, &[]));
+    let jumps = (0..count).map(|_| section(b"JMP ", -8 * count, &[]));
+    let nested = units.chain(jumps).collect::<Vec<_>>().concat();
+    let read: usize = crate::chk::sections(&nested)
+        .map(|(_, x)| 8 + x.len())
+        .sum();
+    assert!(read <= 4 * nested.len(), "{read}");
+    assert!(crate::chk::sections(&nested).count() > 1);
+    assert!(crate::chk::section(&nested, b"UNIT").unwrap().len() <= 4 * nested.len());
+}
+
+#[tokio::test]
+async fn can_parse_chks() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+
+        assert!(chk.version().is_some(), "mpq: {mpq_hash}");
+        assert!(chk.tileset().is_some(), "mpq: {mpq_hash}");
+
+        let (width, height) = chk.dimensions().unwrap();
+        assert_eq!(
+            chk.mtxm().unwrap().len(),
+            width as usize * height as usize,
+            "mpq: {mpq_hash}"
+        );
+
+        for (id, string) in chk.decoded_strings() {
+            assert!(
+                !string
+                    .text
+                    .chars()
+                    .any(|c| c < ' ' && !matches!(c, '\t' | '\n' | '\r')),
+                "mpq: {mpq_hash}, string: {id}"
+            );
+        }
+
+        assert_eq!(
+            chk.triggers().len(),
+            chk.section(b"TRIG").map_or(0, |x| x.len() / 2400),
+            "mpq: {mpq_hash}"
+        );
+    })
+    .await;
+}
+
+#[test]
+fn chk_model_picks_the_sections_starcraft_uses() {
+    let mut unis = vec![0u8; 4048];
+    unis[0] = 1;
+    let unix = vec![0u8; 4168];
+
+    let bw = [
+        section(b"VER ", &205u16.to_le_bytes()),
+        section(b"UNIS", &unis),
+        section(b"UNIx", &unix),
+        section(b"STR ", b"\x01\x00\x04\x00old\0"),
+        section(b"STRx", b"\x01\x00\x00\x00\x08\x00\x00\x00new\0"),
+    ]
+    .concat();
+
+    let chk = crate::chk::Chk::parse(&bw);
+    assert!(chk.is_expansion());
+    assert!(!chk.unit_settings().unwrap().use_defaults[0]);
+    assert_eq!(chk.string_count(), 1);
+    assert_eq!(chk.string(1), Some(b"new".as_slice()));
+    assert_eq!(chk.string(2), None);
+
+    let original = [
+        section(b"VER ", &59u16.to_le_bytes()),
+        section(b"UNIS", &unis),
+        section(b"UNIx", &unix),
+        section(b"STR ", b"\x01\x00\x04\x00old\0"),
+    ]
+    .concat();
+
+    let chk = crate::chk::Chk::parse(&original);
+    assert!(!chk.is_expansion());
+    assert!(chk.unit_settings().unwrap().use_defaults[0]);
+    assert_eq!(chk.string(1), Some(b"old".as_slice()));
+
+    // 65535 by 65535 tiles would be 8 GiB of MTXM.
+    let huge = [
+        section(b"DIM ", &[0xff; 4]),
+        section(b"MTXM", &[1, 0, 2, 0]),
+    ]
+    .concat();
+    let chk = crate::chk::Chk::parse(&huge);
+    assert_eq!(chk.dimensions(), Some((0xffff, 0xffff)));
+    let mtxm = chk.mtxm().unwrap();
+    assert_eq!(mtxm.len(), 256 * 256);
+    assert_eq!(mtxm[..3], [1, 2, 0]);
+}
+
+#[test]
+fn can_decode_map_strings() {
+    use crate::chk::{Encoding, MapString};
+
+    let korean = "\u{C548}\u{B155}\u{D558}\u{C138}\u{C694}";
+    let cp949 = encoding_rs::EUC_KR.encode(korean).0.into_owned();
+    let cp1252 = b"Caf\xe9 au lait".as_slice();
+
+    assert_eq!(Encoding::detect(&[korean.as_bytes()]), Encoding::Utf8);
+    assert_eq!(Encoding::detect(&[&cp949]), Encoding::Cp949);
+    assert_eq!(Encoding::detect(&[cp1252]), Encoding::Cp1252);
+    assert_eq!(Encoding::detect(&[&cp949, b"plain ascii"]), Encoding::Cp949);
+
+    assert_eq!(MapString::decode(&cp949, Encoding::Cp949).text, korean);
+    assert_eq!(
+        MapString::decode(cp1252, Encoding::Cp1252).text,
+        "Caf\u{e9} au lait"
+    );
+
+    let colored = MapString::decode(b"\x13\x06Red\x04 White\nline\x06\x06", Encoding::Utf8);
+    assert_eq!(colored.text, "Red White\nline");
+    assert_eq!(colored.spans.len(), 2);
+    assert_eq!(colored.spans[0].color, Some(0x06));
+    assert_eq!(colored.spans[0].text, "Red");
+    assert_eq!(colored.spans[1].color, Some(0x04));
+    assert_eq!(colored.spans[1].text, " White\nline");
+}
+
+#[tokio::test]
+async fn can_decompile_triggers() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+        let decompiler = crate::trigedit::Decompiler::new(&chk);
+
+        let triggers = decompiler.triggers();
+        assert_eq!(
+            triggers
+                .lines()
+                .filter(|x| x.starts_with("Trigger("))
+                .count(),
+            chk.triggers().len(),
+            "mpq: {mpq_hash}"
+        );
+
+        let briefing = decompiler.briefing();
+        assert_eq!(
+            briefing
+                .lines()
+                .filter(|x| x.starts_with("Trigger("))
+                .count(),
+            chk.briefing_triggers().len(),
+            "mpq: {mpq_hash}"
+        );
+    })
+    .await;
+}
+
+#[test]
+fn decompiles_triggers_to_trigedit_text() {
+    let message = b"Hello \"world\"\nline\x02 <41>\0";
+    let mut str = vec![];
+    str.extend(2u16.to_le_bytes());
+    str.extend(6u16.to_le_bytes());
+    str.extend((6 + message.len() as u16).to_le_bytes());
+    str.extend(message);
+    str.extend(b"Base\0");
+
+    let mut mrgn = vec![0u8; 40];
+    mrgn[16..18].copy_from_slice(&2u16.to_le_bytes());
+
+    let mut trig = vec![0u8; 2400];
+    let mut condition = |index: usize, location: u32, quantity: u32, unit: u16, bytes: [u8; 2]| {
+        let c = &mut trig[index * 20..][..20];
+        c[0..4].copy_from_slice(&location.to_le_bytes());
+        c[8..12].copy_from_slice(&quantity.to_le_bytes());
+        c[12..14].copy_from_slice(&unit.to_le_bytes());
+        c[14..16].copy_from_slice(&bytes);
+    };
+    // Bring, an out of range unit, an unknown opcode, then an entry after the end of the list.
+    condition(0, 1, 5, 0, [0, 3]);
+    condition(1, 0, 7, 300, [10, 15]);
+    condition(2, 0, 0, 0, [0, 99]);
+    condition(4, 0, 0, 0, [0, 22]);
+
+    let mut action = |index: usize, location: u32, string: u32, number: u32, bytes: [u8; 3]| {
+        let a = &mut trig[320 + index * 32..][..32];
+        a[0..4].copy_from_slice(&location.to_le_bytes());
+        a[4..8].copy_from_slice(&string.to_le_bytes());
+        a[20..24].copy_from_slice(&number.to_le_bytes());
+        a[26..29].copy_from_slice(&bytes);
+    };
+    action(0, 0, 1, 0, [9, 0, 0x04]);
+    action(1, 0, 0, 2, [13, 4, 0x02]);
+    action(2, 2, 0, 0, [10, 0, 0]);
+    action(3, 1, 0, 0, [23, 0, 0]);
+    action(4, 0, 0, 0, [3, 0, 0]);
+    // Victory with a flag bit it doesn't use.
+    action(5, 0, 0, 0, [1, 0, 0x10]);
+    trig[2368..2372].copy_from_slice(&4u32.to_le_bytes());
+    trig[2372] = 1;
+    trig[2373] = 2;
+    trig[2399] = 1;
+
+    let chk = [
+        section(b"VER ", &205u16.to_le_bytes()),
+        section(b"STR ", &str),
+        section(b"MRGN", &mrgn),
+        section(b"TRIG", &trig),
+    ]
+    .concat();
+
+    assert_eq!(
+        crate::trigedit::decompile_triggers(&chk),
+        concat!(
+            "Trigger(\"Player 1\",27){\n",
+            "Conditions:\n",
+            "\tBring(\"Player 1\", \"Terran Marine\", \"Base\", At least, 5);\n",
+            "\tDeaths(\"Player 1\", 300, Exactly, 7);\n",
+            "\tCustom(0, 0, 0, 0, 0, 99, 0, 0, 0);\n",
+            "\tCustom(0, 0, 0, 0, 0, 0, 0, 0, 0);\n",
+            "\tAlways();\n",
+            "\n",
+            "Actions:\n",
+            "\tDisplay Text Message(Always Display, \"Hello \\\"world\\\"\\nline<02> <3C>41>\");\n",
+            "\tDisabled(Set Switch(\"Switch 3\", set));\n",
+            "\tCenter View(\"Location 2\");\n",
+            "\tKill Unit At Location(\"Player 1\", \"Terran Marine\", All, \"Base\");\n",
+            "\tPreserve Trigger();\n",
+            "\tCustom(0, 0, 0, 0, 0, 0, 0, 1, 0, 16, 0, 0);\n",
+            "\n",
+            "Flags:\n",
+            "\tExecution(0x00000004);\n",
+            "\tPlayer(1, 2);\n",
+            "}\n",
+            "\n",
+            "//-----------------------------------------------------------------//\n",
+            "\n",
+        )
+    );
+    assert_eq!(crate::trigedit::decompile_briefing(&chk), "");
+
+    // Everything, down to the records after the end of the list, compiles back.
+    let mut compiled = crate::chk::Chk::parse(&chk);
+    let text = crate::trigedit::decompile_triggers(&chk);
+    crate::trigedit::compile_triggers(&mut compiled, &text).unwrap();
+    assert_eq!(compiled.section(b"TRIG"), Some(trig.as_slice()));
+}
+
+#[tokio::test]
+async fn trigedit_round_trips() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let mut chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+
+        let decompiler = crate::trigedit::Decompiler::new(&chk);
+        let (triggers, briefing) = (decompiler.triggers(), decompiler.briefing());
+
+        let original = chk.clone();
+        crate::trigedit::compile_triggers(&mut chk, &triggers).unwrap();
+        crate::trigedit::compile_briefing(&mut chk, &briefing).unwrap();
+
+        // Byte for byte, up to any partial record at the end that StarCraft ignores too.
+        for name in [b"TRIG", b"MBRF"] {
+            let records = original.section(name).unwrap_or_default();
+            let records = &records[..records.len() / 2400 * 2400];
+            assert_eq!(
+                chk.section(name).unwrap_or_default(),
+                records,
+                "mpq: {mpq_hash}, section: {}",
+                String::from_utf8_lossy(name)
+            );
+        }
+
+        let decompiler = crate::trigedit::Decompiler::new(&chk);
+        assert_eq!(decompiler.triggers(), triggers, "mpq: {mpq_hash}");
+        assert_eq!(decompiler.briefing(), briefing, "mpq: {mpq_hash}");
+    })
+    .await;
+}
+
+#[test]
+fn compiles_trigedit_text() {
+    let mut chk = crate::chk::Chk::parse(
+        &[
+            section(b"VER ", &205u16.to_le_bytes()),
+            section(b"STR ", b"\x01\x00\x04\x00Hi\0"),
+            section(b"MRGN", &[0u8; 64 * 20]),
+        ]
+        .concat(),
+    );
+
+    let text = concat!(
+        "Trigger(\"Player 1\",\"Force 2\"){\n",
+        "Conditions:\n",
+        "\tAlways();\n",
+        "\n",
+        "Actions:\n",
+        "\tDisplay Text Message(Always Display, \"Hi\");\n",
+        "\tDisplay Text Message(Don't Always Display, \"New\\nline<02>\");\n",
+        "\tCenter View(\"Base\");\n",
+        "\tDisabled(Set Deaths(\"Player 1\", 300, Add, 7));\n",
+        "\tCustom(0, 0, 0, 0, 0, 0, 0, 200, 0, 0, 0, 0);\n",
+        "}\n",
+        "\n",
+        "//-----------------------------------------------------------------//\n",
+        "\n",
+    );
+    crate::trigedit::compile_triggers(&mut chk, text).unwrap();
+
+    assert_eq!(chk.string_count(), 3);
+    assert_eq!(chk.string(1), Some(b"Hi".as_slice()));
+    assert_eq!(chk.string(2), Some(b"New\nline\x02".as_slice()));
+    assert_eq!(chk.string(3), Some(b"Base".as_slice()));
+    assert_eq!(chk.locations()[0].name, 3);
+
+    let trigger = &chk.triggers()[0];
+    assert_eq!(trigger.players[0], 1);
+    assert_eq!(trigger.players[19], 1);
+    assert_eq!(trigger.actions[3].flags, 0x02);
+    assert_eq!(trigger.actions[3].unit_type, 300);
+    assert_eq!(crate::trigedit::Decompiler::new(&chk).triggers(), text);
+
+    let before = chk.clone();
+    let err = crate::trigedit::compile_triggers(
+        &mut chk,
+        "Trigger(\"Player 1\"){\nConditions:\n\tBring(\"Player 1\", \"Terran Marine\");\n",
+    )
+    .unwrap_err();
+    assert_eq!((err.line, err.column), (3, 2));
+    assert!(err.message.contains("takes 5 arguments"), "{err}");
+
+    let err = crate::trigedit::compile_triggers(
+        &mut chk,
+        "Trigger(\"Player 1\"){\nConditions:\n\tDeaths(\"Player 1\", \"Terran Marine\", Roughly, 1);\n",
+    )
+    .unwrap_err();
+    assert_eq!((err.line, err.column), (3, 38));
+    assert_eq!(chk, before);
+
+    // CP1252 has no Hangul, which would otherwise be written out as `&#54620;`.
+    let mut chk = crate::chk::Chk::parse(&section(b"STR ", b"\x01\x00\x04\x00Caf\xe9\0"));
+    assert_eq!(chk.encoding(), crate::chk::Encoding::Cp1252);
+    let err = crate::trigedit::compile_triggers(
+        &mut chk,
+        "Trigger(\"Player 1\"){\nConditions:\nActions:\n\tDisplay Text Message(Always Display, \"Caf\u{e9} \u{d55c}\");\n}\n",
+    )
+    .unwrap_err();
+    assert_eq!((err.line, err.column), (4, 45));
+    assert!(err.message.contains("Cp1252"), "{err}");
+}
+
+#[tokio::test]
+async fn chk_to_bytes_round_trips() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+
+        let bytes = chk.to_bytes();
+        let reparsed = crate::chk::Chk::parse(&bytes);
+        assert_eq!(reparsed.to_bytes(), bytes, "mpq: {mpq_hash}");
+        assert_eq!(reparsed.triggers(), chk.triggers(), "mpq: {mpq_hash}");
+        assert_eq!(reparsed.units(), chk.units(), "mpq: {mpq_hash}");
+        assert_eq!(reparsed.mtxm(), chk.mtxm(), "mpq: {mpq_hash}");
+
+        let mut compacted = reparsed.clone();
+        compacted.compact_strings();
+        assert_eq!(
+            compacted.string_count(),
+            chk.string_count(),
+            "mpq: {mpq_hash}"
+        );
+        for id in chk.string_references() {
+            assert_eq!(
+                compacted.string(id),
+                chk.string(id),
+                "mpq: {mpq_hash}, string: {id}"
+            );
+        }
+    })
+    .await;
+}
+
+#[test]
+fn chk_to_bytes_writes_canonical_sections() {
+    let str = b"\x03\x00\x08\x00\x0d\x00\x14\x00Name\0unused\0Name\0";
+    let forc = [0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut chk = crate::chk::Chk::parse(
+        &[
+            section(b"ABCD", b"keep"),
+            section(b"VER ", &[59]),
+            section(b"\xffJNK", b"junk"),
+            section(b"STR ", str),
+            section(b"FORC", &forc),
+            section(b"SPRP", &[1, 0, 0]),
+            section(b"VER ", &[205]),
+        ]
+        .concat(),
+    );
+
+    let expected = |str: &[u8]| {
+        [
+            section(b"VER ", &[205, 0]),
+            section(b"STR ", str),
+            section(b"SPRP", &[1, 0, 0, 0]),
+            section(b"FORC", &forc),
+            section(b"ABCD", b"keep"),
+        ]
+        .concat()
+    };
+    assert_eq!(chk.to_bytes(), expected(str));
+
+    chk.compact_strings();
+    assert_eq!(
+        chk.to_bytes(),
+        expected(b"\x03\x00\x09\x00\x08\x00\x09\x00\0Name\0")
+    );
+    assert_eq!(chk.string(1), Some(b"Name".as_slice()));
+    assert_eq!(chk.string(2), Some(b"".as_slice()));
+    assert_eq!(chk.string(3), Some(b"Name".as_slice()));
+}
+
+#[tokio::test]
+async fn can_replace_scenario_chk() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = get_chk_from_mpq_in_memory(&mpq_data).unwrap();
+
+        let mut edited = crate::chk::Chk::parse(&chk);
+        edited.compact_strings();
+        let edited = edited.to_bytes();
+
+        let options = crate::FileOptions::default();
+        let replaced =
+            crate::replace_file(&mpq_data, "staredit\\scenario.chk", &edited, options).unwrap();
+        assert_eq!(
+            get_chk_from_mpq_in_memory(&replaced).unwrap(),
+            edited,
+            "mpq: {mpq_hash}"
+        );
+
+        #[cfg(feature = "stormlib")]
+        {
+            let archive = open_with_stormlib(&replaced, &format!("{mpq_hash}.scx")).unwrap();
+            assert_eq!(
+                archive.read_file("staredit\\scenario.chk").unwrap(),
+                edited,
+                "mpq: {mpq_hash}"
+            );
+        }
+    })
+    .await;
+}
+
+#[test]
+fn can_implode_pkware_stream() {
+    let mut inputs: Vec<Vec<u8>> = vec![
+        vec![],
+        b"AIAIAIAIAIAIA".to_vec(),
+        vec![7; 5000],
+        (0..20000u32).map(|x| (x * x % 251) as u8).collect(),
+    ];
+    // Repeats further apart than the largest match and the dictionary.
+    let block: Vec<u8> = (0..600u32).map(|x| (x * 31 % 256) as u8).collect();
+    inputs.push([block.as_slice(), &[1; 3000], &block, &block].concat());
+
+    for input in inputs {
+        let imploded = crate::compression::implode(&input);
+        assert_eq!(
+            crate::compression::explode(&imploded, input.len()).unwrap(),
+            input
+        );
+    }
+
+    assert!(crate::compression::implode(&[7; 5000]).len() < 100);
+}
+
+#[test]
+fn can_write_archives() {
+    use crate::{Compression, FileOptions, MpqReader, MpqWriter, ReaderOptions, WriterOptions};
+
+    let chk: Vec<u8> = (0..10000u32).map(|x| (x % 97) as u8).collect();
+    let wav: Vec<u8> = (0..9000u32).map(|x| (x * 7 % 13) as u8).collect();
+    let readme = b"not compressed".to_vec();
+
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_file(
+        "staredit\\scenario.chk",
+        vec![1, 2, 3],
+        FileOptions::default(),
+    );
+    writer.add_file(
+        "staredit\\scenario.chk",
+        chk.clone(),
+        FileOptions::default(),
+    );
+    writer.add_file(
+        "staredit\\wav\\sound.wav",
+        wav.clone(),
+        FileOptions {
+            compression: Compression::Zlib,
+            encrypt: true,
+            fix_key: true,
+            locale: 0x409,
+        },
+    );
+    writer.add_file(
+        "readme.txt",
+        readme.clone(),
+        FileOptions {
+            compression: Compression::None,
+            encrypt: true,
+            ..Default::default()
+        },
+    );
+    let mpq = writer.finish().unwrap();
+
+    let mut reader = MpqReader::from_bytes(&mpq, ReaderOptions::default()).unwrap();
+    assert_eq!(reader.read_file("staredit\\scenario.chk").unwrap(), chk);
+    assert_eq!(reader.read_file("staredit\\wav\\sound.wav").unwrap(), wav);
+    assert_eq!(reader.read_file("readme.txt").unwrap(), readme);
+    assert_eq!(
+        reader.read_file("(listfile)").unwrap(),
+        b"staredit\\scenario.chk\r\nstaredit\\wav\\sound.wav\r\nreadme.txt\r\n"
+    );
+    assert_eq!(reader.read_file("(attributes)").unwrap().len(), 8 + 5 * 4);
+
+    let entries: Vec<_> = reader.entries().unwrap().collect();
+    assert_eq!(entries.len(), 5);
+    assert!(entries.iter().all(|x| x.name.is_some()));
+    assert!(crate::analyze_protection(&mpq).unwrap().tricks.is_empty());
+
+    let replaced = crate::replace_file(
+        &mpq,
+        "staredit\\scenario.chk",
+        b"new chk",
+        FileOptions::default(),
+    )
+    .unwrap();
+    let mut reader = MpqReader::from_bytes(&replaced, ReaderOptions::default()).unwrap();
+    assert_eq!(
+        reader.read_file("staredit\\scenario.chk").unwrap(),
+        b"new chk"
+    );
+    assert_eq!(reader.read_file("staredit\\wav\\sound.wav").unwrap(), wav);
+
+    #[cfg(feature = "stormlib")]
+    {
+        let archive = open_with_stormlib(&mpq, "written.mpq").unwrap();
+        assert_eq!(archive.read_file("staredit\\scenario.chk").unwrap(), chk);
+        assert_eq!(archive.read_file("staredit\\wav\\sound.wav").unwrap(), wav);
+        assert_eq!(archive.read_file("readme.txt").unwrap(), readme);
+        assert_eq!(archive.entries().unwrap().count(), 5);
+
+        let archive = open_with_stormlib(&replaced, "replaced.mpq").unwrap();
+        assert_eq!(
+            archive.read_file("staredit\\scenario.chk").unwrap(),
+            b"new chk"
+        );
+        assert_eq!(archive.read_file("staredit\\wav\\sound.wav").unwrap(), wav);
+    }
+
+    assert!(matches!(
+        crate::replace_file(&mpq, "missing.txt", b"", FileOptions::default()),
+        Err(crate::Error::FileNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn can_sanitize_maps() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = get_chk_from_mpq_in_memory(&mpq_data).unwrap();
+
+        let sanitized = crate::sanitize(&mpq_data).unwrap();
+        assert_eq!(
+            get_chk_from_mpq_in_memory(&sanitized).unwrap(),
+            crate::chk::Chk::parse(&chk).to_bytes(),
+            "mpq: {mpq_hash}"
+        );
+        assert!(
+            crate::analyze_protection(&sanitized)
+                .unwrap()
+                .tricks
+                .is_empty(),
+            "mpq: {mpq_hash}"
+        );
+        assert_eq!(
+            crate::sanitize(&sanitized).unwrap(),
+            sanitized,
+            "mpq: {mpq_hash}"
+        );
+    })
+    .await;
+}
+
+#[test]
+fn sanitize_keeps_only_what_starcraft_reads() {
+    use crate::entry::{MPQ_FILE_COMPRESS, MPQ_FILE_EXISTS, MPQ_FILE_IMPLODE};
+    use crate::reader::BlockEntry;
+    use crate::{FileOptions, MpqReader, MpqWriter, ReaderOptions, WriterOptions};
+
+    fn stored(file_size: u32, flags: u32) -> BlockEntry {
+        BlockEntry {
+            offset: 0,
+            compressed_size: 0,
+            file_size,
+            flags: MPQ_FILE_EXISTS | flags,
+        }
     }
+
+    let mut wav = vec![0; 2048];
+    wav[0] = 1;
+    let chk = [
+        section(b"\xffJNK", b"junk"),
+        section(b"VER ", &[59, 0]),
+        section(b"STR ", b"\x01\x00\x04\x00staredit\\wav\\a.wav\0"),
+        section(b"WAV ", &wav),
+        section(b"VER ", &[205, 0]),
+    ]
+    .concat();
+    // A Huffman coded sector using weight table 1. Only table 0 is ported, so this can't be
+    // decompressed and has to be copied as it is.
+    let huffman = [8u32.to_le_bytes(), 13u32.to_le_bytes()].concat();
+    let huffman = [huffman.as_slice(), &[0x41, 1, 2, 3, 4]].concat();
+
+    let mut writer = MpqWriter::new(WriterOptions {
+        sector_size_shift: 2,
+        listfile: false,
+        attributes: false,
+        ..Default::default()
+    });
+    writer.add_stored_file(
+        "staredit\\scenario.chk",
+        stored(100, MPQ_FILE_IMPLODE),
+        vec![0xFF; 16],
+        0x409,
+    );
+    writer.add_file(
+        "staredit\\scenario.chk",
+        chk.clone(),
+        FileOptions {
+            encrypt: true,
+            fix_key: true,
+            ..Default::default()
+        },
+    );
+    // First in the hash chain, but the locale probe looks at 0x409 before 0.
+    writer.add_stored_file(
+        "staredit\\wav\\a.wav",
+        stored(100, MPQ_FILE_IMPLODE),
+        vec![0xFF; 16],
+        0,
+    );
+    writer.add_stored_file(
+        "staredit\\wav\\a.wav",
+        stored(100, MPQ_FILE_COMPRESS),
+        huffman.clone(),
+        0x409,
+    );
+    writer.add_file("unlisted.txt", b"lost".to_vec(), FileOptions::default());
+    let mpq = writer.finish().unwrap();
+    assert!(crate::analyze_protection(&mpq).unwrap().is_protected());
+
+    let sanitized = crate::sanitize(&mpq).unwrap();
+    let mut reader = MpqReader::from_bytes(&sanitized, ReaderOptions::default()).unwrap();
+    assert_eq!(
+        reader.read_file("staredit\\scenario.chk").unwrap(),
+        crate::chk::Chk::parse(&chk).to_bytes()
+    );
+    assert_eq!(
+        reader.read_file("(listfile)").unwrap(),
+        b"staredit\\scenario.chk\r\nstaredit\\wav\\a.wav\r\n"
+    );
+    assert_eq!(reader.header.sector_size_shift, 2);
+    assert_eq!(reader.entries().unwrap().count(), 4);
+
+    let wav_index = reader.hash_chain("staredit\\wav\\a.wav").next().unwrap();
+    let (block, bytes) = reader
+        .read_raw_hash_entry("staredit\\wav\\a.wav", wav_index)
+        .unwrap();
+    assert_eq!((block.file_size, bytes), (100, huffman));
+
+    assert!(crate::analyze_protection(&sanitized)
+        .unwrap()
+        .tricks
+        .is_empty());
+    assert_eq!(crate::sanitize(&sanitized).unwrap(), sanitized);
+}
+
+#[tokio::test]
+async fn canonical_hash_ignores_packaging() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let mut chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+        let expected = crate::chk::canonical_hash(&chk);
+
+        let sanitized = crate::sanitize(&mpq_data).unwrap();
+        let sanitized = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&sanitized).unwrap());
+        assert_eq!(
+            crate::chk::canonical_hash(&sanitized),
+            expected,
+            "mpq: {mpq_hash}"
+        );
+
+        chk.compact_strings();
+        assert_eq!(
+            crate::chk::canonical_hash(&chk),
+            expected,
+            "mpq: {mpq_hash}"
+        );
+    })
+    .await;
+}
+
+#[test]
+fn canonical_hash_is_stable() {
+    use crate::chk::canonical_hash;
+
+    fn sprp(name: u16) -> Vec<u8> {
+        [name.to_le_bytes(), [0, 0]].concat()
+    }
+
+    let unit = [[0; 4], [32, 0, 32, 0], [7, 0, 0, 0]].concat();
+    let chk = crate::chk::Chk::parse(
+        &[
+            section(b"VER ", &[205, 0]),
+            section(b"DIM ", &[2, 0, 1, 0]),
+            section(b"MTXM", &[1, 0, 2, 0]),
+            section(b"STR ", b"\x01\x00\x04\x00Map\0"),
+            section(b"SPRP", &sprp(1)),
+            section(b"UNIT", &unit),
+        ]
+        .concat(),
+    );
+    // The same map with another string table, section order, junk and editor-only sections.
+    let repacked = crate::chk::Chk::parse(
+        &[
+            section(b"\xffJNK", b"junk"),
+            section(b"UNIT", &unit),
+            section(b"ISOM", &[1, 2, 3, 4]),
+            section(
+                b"STRx",
+                b"\x02\x00\x00\x00\x0c\x00\x00\x00\x10\x00\x00\x00old\0Map\0",
+            ),
+            section(b"SPRP", &sprp(2)),
+            section(b"DIM ", &[2, 0, 1, 0, 0xFF]),
+            section(b"MTXM", &[1, 0, 2, 0, 3, 0]),
+            section(b"VER ", &[206, 0]),
+        ]
+        .concat(),
+    );
+    let renamed = crate::chk::Chk::parse(
+        &[chk.to_bytes(), section(b"STR ", b"\x01\x00\x04\x00Maq\0")].concat(),
+    );
+
+    let hash = canonical_hash(&chk);
+    assert_eq!(canonical_hash(&repacked), hash);
+    assert_ne!(canonical_hash(&renamed), hash);
+    // This must never change, the hashes end up in databases.
+    assert_eq!(
+        hash,
+        "7e8bbce944190257575f4b4ae53e8185b886262d6e6a684c5c38b17954388014"
+    );
+
+    // DIM can claim far more tiles than StarCraft loads. MASK is padded to 256 by 256 at most.
+    let huge = crate::chk::Chk::parse(
+        &[
+            section(b"DIM ", &[0xff; 4]),
+            section(b"MTXM", &[1, 0]),
+            section(b"MASK", &[0xff]),
+        ]
+        .concat(),
+    );
+    assert_eq!(canonical_hash(&huge).len(), 64);
+}
+
+#[tokio::test]
+async fn fingerprints_survive_sanitizing() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+
+        let sanitized = crate::sanitize(&mpq_data).unwrap();
+        let sanitized = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&sanitized).unwrap());
+        assert_eq!(
+            crate::chk::fingerprint(&sanitized),
+            crate::chk::fingerprint(&chk),
+            "mpq: {mpq_hash}"
+        );
+        assert_eq!(crate::chk::similarity(&chk, &sanitized), 1.0);
+    })
+    .await;
+}
+
+#[test]
+fn similarity_finds_edited_maps() {
+    use crate::chk::{fingerprint, similarity, Chk, Trigger};
+
+    // A 32x32 map with 4 terrain bands, some units and one trigger per unit.
+    fn map(seed: u16, edit: bool) -> Chk {
+        let mut chk = Chk::default();
+        chk.set_section(*b"VER ", vec![205, 0]);
+        chk.set_section(*b"ERA ", vec![seed as u8 % 2, 0]);
+        chk.set_section(*b"DIM ", vec![32, 0, 32, 0]);
+        let mut mtxm: Vec<u16> = (0..32 * 32).map(|i| (i / 256 + seed) << 4).collect();
+        if edit {
+            mtxm[0..10].fill(0x123);
+        }
+        chk.set_section(
+            *b"MTXM",
+            mtxm.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        );
+
+        let mut units = Vec::new();
+        let mut triggers = Vec::new();
+        for i in 0..20u16 {
+            let x = if edit && i == 0 { 999 } else { i * 64 + seed };
+            units.extend([0; 4]);
+            units.extend(
+                [
+                    x.to_le_bytes(),
+                    (i * 32).to_le_bytes(),
+                    (i + seed).to_le_bytes(),
+                ]
+                .concat(),
+            );
+            units.extend([0; 26]);
+
+            let text = format!("Unit number {i} of map {seed}!");
+            let text = if edit && i == 0 {
+                "Changed".to_owned()
+            } else {
+                text
+            };
+            let string = chk.add_string(text.as_bytes()).unwrap();
+            let mut trigger = Trigger::default();
+            trigger.players[(i % 8) as usize] = 1;
+            trigger.conditions[0].condition = 22;
+            trigger.actions[0].action = 9;
+            trigger.actions[0].string = string;
+            trigger.actions[1].action = (i + seed) as u8 % 60;
+            triggers.extend(trigger.to_bytes());
+        }
+        chk.set_section(*b"UNIT", units);
+        chk.set_section(*b"TRIG", triggers);
+        chk
+    }
+
+    let original = map(1, false);
+    let edited = map(1, true);
+    let unrelated = map(2, false);
+
+    assert_eq!(similarity(&original, &original), 1.0);
+    let mut compacted = Chk::parse(&original.to_bytes());
+    compacted.compact_strings();
+    assert_eq!(fingerprint(&compacted), fingerprint(&original));
+
+    let close = similarity(&original, &edited);
+    let far = similarity(&original, &unrelated);
+    assert!(close > 0.8 && close < 1.0, "close: {close}");
+    assert!(far < 0.3, "far: {far}");
+    assert_eq!(similarity(&Chk::default(), &Chk::default()), 1.0);
+
+    // DIM claims 65535 by 65535 tiles, but only 256 by 256 are read.
+    let mut huge = Chk::default();
+    huge.set_section(*b"DIM ", vec![0xff; 4]);
+    huge.set_section(*b"MTXM", vec![0x20, 0x01]);
+    let mut largest = huge.clone();
+    largest.set_section(*b"DIM ", vec![0, 1, 0, 1]);
+    assert_eq!(fingerprint(&huge).terrain, vec![0; 64]);
+    assert_eq!(fingerprint(&huge), fingerprint(&largest));
+}
+
+#[tokio::test]
+async fn can_render_minimaps() {
+    use crate::render::{render, RenderOptions, Scale, TilesetFiles};
+
+    // The real tilesets can't be downloaded, so every tile gets a made up colour.
+    let tileset = TilesetFiles::from_bytes(
+        (0..0x10000u32)
+            .flat_map(|x| (x as u16).to_le_bytes())
+            .collect(),
+        (0..0x10000u32)
+            .flat_map(|x| ((x as u16) << 1).to_le_bytes())
+            .collect(),
+        (0..0x10000u32).map(|x| (x / 64) as u8).collect(),
+        (0..=255u8).flat_map(|x| [x, 255 - x, x / 2, 0]).collect(),
+    )
+    .unwrap();
+    let options = RenderOptions {
+        scale: Scale::Minimap,
+        units: true,
+        sprites: true,
+    };
+
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+        let (width, height) = chk.dimensions().unwrap();
+
+        let image = render(&chk, &tileset, options).unwrap();
+        assert_eq!(
+            (image.width, image.height),
+            (width as u32, height as u32),
+            "mpq: {mpq_hash}"
+        );
+        assert!(!image.to_png().unwrap().is_empty());
+    })
+    .await;
+}
+
+#[test]
+fn renders_terrain_and_units() {
+    use crate::render::{render, RenderOptions, Scale, TilesetFiles};
+
+    // Group 0 tile 0 is megatile 0, group 1 tile 2 is megatile 1.
+    let mut cv5 = vec![0; 2 * 52];
+    cv5[52 + 20 + 4] = 1;
+    // Megatile 0 is minitile 0 throughout, megatile 1 is minitile 1 flipped.
+    let vx4 = [vec![0; 32], [3, 0].repeat(16)].concat();
+    // Minitile 0 is colour 1, minitile 1 is colour 3 with a column of colour 2 on the left.
+    let vr4 = [vec![1; 64], [2, 3, 3, 3, 3, 3, 3, 3].repeat(8)].concat();
+    let mut wpe = vec![0; 1024];
+    wpe[4..7].copy_from_slice(&[10, 20, 30]);
+    wpe[8..11].copy_from_slice(&[200, 0, 0]);
+    wpe[12..15].copy_from_slice(&[0, 200, 0]);
+    let tileset = TilesetFiles::from_bytes(cv5, vx4, vr4, wpe).unwrap();
+
+    let mut chk = crate::chk::Chk::default();
+    chk.set_section(*b"DIM ", vec![2, 0, 1, 0]);
+    chk.set_section(*b"MTXM", vec![0x00, 0x00, 0x12, 0x00]);
+    let mut unit = vec![0; 36];
+    unit[4..8].copy_from_slice(&[16, 0, 16, 0]);
+    unit[16] = 1;
+    chk.set_section(*b"UNIT", unit);
+
+    let pixel = |image: &crate::render::Image, x: usize, y: usize| {
+        let i = (y * image.width as usize + x) * 3;
+        image.pixels[i..i + 3].to_vec()
+    };
+
+    let image = render(&chk, &tileset, RenderOptions::default()).unwrap();
+    assert_eq!((image.width, image.height), (64, 32));
+    assert_eq!(pixel(&image, 0, 0), [10, 20, 30]);
+    assert_eq!(pixel(&image, 32, 5), [0, 200, 0]);
+    assert_eq!(pixel(&image, 39, 5), [200, 0, 0]);
+
+    let options = RenderOptions {
+        units: true,
+        ..Default::default()
+    };
+    let image = render(&chk, &tileset, options).unwrap();
+    assert_eq!(pixel(&image, 16, 16), [12, 72, 204]);
+    assert_eq!(pixel(&image, 0, 0), [10, 20, 30]);
+
+    let options = RenderOptions {
+        scale: Scale::Minimap,
+        ..Default::default()
+    };
+    let image = render(&chk, &tileset, options).unwrap();
+    assert_eq!(image.pixels, [10, 20, 30, 25, 175, 0]);
+
+    let png = image.to_png().unwrap();
+    let mut reader = png::Decoder::new(std::io::Cursor::new(png))
+        .read_info()
+        .unwrap();
+    let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, image.pixels);
+
+    assert!(render(&crate::chk::Chk::default(), &tileset, options).is_err());
+
+    // DIM comes from the map, so a huge one is refused rather than allocated, and MTXM past
+    // what DIM covers is ignored.
+    chk.set_section(*b"DIM ", vec![0xFF, 0xFF, 0xFF, 0xFF]);
+    assert!(matches!(
+        render(&chk, &tileset, RenderOptions::default()),
+        Err(crate::Error::Corrupt(_))
+    ));
+    chk.set_section(*b"DIM ", vec![1, 0, 1, 0]);
+    let image = render(&chk, &tileset, options).unwrap();
+    assert_eq!(image.pixels, [10, 20, 30]);
+}
+
+#[test]
+fn loads_tileset_files_by_any_case() {
+    use crate::chk::Tileset;
+    use crate::render::TilesetFiles;
+
+    let dir = std::env::temp_dir().join(format!("bwmpq-tileset-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in [
+        "AshWorld.cv5",
+        "AshWorld.vx4",
+        "AshWorld.vr4",
+        "ASHWORLD.WPE",
+    ] {
+        std::fs::write(dir.join(name), vec![0; 1024]).unwrap();
+    }
+    std::fs::write(dir.join("Jungle.cv5"), vec![0; 1024]).unwrap();
+
+    let ashworld = TilesetFiles::load(&dir, Tileset::Ashworld);
+    let jungle = TilesetFiles::load(&dir, Tileset::Jungle);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    ashworld.unwrap();
+    match jungle {
+        Err(crate::Error::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+        x => panic!("{x:?}"),
+    }
+}
+
+#[tokio::test]
+async fn can_summarize_maps() {
+    for_each_map(|mpq_hash, _, mpq_data| {
+        let chk = crate::chk::Chk::parse(&get_chk_from_mpq_in_memory(&mpq_data).unwrap());
+
+        let summary = crate::summarize(&mpq_data).unwrap();
+        assert_eq!(
+            summary,
+            crate::MapSummary::from_chk(&chk),
+            "mpq: {mpq_hash}"
+        );
+        assert_eq!(
+            Some((summary.width, summary.height)),
+            chk.dimensions(),
+            "mpq: {mpq_hash}"
+        );
+        assert!(summary.player_count <= 8, "mpq: {mpq_hash}");
+        assert_eq!(
+            summary.units.iter().map(|x| x.count).sum::<usize>(),
+            chk.units().len(),
+            "mpq: {mpq_hash}"
+        );
+    })
+    .await;
+}
+
+#[test]
+fn summarizes_map_metadata() {
+    use crate::chk::{Chk, Owner, Race, Tileset, Trigger};
+    use crate::{FileOptions, GameType, MpqWriter, WriterOptions};
+
+    fn unit(unit_id: u16, owner: u8, resources: u32) -> Vec<u8> {
+        let mut unit = vec![0; 36];
+        unit[8..10].copy_from_slice(&unit_id.to_le_bytes());
+        unit[16] = owner;
+        unit[20..24].copy_from_slice(&resources.to_le_bytes());
+        unit
+    }
+
+    let mut chk = Chk::default();
+    chk.set_section(*b"VER ", vec![205, 0]);
+    chk.set_section(*b"ERA ", vec![4, 0]);
+    chk.set_section(*b"DIM ", vec![64, 0, 96, 0]);
+    chk.set_section(*b"OWNR", vec![6, 5, 6, 0, 0, 0, 0, 0, 7, 7, 7, 7]);
+    chk.set_section(*b"SIDE", vec![0, 1, 2, 0, 0, 0, 0, 0, 4, 4, 4, 4]);
+    chk.set_section(*b"FORC", vec![0, 1, 0, 0, 0, 0, 0, 0]);
+    let title = chk.add_string(b"\x03Jungle \x04Story").unwrap() as u16;
+    chk.set_section(*b"SPRP", [title.to_le_bytes(), [0, 0]].concat());
+    let mut anywhere = vec![0; 64 * 20];
+    anywhere[63 * 20 + 8] = 1;
+    chk.set_section(*b"MRGN", anywhere);
+    chk.set_section(
+        *b"UNIT",
+        [
+            unit(176, 11, 1500),
+            unit(176, 11, 1500),
+            unit(188, 11, 5000),
+            unit(0, 0, 0),
+            unit(214, 0, 0),
+        ]
+        .concat(),
+    );
+
+    let mut trigger = Trigger::default();
+    trigger.conditions[0].condition = 22;
+    trigger.actions[0].action = 2;
+    chk.set_section(*b"TRIG", trigger.to_bytes());
+
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_file(
+        "staredit\\scenario.chk",
+        chk.to_bytes(),
+        FileOptions::default(),
+    );
+    let summary = crate::summarize(&writer.finish().unwrap()).unwrap();
+
+    assert_eq!(summary.title.as_deref(), Some("Jungle Story"));
+    assert_eq!(summary.description, None);
+    assert_eq!(summary.tileset, Some(Tileset::Jungle));
+    assert_eq!((summary.width, summary.height), (64, 96));
+    assert!(summary.expansion);
+    assert_eq!(summary.game_type, GameType::Melee);
+    assert_eq!(
+        (
+            summary.player_count,
+            summary.human_slots,
+            summary.computer_slots
+        ),
+        (3, 2, 1)
+    );
+    assert_eq!(summary.players[1].owner, Owner::Computer);
+    assert_eq!(summary.players[1].race, Race::Terran);
+    assert_eq!(summary.players[1].force, 1);
+    assert_eq!(summary.players[2].player, 3);
+    assert_eq!(summary.trigger_count, 1);
+    assert_eq!(summary.location_count, 1);
+    assert_eq!(summary.string_count, 1);
+    assert_eq!(summary.units.len(), 4);
+    assert_eq!(
+        summary.units[1].name.as_deref(),
+        Some("Mineral Field (Type 1)")
+    );
+    assert_eq!(summary.units[1].count, 2);
+    assert_eq!((summary.minerals, summary.gas), (3000, 5000));
+
+    // Any released VER will do, an original StarCraft map is summarized the same.
+    chk.set_section(*b"VER ", vec![59, 0]);
+    assert_eq!(crate::MapSummary::from_chk(&chk).game_type, GameType::Melee);
+    // StarCraft doesn't load beta maps, or maps without a VER.
+    let mut beta = chk.clone();
+    beta.set_section(*b"VER ", vec![47, 0]);
+    assert_eq!(
+        crate::MapSummary::from_chk(&beta).game_type,
+        GameType::UseMapSettings
+    );
+    beta.remove_section(b"VER ");
+    assert_eq!(
+        crate::MapSummary::from_chk(&beta).game_type,
+        GameType::UseMapSettings
+    );
+
+    // Without triggers the start location decides.
+    let mut untriggered = chk.clone();
+    untriggered.set_section(*b"TRIG", Vec::new());
+    assert_eq!(
+        crate::MapSummary::from_chk(&untriggered).game_type,
+        GameType::Melee
+    );
+    untriggered.set_section(*b"UNIT", unit(176, 11, 1500));
+    assert_eq!(
+        crate::MapSummary::from_chk(&untriggered).game_type,
+        GameType::UseMapSettings
+    );
+
+    trigger.actions[1].action = 9;
+    chk.set_section(*b"TRIG", trigger.to_bytes());
+    assert_eq!(
+        crate::MapSummary::from_chk(&chk).game_type,
+        GameType::UseMapSettings
+    );
+}
+
+#[test]
+fn batch_keeps_going_past_bad_maps() {
+    use crate::batch::{BatchInput, BatchOptions, Progress};
+    use crate::chk::Chk;
+    use crate::{FileOptions, MpqWriter, WriterOptions};
+
+    let mut chk = Chk::default();
+    chk.set_section(*b"VER ", vec![205, 0]);
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    writer.add_file(
+        "staredit\\scenario.chk",
+        chk.to_bytes(),
+        FileOptions::default(),
+    );
+    let map = writer.finish().unwrap();
+
+    let path = std::env::temp_dir().join(format!("bwmpq-batch-{}.scm", std::process::id()));
+    std::fs::write(&path, &map).unwrap();
+
+    let mut inputs: Vec<BatchInput> = Vec::new();
+    for i in 0..40 {
+        inputs.push(match i % 4 {
+            0 => map.clone().into(),
+            1 => path.clone().into(),
+            2 => b"not an archive".to_vec().into(),
+            _ => BatchInput::Path(path.with_extension("missing")),
+        });
+    }
+    // One more that makes the closure panic.
+    inputs.push(Vec::new().into());
+
+    let mut items = Vec::new();
+    let mut progress = Vec::new();
+    let last = crate::batch::process(
+        inputs,
+        BatchOptions { concurrency: 4 },
+        |mpq| {
+            assert!(!mpq.is_empty(), "empty input");
+            crate::get_chk_from_mpq_in_memory(mpq).map(|x| x.len())
+        },
+        |item, x: Progress| {
+            assert_eq!(item.index, x.index);
+            items.push(item);
+            progress.push(x);
+        },
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    items.sort_by_key(|x| x.index);
+    assert_eq!(items.len(), 41);
+    for (i, item) in items.iter().enumerate() {
+        assert_eq!(item.index, i);
+        match (i, i % 4) {
+            (40, _) => assert!(
+                matches!(&item.result, Err(crate::Error::Panicked(x)) if x.contains("empty input"))
+            ),
+            (_, 0 | 1) => assert_eq!(item.result.as_ref().unwrap(), &chk.to_bytes().len()),
+            (_, 2) => assert!(matches!(item.result, Err(crate::Error::NotAnMpq(_)))),
+            _ => assert!(matches!(item.result, Err(crate::Error::Io(_)))),
+        }
+        assert_eq!(item.path.is_some(), i % 4 == 1 || i % 4 == 3);
+    }
+
+    assert_eq!(progress.len(), 41);
+    assert_eq!(progress.last(), Some(&last));
+    assert_eq!(
+        (last.completed, last.failed, last.total),
+        (41, 21, Some(41))
+    );
+    assert!(progress
+        .iter()
+        .enumerate()
+        .all(|(i, x)| x.completed == i + 1));
+    let mut seen: Vec<_> = progress.iter().map(|x| x.index).collect();
+    seen.sort();
+    assert_eq!(seen, (0..41).collect::<Vec<_>>());
+
+    // Filtering leaves the length unknown.
+    let inputs = (0..3)
+        .map(|_| BatchInput::Bytes(Vec::new()))
+        .filter(|_| true);
+    let last = crate::batch::process(inputs, BatchOptions::default(), |_| Ok(()), |_, _| {});
+    assert_eq!((last.completed, last.total), (3, None));
+}
+
+#[test]
+fn can_extract_synthetic_corpus() {
+    use crate::chk::{canonical_hash, Chk};
+    use crate::{MpqReader, ReaderOptions, Resolution};
+    use corpus::Expected;
+
+    for case in corpus::cases() {
+        let name = case.name;
+        assert_eq!(hash(&case.mpq), case.sha256, "{name}");
+
+        let probed = get_chk_from_mpq_in_memory(&case.mpq);
+        let chained =
+            MpqReader::from_bytes(case.mpq.as_slice(), ReaderOptions::map()).and_then(|mut x| {
+                x.read_file_resolved("staredit\\scenario.chk", Resolution::HashChain)
+            });
+        #[cfg(feature = "stormlib")]
+        let stormlib = open_with_stormlib(&case.mpq, &format!("{name}.scx"))
+            .and_then(|x| x.read_file("staredit\\scenario.chk"));
+
+        match case.expected {
+            Expected::Chk {
+                sha256,
+                canonical_hash: expected,
+            } => {
+                let chk = probed.unwrap_or_else(|err| panic!("{name}: {err:?}"));
+                assert_eq!(hash(&chk), sha256, "{name}");
+                assert_eq!(canonical_hash(&Chk::parse(&chk)), expected, "{name}");
+
+                // The decoys come first in the hash chain, so only the locale probe gets past
+                // them.
+                if name == "decoy_locales" {
+                    assert!(matches!(chained, Err(crate::Error::Corrupt(_))), "{name}");
+                } else {
+                    assert_eq!(chained.unwrap().1, chk, "{name}");
+                }
+
+                #[cfg(feature = "stormlib")]
+                assert_eq!(
+                    stormlib.unwrap_or_else(|err| panic!("{name}: {err:?}")),
+                    chk,
+                    "{name}"
+                );
+            }
+            Expected::Error(is_expected) => {
+                for err in [probed.unwrap_err(), chained.unwrap_err()] {
+                    assert!(is_expected(&err), "{name}: {err:?}");
+                }
+
+                #[cfg(feature = "stormlib")]
+                assert!(stormlib.is_err(), "{name}");
+            }
+        }
+    }
+}
+
+#[test]
+fn reports_typed_errors() {
+    let err = get_chk_from_mpq_in_memory(b"definitely not an mpq archive").unwrap_err();
+
+    assert!(matches!(err, crate::Error::NotAnMpq(_)), "{err:?}");
+}
+
+#[test]
+fn crypto_matches_known_keys() {
+    use crate::crypto::{hash_string, MPQ_HASH_FILE_KEY};
+
+    assert_eq!(hash_string(b"(hash table)", MPQ_HASH_FILE_KEY), 0xC3AF3770);
+    assert_eq!(hash_string(b"(block table)", MPQ_HASH_FILE_KEY), 0xEC83B3A3);
+
+    let plain: Vec<u8> = (0..35).collect();
+    let mut data = plain.clone();
+    crate::crypto::encrypt_block(&mut data, 0xC3AF3770);
+    assert_ne!(data[..32], plain[..32]);
+    assert_eq!(data[32..], plain[32..]);
+    crate::crypto::decrypt_block(&mut data, 0xC3AF3770);
+    assert_eq!(data, plain);
+}
+
+#[test]
+fn can_explode_pkware_stream() {
+    // The test vector that ships with zlib's blast.c.
+    let input = [0x00, 0x04, 0x82, 0x24, 0x25, 0x8f, 0x80, 0x7f];
+
+    let output = crate::compression::explode(&input, 13).unwrap();
+
+    assert_eq!(output, b"AIAIAIAIAIAIA");
+}
+
+#[test]
+fn can_decode_storm_huffman() {
+    // Compression type 0, every byte weighted alike but 0 and 1. The mask byte comes first.
+    let input = [
+        0x01, 0x00, 0x2c, 0xea, 0x5c, 0xaa, 0x24, 0x37, 0xb7, 0x82, 0x77, 0xbf, 0xef, 0x4f, 0xe7,
+        0xd3, 0xf5, 0xe9, 0x7c, 0x75, 0x39, 0x3e, 0x0c, 0xe3, 0xb2, 0x5f, 0xd6, 0xc5, 0xf2, 0x10,
+    ];
+
+    let output = crate::compression::decompress(&input, 29).unwrap();
+    assert_eq!(output, b"StarCraft StarCraft StarCraft");
+
+    // Stopping at the expected size, or at the end of the stream, whichever comes first.
+    let output = crate::compression::decompress(&input, 9).unwrap();
+    assert_eq!(output, b"StarCraft");
+    let output = crate::compression::decompress(&input, 100).unwrap();
+    assert_eq!(output.len(), 29);
+
+    assert!(matches!(
+        crate::compression::decompress(&input[..10], 29),
+        Err(crate::Error::Corrupt(_))
+    ));
+    assert!(matches!(
+        crate::compression::decompress(&[0x01, 0x07], 29),
+        Err(crate::Error::Unsupported(_))
+    ));
 }
 
 #[rustfmt::skip]
@@ -0,0 +1,266 @@
+//! A corpus of small archives built in memory, for testing extraction without downloading maps.
+//!
+//! Every archive holds one of two CHKs and is built the way map protectors and old editors lay
+//! maps out: sector sizes from 512 bytes up, imploded and zlib sectors, encrypted files, decoy
+//! scenario.chk entries at other locales, CHK sections with negative sizes and archives that
+//! are cut short. Everything is deterministic, so each case has golden hashes of the archive,
+//! of the CHK that comes out of it and of that CHK's `canonical_hash`.
+
+use crate::chk::Chk;
+use crate::chk::Trigger;
+use crate::entry::MPQ_FILE_EXISTS;
+use crate::entry::MPQ_FILE_IMPLODE;
+use crate::entry::MPQ_FILE_SINGLE_UNIT;
+use crate::reader::BlockEntry;
+use crate::Compression;
+use crate::Error;
+use crate::FileOptions;
+use crate::MpqWriter;
+use crate::WriterOptions;
+
+const CHK_FILENAME: &str = "staredit\\scenario.chk";
+
+/// What reading scenario.chk out of a case should give.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Expected {
+    /// The CHK, with the SHA-256 of its bytes and its `canonical_hash`.
+    Chk {
+        sha256: &'static str,
+        canonical_hash: &'static str,
+    },
+    /// An error, which the function tells apart from other errors.
+    Error(fn(&Error) -> bool),
+}
+
+pub(super) struct Case {
+    pub(super) name: &'static str,
+    pub(super) mpq: Vec<u8>,
+    /// The SHA-256 of `mpq`, which catches changes to the writer or to this module.
+    pub(super) sha256: &'static str,
+    pub(super) expected: Expected,
+}
+
+const CHK: Expected = Expected::Chk {
+    sha256: "14f52d3be2a4b4b0dfb980d99928c43b3cf9af39d61dcbad986fbdea56301ce8",
+    canonical_hash: "835bb5c5992748adb053b297e3a042e7ca7e9ba241abaf18d0b927c519d7f936",
+};
+const NEGATIVE_SECTIONS_CHK: Expected = Expected::Chk {
+    sha256: "8660d9dfb94972ceeb1bef42b3e6f025f42f188e0eab09668a26eb408fdf3b66",
+    canonical_hash: "1aeb07229e1e1236f959a052170feb9c911e030da84c643db261aa46b0602656",
+};
+
+pub(super) fn cases() -> Vec<Case> {
+    let chk = chk();
+    let case = |name, mpq, sha256, expected| Case {
+        name,
+        mpq,
+        sha256,
+        expected,
+    };
+
+    vec![
+        case(
+            "zlib",
+            archive(&chk, 3, Compression::Zlib, false, false),
+            "2de30b932474e72c2be164b759e825fa22801fd3c26ae9af1fb204b56a0dfa5e",
+            CHK,
+        ),
+        case(
+            "implode",
+            archive(&chk, 0, Compression::Implode, false, false),
+            "ae48d36de71d4e4871d9fc0ef0541a9b8f5bbd76f78f69794540bba86f546b97",
+            CHK,
+        ),
+        case(
+            "uncompressed",
+            archive(&chk, 3, Compression::None, false, false),
+            "581e2af69f52c0d12017015f4dadd8ce9879cdeefb87fb7e87d594c24a99f893",
+            CHK,
+        ),
+        case(
+            "encrypted",
+            archive(&chk, 3, Compression::Implode, true, false),
+            "15e44fa58e90913ee62a527206816faf55ce242d885af010140bbf57ad1bc2e0",
+            CHK,
+        ),
+        case(
+            "encrypted_fix_key",
+            archive(&chk, 1, Compression::Zlib, true, true),
+            "9e130ca755a1420bc5c92d2b295f12feb2b58b7e79a5649a54ef0346ef27586d",
+            CHK,
+        ),
+        case(
+            "decoy_locales",
+            decoy_locales(&chk),
+            "3b27726c99b9008a84c9df10c1fcd5f22f14fe729af310f758ab1e04bc33ce47",
+            CHK,
+        ),
+        case(
+            "negative_sections",
+            archive(&negative_sections(&chk), 3, Compression::Zlib, false, false),
+            "c080171050ca7bbc7b7304304e97675545709b0360ef44abd84a0cbbc7b50e30",
+            NEGATIVE_SECTIONS_CHK,
+        ),
+        case(
+            "truncated_file",
+            truncated_file(&chk),
+            "e9f47f99530668891dd1466bec66a49285545609cdbeae857c8429b66b36eac5",
+            Expected::Error(|err| matches!(err, Error::Corrupt(_))),
+        ),
+        case(
+            "truncated_tables",
+            truncated_tables(&chk),
+            "f63ec4df17f611513fc6c45073fa2772219c501ecdacd7f35a472a9dac516b1a",
+            CHK,
+        ),
+    ]
+}
+
+/// A melee map with a few units and a trigger, and terrain noisy enough that it spans several
+/// sectors however it is compressed.
+fn chk() -> Vec<u8> {
+    let mut random = Random(0x5eed);
+
+    let mut chk = Chk::default();
+    chk.set_section(*b"VER ", vec![205, 0]);
+    chk.set_section(*b"ERA ", vec![4, 0]);
+    chk.set_section(*b"DIM ", vec![64, 0, 64, 0]);
+    chk.set_section(
+        *b"MTXM",
+        (0..64 * 64)
+            .flat_map(|_| (random.next() as u16 % 0x800).to_le_bytes())
+            .collect(),
+    );
+    chk.set_section(*b"OWNR", vec![6, 6, 0, 0, 0, 0, 0, 0, 7, 7, 7, 7]);
+    chk.set_section(*b"SIDE", vec![0, 1, 0, 0, 0, 0, 0, 0, 4, 4, 4, 4]);
+    let title = chk.add_string(b"Synthetic").unwrap() as u16;
+    chk.set_section(*b"SPRP", [title.to_le_bytes(), [0, 0]].concat());
+
+    let mut units = Vec::new();
+    for i in 0..16u16 {
+        // Mineral fields and SCVs for players 1 and 2.
+        let (unit_id, owner) = if i % 2 == 0 {
+            (176u16, 11)
+        } else {
+            (7, i as u8 % 4 / 2)
+        };
+        let mut unit = vec![0; 36];
+        unit[4..6].copy_from_slice(&(i * 128 + 16).to_le_bytes());
+        unit[6..8].copy_from_slice(&(i * 96 + 16).to_le_bytes());
+        unit[8..10].copy_from_slice(&unit_id.to_le_bytes());
+        unit[16] = owner;
+        units.extend(unit);
+    }
+    chk.set_section(*b"UNIT", units);
+
+    // Always, then Victory, for all players.
+    let mut trigger = Trigger::default();
+    trigger.conditions[0].condition = 22;
+    trigger.actions[0].action = 1;
+    trigger.players[17] = 1;
+    chk.set_section(*b"TRIG", trigger.to_bytes());
+
+    chk.to_bytes()
+}
+
+/// `chk` with a COLR section hidden inside a junk section, reached by a section with a
+/// negative size. StarCraft stops reading when the jump comes round again.
+fn negative_sections(chk: &[u8]) -> Vec<u8> {
+    let colr = [
+        b"COLR".as_slice(),
+        &8u32.to_le_bytes(),
+        &[0, 1, 2, 3, 4, 5, 6, 7],
+    ]
+    .concat();
+
+    let mut out = chk.to_vec();
+    out.extend(b"JUNK");
+    out.extend((colr.len() as u32).to_le_bytes());
+    out.extend(&colr);
+    out.extend(b"BACK");
+    out.extend((-(colr.len() as i32 + 8)).to_le_bytes());
+    out
+}
+
+/// An archive with `chk` as its only file, besides the listfile and attributes.
+fn archive(
+    chk: &[u8],
+    sector_size_shift: u16,
+    compression: Compression,
+    encrypt: bool,
+    fix_key: bool,
+) -> Vec<u8> {
+    let mut writer = MpqWriter::new(WriterOptions {
+        sector_size_shift,
+        ..WriterOptions::default()
+    });
+    writer.add_file(
+        CHK_FILENAME,
+        chk.to_vec(),
+        FileOptions {
+            compression,
+            encrypt,
+            fix_key,
+            locale: 0,
+        },
+    );
+    writer.finish().unwrap()
+}
+
+/// The real CHK at the neutral locale, with entries at locales looked at before it whose data
+/// doesn't decompress.
+fn decoy_locales(chk: &[u8]) -> Vec<u8> {
+    let mut writer = MpqWriter::new(WriterOptions::default());
+    for locale in [0x404, 0x409] {
+        let garbage = vec![0xAA; 64];
+        let block = BlockEntry {
+            offset: 0,
+            compressed_size: garbage.len() as u32,
+            file_size: 1024,
+            flags: MPQ_FILE_EXISTS | MPQ_FILE_IMPLODE | MPQ_FILE_SINGLE_UNIT,
+        };
+        writer.add_stored_file(CHK_FILENAME, block, garbage, locale);
+    }
+    writer.add_file(CHK_FILENAME, chk.to_vec(), FileOptions::default());
+    writer.finish().unwrap()
+}
+
+/// An archive whose CHK data is cut off a third of the way in. The tables are still there,
+/// moved up to where the data now ends.
+fn truncated_file(chk: &[u8]) -> Vec<u8> {
+    let mpq = archive(chk, 3, Compression::None, false, false);
+    let (hash_table_pos, data_end) = (u32_at(&mpq, 16) as usize, 32 + chk.len() / 3);
+
+    let mut out = mpq[..data_end].to_vec();
+    out.extend(&mpq[hash_table_pos..]);
+    let moved_by = (hash_table_pos - data_end) as u32;
+    for offset in [8, 16, 20] {
+        let value = u32_at(&out, offset) - moved_by;
+        out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    out
+}
+
+/// An archive cut off in the middle of its block table. The CHK's block comes first and is
+/// still whole, and like StarCraft the reader makes do with the blocks that are left.
+fn truncated_tables(chk: &[u8]) -> Vec<u8> {
+    let mut mpq = archive(chk, 3, Compression::Zlib, false, false);
+    mpq.truncate(mpq.len() - 8);
+    mpq
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// xorshift64, so that the corpus doesn't depend on a random number crate.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -0,0 +1,716 @@
+use super::escape::is_hex_escape;
+use super::tables::Field;
+use super::tables::Fields;
+use super::tables::Kind;
+use super::tables::Signature;
+use super::tables::ACTIONS;
+use super::tables::ACTION_FIELDS;
+use super::tables::ALLIANCES;
+use super::tables::ALWAYS_DISPLAY;
+use super::tables::BRIEFING_ACTIONS;
+use super::tables::COMPARISONS;
+use super::tables::CONDITIONS;
+use super::tables::CONDITION_FIELDS;
+use super::tables::FLAG_DISABLED;
+use super::tables::MODIFIERS;
+use super::tables::ORDERS;
+use super::tables::PLAYERS;
+use super::tables::RESOURCES;
+use super::tables::SCORES;
+use super::tables::STATES;
+use super::tables::SWITCH_ACTIONS;
+use super::tables::SWITCH_STATES;
+use super::tables::UNITS;
+use crate::chk::Chk;
+use crate::chk::Encoding;
+use crate::chk::Location;
+use crate::chk::SectionName;
+use crate::chk::Trigger;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why TrigEdit text failed to compile, and where. Lines and columns count from 1, columns in
+/// characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+type Result<T> = std::result::Result<T, CompileError>;
+
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+fn error(pos: Pos, message: impl Into<String>) -> CompileError {
+    CompileError {
+        line: pos.line,
+        column: pos.column,
+        message: message.into(),
+    }
+}
+
+enum Value {
+    /// A quoted string, already unescaped and encoded.
+    Quoted(Vec<u8>),
+    /// Anything else, such as a number or an enum name, with surrounding whitespace trimmed.
+    Bare(String),
+}
+
+struct Arg {
+    pos: Pos,
+    value: Value,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    pos: Pos,
+    encoding: Encoding,
+}
+
+impl Parser {
+    fn new(text: &str, encoding: Encoding) -> Parser {
+        Parser {
+            chars: text.chars().collect(),
+            index: 0,
+            pos: Pos { line: 1, column: 1 },
+            encoding,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    /// Skips whitespace and `//` comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('/') if self.chars.get(self.index + 1) == Some(&'/') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_blank();
+        self.peek().is_none()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_blank();
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(error(self.pos, format!("expected `{c}`")))
+        }
+    }
+
+    /// A keyword, label or condition/action name: everything up to the next punctuation.
+    fn name(&mut self) -> Result<(Pos, String)> {
+        self.skip_blank();
+        let pos = self.pos;
+
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if matches!(c, '(' | ')' | '{' | '}' | ':' | ',' | ';' | '"' | '\n') {
+                break;
+            }
+            name.push(c);
+            self.bump();
+        }
+
+        let name = name.trim_end().to_owned();
+        if name.is_empty() {
+            return Err(error(pos, "expected a name"));
+        }
+        Ok((pos, name))
+    }
+
+    /// A parenthesised argument list, after the opening `(`.
+    fn args(&mut self) -> Result<Vec<Arg>> {
+        let mut args = Vec::new();
+        if self.eat(')') {
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.arg()?);
+            if self.eat(')') {
+                return Ok(args);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn arg(&mut self) -> Result<Arg> {
+        self.skip_blank();
+        let pos = self.pos;
+
+        if self.peek() == Some('"') {
+            self.bump();
+            let value = Value::Quoted(self.quoted(pos)?);
+            return Ok(Arg { pos, value });
+        }
+
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if matches!(c, ',' | ')' | '\n') {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+
+        let text = text.trim_end().to_owned();
+        if text.is_empty() {
+            return Err(error(pos, "expected an argument"));
+        }
+        Ok(Arg {
+            pos,
+            value: Value::Bare(text),
+        })
+    }
+
+    /// The rest of a quoted string, after the opening `"`. See `escape::quote`.
+    fn quoted(&mut self, start: Pos) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            let pos = self.pos;
+            match self.bump() {
+                None | Some('\n') => return Err(error(start, "unterminated string")),
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some('n') => text.push('\n'),
+                    Some(c @ ('"' | '\\')) => text.push(c),
+                    _ => {
+                        return Err(error(
+                            pos,
+                            "unknown escape, expected `\\n`, `\\\"` or `\\\\`",
+                        ))
+                    }
+                },
+                Some('<') if is_hex_escape(&self.chars[self.index - 1..]) => {
+                    let hex: String = self.chars[self.index..self.index + 2].iter().collect();
+                    for _ in 0..3 {
+                        self.bump();
+                    }
+                    bytes.extend(self.encoding.encode(&text));
+                    text.clear();
+                    bytes.push(u8::from_str_radix(&hex, 16).unwrap());
+                }
+                Some(c) if !self.encoding.can_encode(c) => {
+                    return Err(error(
+                        pos,
+                        format!("`{c}` can't be written in {:?}", self.encoding),
+                    ))
+                }
+                Some(c) => text.push(c),
+            }
+        }
+
+        bytes.extend(self.encoding.encode(&text));
+        Ok(bytes)
+    }
+}
+
+/// Turns TrigEdit text into trigger records, looking up and allocating names in `chk`.
+struct Compiler<'a> {
+    chk: &'a mut Chk,
+    strings: HashMap<Vec<u8>, u32>,
+    locations: Vec<Option<Vec<u8>>>,
+    switches: Vec<Option<Vec<u8>>>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(chk: &'a mut Chk) -> Compiler<'a> {
+        let mut strings = HashMap::new();
+        for id in 1..=chk.string_count() as u32 {
+            if let Some(string) = chk.string(id) {
+                strings.entry(string.to_vec()).or_insert(id);
+            }
+        }
+
+        let switches = chk
+            .switch_names()
+            .unwrap_or_default()
+            .iter()
+            .map(|&x| named_string(chk, x))
+            .collect();
+
+        let mut compiler = Compiler {
+            chk,
+            strings,
+            locations: Vec::new(),
+            switches,
+        };
+        compiler.load_locations();
+        compiler
+    }
+
+    fn load_locations(&mut self) {
+        self.locations = self
+            .chk
+            .locations()
+            .iter()
+            .map(|x| named_string(self.chk, x.name as u32))
+            .collect();
+    }
+
+    fn triggers(&mut self, text: &str, actions: &[Signature]) -> Result<Vec<Trigger>> {
+        let mut p = Parser::new(text, self.chk.encoding());
+        let mut triggers = Vec::new();
+
+        while !p.at_end() {
+            let (pos, name) = p.name()?;
+            if !name.eq_ignore_ascii_case("Trigger") {
+                return Err(error(pos, format!("expected `Trigger`, found `{name}`")));
+            }
+
+            let mut trigger = Trigger::default();
+            p.expect('(')?;
+            for arg in p.args()? {
+                let player = self.value(Kind::Player, &arg)? as usize;
+                match trigger.players.get_mut(player) {
+                    Some(x) => *x = 1,
+                    None => return Err(error(arg.pos, format!("player {player} is out of range"))),
+                }
+            }
+            p.expect('{')?;
+
+            let (pos, name) = p.name()?;
+            if !name.eq_ignore_ascii_case("Conditions") {
+                return Err(error(
+                    pos,
+                    format!("expected `Conditions:`, found `{name}`"),
+                ));
+            }
+            p.expect(':')?;
+
+            let mut count = 0;
+            let mut has_actions = false;
+            loop {
+                if p.eat('}') {
+                    break;
+                }
+
+                let (pos, name) = p.name()?;
+                if name.eq_ignore_ascii_case("Actions") {
+                    p.expect(':')?;
+                    has_actions = true;
+                    break;
+                }
+                if count == Trigger::CONDITION_COUNT {
+                    return Err(error(pos, "a trigger can't have more than 16 conditions"));
+                }
+
+                trigger.conditions[count] = self.record(
+                    &mut p,
+                    pos,
+                    &name,
+                    "condition",
+                    CONDITIONS,
+                    CONDITION_FIELDS,
+                )?;
+                count += 1;
+                p.expect(';')?;
+            }
+
+            let mut count = 0;
+            while has_actions && !p.eat('}') {
+                let (pos, name) = p.name()?;
+                if name.eq_ignore_ascii_case("Flags") {
+                    p.expect(':')?;
+                    self.flags(&mut p, &mut trigger)?;
+                    break;
+                }
+                if count == Trigger::ACTION_COUNT {
+                    return Err(error(pos, "a trigger can't have more than 64 actions"));
+                }
+
+                trigger.actions[count] =
+                    self.record(&mut p, pos, &name, "action", actions, ACTION_FIELDS)?;
+                count += 1;
+                p.expect(';')?;
+            }
+
+            triggers.push(trigger);
+        }
+
+        Ok(triggers)
+    }
+
+    /// The `Flags:` block that ends a trigger, after the label. `Execution(N)` sets the execution
+    /// flags and `Player(N, VALUE)` a player byte to something other than 1.
+    fn flags(&mut self, p: &mut Parser, trigger: &mut Trigger) -> Result<()> {
+        while !p.eat('}') {
+            let (pos, name) = p.name()?;
+            p.expect('(')?;
+            let args = p.args()?;
+
+            match (name.to_ascii_lowercase().as_str(), args.as_slice()) {
+                ("execution", [flags]) => trigger.execution_flags = number(flags)?,
+                ("player", [player, value]) => {
+                    let index = number(player)? as usize;
+                    let value = number(value)?;
+                    if value > u8::MAX as u32 {
+                        return Err(error(args[1].pos, "a player flag is one byte"));
+                    }
+                    match trigger.players.get_mut(index) {
+                        Some(x) => *x = value as u8,
+                        None => {
+                            return Err(error(
+                                player.pos,
+                                format!("player {index} is out of range"),
+                            ))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(error(
+                        pos,
+                        format!(
+                        "expected `Execution(flags)` or `Player(player, value)`, found `{name}`"
+                    ),
+                    ))
+                }
+            }
+            p.expect(';')?;
+        }
+        Ok(())
+    }
+
+    /// One condition or action, after its name.
+    fn record<T: Fields + Default>(
+        &mut self,
+        p: &mut Parser,
+        pos: Pos,
+        name: &str,
+        what: &str,
+        signatures: &[Signature],
+        raw_fields: &[Field],
+    ) -> Result<T> {
+        p.expect('(')?;
+
+        if name.eq_ignore_ascii_case("Disabled") {
+            let (pos, name) = p.name()?;
+            let mut record: T = self.record(p, pos, &name, what, signatures, raw_fields)?;
+            p.expect(')')?;
+            record.set(Field::Flags, record.get(Field::Flags) | FLAG_DISABLED);
+            return Ok(record);
+        }
+
+        let args = p.args()?;
+        let mut record = T::default();
+
+        if name.eq_ignore_ascii_case("Custom") {
+            if args.len() != raw_fields.len() {
+                return Err(error(
+                    pos,
+                    format!(
+                        "Custom {what}s take {} arguments, found {}",
+                        raw_fields.len(),
+                        args.len()
+                    ),
+                ));
+            }
+
+            for (arg, &field) in args.iter().zip(raw_fields) {
+                let value = number(arg)?;
+                check_fits(arg, field, value)?;
+                record.set(field, value);
+            }
+            return Ok(record);
+        }
+
+        let Some(signature) = signatures
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+        else {
+            return Err(error(pos, format!("unknown {what} `{name}`")));
+        };
+
+        if args.len() != signature.args.len() {
+            return Err(error(
+                pos,
+                format!(
+                    "{} takes {} arguments, found {}",
+                    signature.name,
+                    signature.args.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        record.set(Field::Opcode, signature.opcode as u32);
+        for (arg, &(kind, field)) in args.iter().zip(signature.args) {
+            let value = self.value(kind, arg)?;
+            if kind == Kind::AlwaysDisplay {
+                record.set(field, record.get(field) | value);
+            } else {
+                check_fits(arg, field, value)?;
+                record.set(field, value);
+            }
+        }
+
+        Ok(record)
+    }
+
+    fn value(&mut self, kind: Kind, arg: &Arg) -> Result<u32> {
+        match kind {
+            Kind::Player => listed(arg, PLAYERS, "player"),
+            Kind::Unit => listed(arg, UNITS, "unit"),
+            Kind::Location => self.location(arg),
+            Kind::Comparison => named(arg, COMPARISONS, "comparison"),
+            Kind::Modifier => named(arg, MODIFIERS, "modifier"),
+            Kind::Number => number(arg),
+            Kind::Resource => named(arg, RESOURCES, "resource type"),
+            Kind::Score => named(arg, SCORES, "score type"),
+            Kind::Switch => self.switch(arg),
+            Kind::SwitchState => named(arg, SWITCH_STATES, "switch state"),
+            Kind::SwitchAction => named(arg, SWITCH_ACTIONS, "switch action"),
+            Kind::State => named(arg, STATES, "state"),
+            Kind::String | Kind::Wav => self.string(arg),
+            Kind::AiScript => match &arg.value {
+                Value::Quoted(script) => match <[u8; 4]>::try_from(script.as_slice()) {
+                    Ok(script) => Ok(u32::from_le_bytes(script)),
+                    Err(_) => Err(error(arg.pos, "AI scripts are 4 characters long")),
+                },
+                Value::Bare(_) => number(arg),
+            },
+            Kind::Order => named(arg, ORDERS, "order"),
+            Kind::Alliance => named(arg, ALLIANCES, "alliance status"),
+            Kind::AlwaysDisplay => named(arg, ALWAYS_DISPLAY, "display setting"),
+            Kind::Count => match &arg.value {
+                Value::Bare(text) if text.eq_ignore_ascii_case("All") => Ok(0),
+                _ => number(arg),
+            },
+        }
+    }
+
+    /// A location by name, by its default `"Location N"` name, or a new location if the name
+    /// isn't in use.
+    fn location(&mut self, arg: &Arg) -> Result<u32> {
+        let Value::Quoted(name) = &arg.value else {
+            return number(arg);
+        };
+        if name.is_empty() {
+            return Ok(0);
+        }
+
+        if let Some(index) = self.locations.iter().position(|x| x.as_ref() == Some(name)) {
+            return Ok(index as u32 + 1);
+        }
+        if let Some(id) =
+            numbered(name, "Location ").filter(|&x| x as usize <= self.locations.len())
+        {
+            return Ok(id);
+        }
+
+        let string = self.string(arg)?;
+        let Ok(string) = u16::try_from(string) else {
+            return Err(error(
+                arg.pos,
+                "the string table is too big for a new location name",
+            ));
+        };
+        let location = Location {
+            left: 0,
+            top: 0,
+            right: 32,
+            bottom: 32,
+            name: string,
+            elevation_flags: 0,
+        };
+        let Some(id) = self.chk.add_location(location) else {
+            return Err(error(arg.pos, "every location slot is taken"));
+        };
+
+        self.load_locations();
+        Ok(id)
+    }
+
+    /// A switch by name or by its default `"Switch N"` name. Switches are numbered from 1 in
+    /// text and from 0 in the record.
+    fn switch(&mut self, arg: &Arg) -> Result<u32> {
+        let Value::Quoted(name) = &arg.value else {
+            return number(arg);
+        };
+
+        if let Some(index) = self.switches.iter().position(|x| x.as_ref() == Some(name)) {
+            return Ok(index as u32);
+        }
+        match numbered(name, "Switch ").filter(|&x| x <= 256) {
+            Some(id) => Ok(id - 1),
+            None => Err(error(arg.pos, "unknown switch")),
+        }
+    }
+
+    /// An existing string with the same bytes, or a new one.
+    fn string(&mut self, arg: &Arg) -> Result<u32> {
+        let Value::Quoted(string) = &arg.value else {
+            return number(arg);
+        };
+        if string.is_empty() {
+            return Ok(0);
+        }
+
+        if let Some(&id) = self.strings.get(string) {
+            return Ok(id);
+        }
+        let Some(id) = self.chk.add_string(string) else {
+            return Err(error(arg.pos, "the string table is full"));
+        };
+
+        self.strings.insert(string.clone(), id);
+        Ok(id)
+    }
+}
+
+fn named_string(chk: &Chk, id: u32) -> Option<Vec<u8>> {
+    chk.string(id).filter(|x| !x.is_empty()).map(<[u8]>::to_vec)
+}
+
+/// Parses the `N` of a default name such as `Location N`. N is at least 1.
+fn numbered(name: &[u8], prefix: &str) -> Option<u32> {
+    let n = name.strip_prefix(prefix.as_bytes())?;
+    std::str::from_utf8(n)
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|&x| x != 0)
+}
+
+/// A decimal or `0x` hexadecimal number. Negative numbers wrap, as they do in the editors.
+fn number(arg: &Arg) -> Result<u32> {
+    let parsed = match &arg.value {
+        Value::Bare(text) => match text.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => text
+                .parse::<u32>()
+                .ok()
+                .or_else(|| text.parse::<i32>().ok().map(|x| x as u32)),
+        },
+        Value::Quoted(_) => None,
+    };
+
+    parsed.ok_or_else(|| error(arg.pos, "expected a number"))
+}
+
+/// A quoted name from a list indexed by value, or a number.
+fn listed(arg: &Arg, names: &[&str], what: &str) -> Result<u32> {
+    match &arg.value {
+        Value::Quoted(name) => names
+            .iter()
+            .position(|x| x.as_bytes().eq_ignore_ascii_case(name))
+            .map(|x| x as u32)
+            .ok_or_else(|| error(arg.pos, format!("unknown {what}"))),
+        Value::Bare(_) => number(arg),
+    }
+}
+
+/// An enum value by name, or a number.
+fn named(arg: &Arg, names: &[(u32, &str)], what: &str) -> Result<u32> {
+    if let Value::Bare(text) = &arg.value {
+        if let Some((value, _)) = names.iter().find(|(_, x)| x.eq_ignore_ascii_case(text)) {
+            return Ok(*value);
+        }
+    }
+
+    number(arg).map_err(|_| {
+        let expected: Vec<&str> = names.iter().map(|(_, x)| *x).collect();
+        error(
+            arg.pos,
+            format!("unknown {what}, expected one of: {}", expected.join(", ")),
+        )
+    })
+}
+
+fn check_fits(arg: &Arg, field: Field, value: u32) -> Result<()> {
+    if value > field.max() {
+        return Err(error(
+            arg.pos,
+            format!(
+                "{value} is too large, the most this can be is {}",
+                field.max()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Compiles `text` and replaces `section` with the result. `chk` is only changed if compiling
+/// succeeds.
+fn compile(chk: &mut Chk, text: &str, section: SectionName, actions: &[Signature]) -> Result<()> {
+    let mut compiled = chk.clone();
+    let triggers = Compiler::new(&mut compiled).triggers(text, actions)?;
+
+    compiled.set_section(
+        section,
+        triggers.iter().flat_map(Trigger::to_bytes).collect(),
+    );
+    *chk = compiled;
+    Ok(())
+}
+
+/// Compiles TrigEdit text, as written by `decompile_triggers`, and replaces TRIG with it.
+///
+/// Strings are matched against the string table and added to it when there is no match.
+/// Locations are matched by name and, when no location has the name, created as a 1x1 tile
+/// location in the top left corner. Switches must already exist.
+pub fn compile_triggers(chk: &mut Chk, text: &str) -> Result<()> {
+    compile(chk, text, *b"TRIG", ACTIONS)
+}
+
+/// Compiles mission briefing TrigEdit text, as written by `decompile_briefing`, and replaces
+/// MBRF with it.
+pub fn compile_briefing(chk: &mut Chk, text: &str) -> Result<()> {
+    compile(chk, text, *b"MBRF", BRIEFING_ACTIONS)
+}
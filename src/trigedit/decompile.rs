@@ -0,0 +1,323 @@
+use super::escape::quote;
+use super::tables::Field;
+use super::tables::Fields;
+use super::tables::Kind;
+use super::tables::Signature;
+use super::tables::ACTIONS;
+use super::tables::ACTION_FIELDS;
+use super::tables::ALLIANCES;
+use super::tables::ALWAYS_DISPLAY;
+use super::tables::BRIEFING_ACTIONS;
+use super::tables::COMPARISONS;
+use super::tables::CONDITIONS;
+use super::tables::CONDITION_FIELDS;
+use super::tables::FLAG_ALWAYS_DISPLAY;
+use super::tables::FLAG_DISABLED;
+use super::tables::MODIFIERS;
+use super::tables::ORDERS;
+use super::tables::PLAYERS;
+use super::tables::RESOURCES;
+use super::tables::SCORES;
+use super::tables::STATES;
+use super::tables::SWITCH_ACTIONS;
+use super::tables::SWITCH_STATES;
+use super::tables::UNITS;
+use crate::chk::Chk;
+use crate::chk::Encoding;
+use crate::chk::Location;
+use crate::chk::Trigger;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+pub(crate) const SEPARATOR: &str =
+    "//-----------------------------------------------------------------//";
+
+/// Turns TRIG and MBRF records into TrigEdit text, resolving names from the map they came from.
+///
+/// Anything that doesn't fit the usual syntax is written so that compiling the text gives back
+/// the same bytes, and nothing panics:
+///
+/// - Unknown opcodes, records after the first empty one and known opcodes with data outside
+///   their arguments (such as a mask, or flag bits other than disabled and always display) are
+///   `Custom(...)` calls with every raw field.
+/// - Out of range ("EUD") players, units and strings are plain numbers. So are strings,
+///   locations and switches that a name wouldn't compile back to, such as a string that another
+///   string with the same text comes before.
+/// - Execution flags and player bytes other than 0 and 1, or for player 28 which has no name,
+///   go in a `Flags:` block after the actions.
+pub struct Decompiler<'a> {
+    chk: &'a Chk,
+    encoding: Encoding,
+    locations: Vec<Location>,
+    switch_names: Vec<u32>,
+    /// The first string id with each text, which is what the compiler picks.
+    string_ids: HashMap<&'a [u8], u32>,
+}
+
+impl<'a> Decompiler<'a> {
+    pub fn new(chk: &'a Chk) -> Decompiler<'a> {
+        let mut string_ids = HashMap::new();
+        for id in 1..=chk.string_count() as u32 {
+            if let Some(string) = chk.string(id) {
+                string_ids.entry(string).or_insert(id);
+            }
+        }
+
+        Decompiler {
+            chk,
+            encoding: chk.encoding(),
+            locations: chk.locations(),
+            switch_names: chk.switch_names().unwrap_or_default(),
+            string_ids,
+        }
+    }
+
+    /// Every trigger in TRIG.
+    pub fn triggers(&self) -> String {
+        self.chk
+            .triggers()
+            .iter()
+            .map(|x| self.trigger(x, ACTIONS))
+            .collect()
+    }
+
+    /// Every mission briefing trigger in MBRF.
+    pub fn briefing(&self) -> String {
+        self.chk
+            .briefing_triggers()
+            .iter()
+            .map(|x| self.trigger(x, BRIEFING_ACTIONS))
+            .collect()
+    }
+
+    fn trigger(&self, trigger: &Trigger, actions: &[Signature]) -> String {
+        let players: Vec<String> = (0..trigger.players.len())
+            .filter(|&x| trigger.players[x] == 1)
+            .map(|x| self.arg(Kind::Player, x as u32))
+            .collect();
+
+        let mut out = format!("Trigger({}){{\nConditions:\n", players.join(","));
+        for condition in in_use(&trigger.conditions, CONDITION_FIELDS) {
+            let line = self.call(condition, condition.condition, CONDITIONS, CONDITION_FIELDS);
+            writeln!(out, "\t{line};").unwrap();
+        }
+
+        out.push_str("\nActions:\n");
+        for action in in_use(&trigger.actions, ACTION_FIELDS) {
+            let line = self.call(action, action.action, actions, ACTION_FIELDS);
+            writeln!(out, "\t{line};").unwrap();
+        }
+
+        let mut flags = Vec::new();
+        if trigger.execution_flags != 0 {
+            flags.push(format!("Execution(0x{:08X})", trigger.execution_flags));
+        }
+        for (player, &value) in trigger.players.iter().enumerate() {
+            if value > 1 {
+                flags.push(format!("Player({player}, {value})"));
+            }
+        }
+        if !flags.is_empty() {
+            out.push_str("\nFlags:\n");
+            for line in flags {
+                writeln!(out, "\t{line};").unwrap();
+            }
+        }
+
+        writeln!(out, "}}\n\n{SEPARATOR}\n").unwrap();
+        out
+    }
+
+    fn call<T: Fields>(
+        &self,
+        record: &T,
+        opcode: u8,
+        signatures: &[Signature],
+        raw_fields: &[Field],
+    ) -> String {
+        let signature = signatures
+            .iter()
+            .find(|x| x.opcode == opcode)
+            .filter(|x| fits(record, x, raw_fields));
+        let Some(signature) = signature else {
+            let args: Vec<String> = raw_fields
+                .iter()
+                .map(|&field| record.get(field).to_string())
+                .collect();
+            return format!("Custom({})", args.join(", "));
+        };
+
+        let args: Vec<String> = signature
+            .args
+            .iter()
+            .map(|&(kind, field)| self.arg(kind, record.get(field)))
+            .collect();
+        let call = format!("{}({})", signature.name, args.join(", "));
+
+        if record.get(Field::Flags) & FLAG_DISABLED != 0 {
+            format!("Disabled({call})")
+        } else {
+            call
+        }
+    }
+
+    fn arg(&self, kind: Kind, value: u32) -> String {
+        let named = |names: &[(u32, &str)]| {
+            names
+                .iter()
+                .find(|(x, _)| *x == value)
+                .map_or_else(|| value.to_string(), |(_, name)| name.to_string())
+        };
+
+        match kind {
+            Kind::Player => PLAYERS
+                .get(value as usize)
+                .map_or_else(|| value.to_string(), |x| format!("\"{x}\"")),
+            Kind::Unit => UNITS
+                .get(value as usize)
+                .map_or_else(|| value.to_string(), |x| format!("\"{x}\"")),
+            Kind::Location => self.location(value),
+            Kind::Comparison => named(COMPARISONS),
+            Kind::Modifier => named(MODIFIERS),
+            Kind::Number => value.to_string(),
+            Kind::Resource => named(RESOURCES),
+            Kind::Score => named(SCORES),
+            Kind::Switch => self.switch(value),
+            Kind::SwitchState => named(SWITCH_STATES),
+            Kind::SwitchAction => named(SWITCH_ACTIONS),
+            Kind::State => named(STATES),
+            Kind::String | Kind::Wav => self.string(value),
+            Kind::AiScript => {
+                let script = value.to_le_bytes();
+                if script.iter().all(|x| x.is_ascii_alphanumeric()) {
+                    format!("\"{}\"", String::from_utf8_lossy(&script))
+                } else {
+                    value.to_string()
+                }
+            }
+            Kind::Order => named(ORDERS),
+            Kind::Alliance => named(ALLIANCES),
+            Kind::AlwaysDisplay => ALWAYS_DISPLAY[(value & FLAG_ALWAYS_DISPLAY != 0) as usize]
+                .1
+                .to_owned(),
+            Kind::Count => {
+                if value == 0 {
+                    "All".to_owned()
+                } else {
+                    value.to_string()
+                }
+            }
+        }
+    }
+
+    /// Locations are numbered from 1. Unnamed ones are written as `"Location N"`.
+    fn location(&self, id: u32) -> String {
+        if id == 0 || id as usize > self.locations.len() {
+            return id.to_string();
+        }
+
+        let names: Vec<_> = self
+            .locations
+            .iter()
+            .map(|x| self.named_string(x.name as u32))
+            .collect();
+        self.name_or_number(&names, id as usize - 1, &format!("Location {id}"))
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Switches are numbered from 0 but written from 1, like the editors do.
+    fn switch(&self, id: u32) -> String {
+        if id >= 256 {
+            return id.to_string();
+        }
+
+        let mut names: Vec<_> = self
+            .switch_names
+            .iter()
+            .map(|&x| self.named_string(x))
+            .collect();
+        names.resize(256, None);
+        self.name_or_number(&names, id as usize, &format!("Switch {}", id + 1))
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// The quoted name of entry `index`, or `default` if it has none. `None` if the compiler would
+    /// find another entry by that name.
+    fn name_or_number(
+        &self,
+        names: &[Option<&[u8]>],
+        index: usize,
+        default: &str,
+    ) -> Option<String> {
+        let name = names[index].unwrap_or(default.as_bytes());
+        let first = names.iter().position(|&x| x == Some(name));
+        match (names[index], first) {
+            (Some(_), Some(first)) if first == index && self.quotable(name) => {
+                Some(quote(name, self.encoding))
+            }
+            (None, None) => Some(format!("\"{default}\"")),
+            _ => None,
+        }
+    }
+
+    /// String 0 means no string and is written as `""`.
+    fn string(&self, id: u32) -> String {
+        if id == 0 {
+            return "\"\"".to_owned();
+        }
+
+        match self.named_string(id) {
+            Some(x) if self.string_ids.get(x) == Some(&id) && self.quotable(x) => {
+                quote(x, self.encoding)
+            }
+            _ => id.to_string(),
+        }
+    }
+
+    fn named_string(&self, id: u32) -> Option<&'a [u8]> {
+        self.chk.string(id).filter(|x| !x.is_empty())
+    }
+
+    /// Returns true if the compiler gets `bytes` back from the quoted text.
+    fn quotable(&self, bytes: &[u8]) -> bool {
+        self.encoding.encode(&self.encoding.decode(bytes)) == bytes
+    }
+}
+
+/// The records up to the last one with anything in it. StarCraft stops at the first one without
+/// an opcode, but what comes after is kept too.
+fn in_use<'r, T: Fields>(records: &'r [T], raw_fields: &[Field]) -> &'r [T] {
+    let end = records
+        .iter()
+        .rposition(|x| raw_fields.iter().any(|&field| x.get(field) != 0))
+        .map_or(0, |x| x + 1);
+    &records[..end]
+}
+
+/// Returns true if `signature` and the disabled flag cover everything in `record`.
+fn fits(record: &impl Fields, signature: &Signature, raw_fields: &[Field]) -> bool {
+    let mut flags = FLAG_DISABLED;
+    if signature
+        .args
+        .iter()
+        .any(|&(kind, _)| kind == Kind::AlwaysDisplay)
+    {
+        flags |= FLAG_ALWAYS_DISPLAY;
+    }
+
+    raw_fields.iter().all(|&field| match field {
+        Field::Opcode => true,
+        Field::Flags => record.get(field) & !flags == 0,
+        _ => record.get(field) == 0 || signature.args.iter().any(|&(_, x)| x == field),
+    })
+}
+
+/// Decompiles the TRIG section of a raw CHK, such as the one `get_chk_from_mpq_in_memory` returns.
+pub fn decompile_triggers(chk: &[u8]) -> String {
+    Decompiler::new(&Chk::parse(chk)).triggers()
+}
+
+/// Decompiles the MBRF section of a raw CHK.
+pub fn decompile_briefing(chk: &[u8]) -> String {
+    Decompiler::new(&Chk::parse(chk)).briefing()
+}
@@ -0,0 +1,47 @@
+use crate::chk::Encoding;
+
+/// Writes raw map string bytes as a quoted TrigEdit string. Line breaks become `\n`, other
+/// control bytes `<XX>`, and `\`, `"` and anything that would read as `<XX>` are escaped.
+pub(crate) fn quote(bytes: &[u8], encoding: Encoding) -> String {
+    let mut out = String::from("\"");
+
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if byte >= 0x20 {
+            continue;
+        }
+
+        push_text(&mut out, &encoding.decode(&bytes[start..i]));
+        if byte == b'\n' {
+            out.push_str("\\n");
+        } else {
+            out.push_str(&format!("<{byte:02X}>"));
+        }
+        start = i + 1;
+    }
+    push_text(&mut out, &encoding.decode(&bytes[start..]));
+
+    out.push('"');
+    out
+}
+
+fn push_text(out: &mut String, text: &str) {
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '<' if is_hex_escape(&chars[i..]) => out.push_str("<3C>"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Returns true if `chars` starts with `<XX>`.
+pub(crate) fn is_hex_escape(chars: &[char]) -> bool {
+    chars.len() >= 4
+        && chars[0] == '<'
+        && chars[1].is_ascii_hexdigit()
+        && chars[2].is_ascii_hexdigit()
+        && chars[3] == '>'
+}
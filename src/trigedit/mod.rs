@@ -0,0 +1,13 @@
+//! Conversion between TRIG/MBRF records and the TrigEdit text syntax used by SCMDraft.
+
+mod compile;
+mod decompile;
+mod escape;
+pub(crate) mod tables;
+
+pub use compile::compile_briefing;
+pub use compile::compile_triggers;
+pub use compile::CompileError;
+pub use decompile::decompile_briefing;
+pub use decompile::decompile_triggers;
+pub use decompile::Decompiler;
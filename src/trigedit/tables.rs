@@ -0,0 +1,587 @@
+//! Opcodes, argument layouts and names shared by the decompiler and the compiler.
+
+use crate::chk::Action;
+use crate::chk::Condition;
+
+/// A field of a condition or action record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Field {
+    Location,
+    String,
+    Wav,
+    Time,
+    Group,
+    Number,
+    UnitType,
+    Comparison,
+    Modifier,
+    ResourceType,
+    Quantity,
+    Flags,
+    /// The condition or action opcode.
+    Opcode,
+    /// The unused byte after an action's flags.
+    Padding,
+    /// The bit mask used by extended ("EUD") conditions and actions.
+    Mask,
+}
+
+impl Field {
+    /// The largest value the field can hold.
+    pub(crate) fn max(self) -> u32 {
+        match self {
+            Field::UnitType | Field::Mask => u16::MAX as u32,
+            Field::Comparison
+            | Field::Modifier
+            | Field::ResourceType
+            | Field::Flags
+            | Field::Opcode
+            | Field::Padding => u8::MAX as u32,
+            _ => u32::MAX,
+        }
+    }
+}
+
+/// How an argument is written in TrigEdit text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Player,
+    Unit,
+    Location,
+    Comparison,
+    Modifier,
+    Number,
+    Resource,
+    Score,
+    Switch,
+    SwitchState,
+    SwitchAction,
+    State,
+    String,
+    Wav,
+    AiScript,
+    Order,
+    Alliance,
+    /// The "always display" bit of the action flags.
+    AlwaysDisplay,
+    /// A unit count, where 0 means all units.
+    Count,
+}
+
+pub(crate) struct Signature {
+    pub(crate) opcode: u8,
+    pub(crate) name: &'static str,
+    pub(crate) args: &'static [(Kind, Field)],
+}
+
+const fn sig(opcode: u8, name: &'static str, args: &'static [(Kind, Field)]) -> Signature {
+    Signature { opcode, name, args }
+}
+
+use Field as F;
+use Kind as K;
+
+#[rustfmt::skip]
+pub(crate) const CONDITIONS: &[Signature] = &[
+    sig(1, "Countdown Timer", &[(K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(2, "Command", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(3, "Bring", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Location, F::Location), (K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(4, "Accumulate", &[(K::Player, F::Group), (K::Comparison, F::Comparison), (K::Number, F::Quantity), (K::Resource, F::ResourceType)]),
+    sig(5, "Kill", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(6, "Command the Most", &[(K::Unit, F::UnitType)]),
+    sig(7, "Commands the Most At", &[(K::Unit, F::UnitType), (K::Location, F::Location)]),
+    sig(8, "Most Kills", &[(K::Unit, F::UnitType)]),
+    sig(9, "Highest Score", &[(K::Score, F::ResourceType)]),
+    sig(10, "Most Resources", &[(K::Resource, F::ResourceType)]),
+    sig(11, "Switch", &[(K::Switch, F::ResourceType), (K::SwitchState, F::Comparison)]),
+    sig(12, "Elapsed Time", &[(K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(13, "Mission Briefing", &[]),
+    sig(14, "Opponents", &[(K::Player, F::Group), (K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(15, "Deaths", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(16, "Command the Least", &[(K::Unit, F::UnitType)]),
+    sig(17, "Command the Least At", &[(K::Unit, F::UnitType), (K::Location, F::Location)]),
+    sig(18, "Least Kills", &[(K::Unit, F::UnitType)]),
+    sig(19, "Lowest Score", &[(K::Score, F::ResourceType)]),
+    sig(20, "Least Resources", &[(K::Resource, F::ResourceType)]),
+    sig(21, "Score", &[(K::Player, F::Group), (K::Score, F::ResourceType), (K::Comparison, F::Comparison), (K::Number, F::Quantity)]),
+    sig(22, "Always", &[]),
+    sig(23, "Never", &[]),
+];
+
+#[rustfmt::skip]
+pub(crate) const ACTIONS: &[Signature] = &[
+    sig(1, "Victory", &[]),
+    sig(2, "Defeat", &[]),
+    sig(3, "Preserve Trigger", &[]),
+    sig(4, "Wait", &[(K::Number, F::Time)]),
+    sig(5, "Pause Game", &[]),
+    sig(6, "Unpause Game", &[]),
+    sig(7, "Transmission", &[(K::AlwaysDisplay, F::Flags), (K::String, F::String), (K::Unit, F::UnitType), (K::Location, F::Location), (K::Modifier, F::Modifier), (K::Number, F::Number), (K::Wav, F::Wav), (K::Number, F::Time)]),
+    sig(8, "Play WAV", &[(K::Wav, F::Wav), (K::Number, F::Time)]),
+    sig(9, "Display Text Message", &[(K::AlwaysDisplay, F::Flags), (K::String, F::String)]),
+    sig(10, "Center View", &[(K::Location, F::Location)]),
+    sig(11, "Create Unit with Properties", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Number, F::Modifier), (K::Location, F::Location), (K::Number, F::Number)]),
+    sig(12, "Set Mission Objectives", &[(K::String, F::String)]),
+    sig(13, "Set Switch", &[(K::Switch, F::Number), (K::SwitchAction, F::Modifier)]),
+    sig(14, "Set Countdown Timer", &[(K::Modifier, F::Modifier), (K::Number, F::Time)]),
+    sig(15, "Run AI Script", &[(K::AiScript, F::Number)]),
+    sig(16, "Run AI Script At Location", &[(K::AiScript, F::Number), (K::Location, F::Location)]),
+    sig(17, "Leader Board Control", &[(K::String, F::String), (K::Unit, F::UnitType)]),
+    sig(18, "Leader Board Control At Location", &[(K::String, F::String), (K::Unit, F::UnitType), (K::Location, F::Location)]),
+    sig(19, "Leader Board Resources", &[(K::String, F::String), (K::Resource, F::UnitType)]),
+    sig(20, "Leader Board Kills", &[(K::String, F::String), (K::Unit, F::UnitType)]),
+    sig(21, "Leader Board Points", &[(K::String, F::String), (K::Score, F::UnitType)]),
+    sig(22, "Kill Unit", &[(K::Player, F::Group), (K::Unit, F::UnitType)]),
+    sig(23, "Kill Unit At Location", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(24, "Remove Unit", &[(K::Player, F::Group), (K::Unit, F::UnitType)]),
+    sig(25, "Remove Unit At Location", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(26, "Set Resources", &[(K::Player, F::Group), (K::Modifier, F::Modifier), (K::Number, F::Number), (K::Resource, F::UnitType)]),
+    sig(27, "Set Score", &[(K::Player, F::Group), (K::Modifier, F::Modifier), (K::Number, F::Number), (K::Score, F::UnitType)]),
+    sig(28, "Minimap Ping", &[(K::Location, F::Location)]),
+    sig(29, "Talking Portrait", &[(K::Unit, F::UnitType), (K::Number, F::Time)]),
+    sig(30, "Mute Unit Speech", &[]),
+    sig(31, "Unmute Unit Speech", &[]),
+    sig(32, "Leaderboard Computer Players", &[(K::State, F::Modifier)]),
+    sig(33, "Leaderboard Goal Control", &[(K::String, F::String), (K::Unit, F::UnitType), (K::Number, F::Number)]),
+    sig(34, "Leaderboard Goal Control At Location", &[(K::String, F::String), (K::Unit, F::UnitType), (K::Number, F::Number), (K::Location, F::Location)]),
+    sig(35, "Leaderboard Goal Resources", &[(K::String, F::String), (K::Number, F::Number), (K::Resource, F::UnitType)]),
+    sig(36, "Leaderboard Goal Kills", &[(K::String, F::String), (K::Unit, F::UnitType), (K::Number, F::Number)]),
+    sig(37, "Leaderboard Goal Points", &[(K::String, F::String), (K::Score, F::UnitType), (K::Number, F::Number)]),
+    sig(38, "Move Location", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Location, F::Location), (K::Location, F::Number)]),
+    sig(39, "Move Unit", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Count, F::Modifier), (K::Location, F::Location), (K::Location, F::Number)]),
+    sig(40, "Leaderboard Greed", &[(K::Number, F::Number)]),
+    sig(41, "Set Next Scenario", &[(K::String, F::String)]),
+    sig(42, "Set Doodad State", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Location, F::Location), (K::State, F::Modifier)]),
+    sig(43, "Set Invincibility", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Location, F::Location), (K::State, F::Modifier)]),
+    sig(44, "Create Unit", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Number, F::Modifier), (K::Location, F::Location)]),
+    sig(45, "Set Deaths", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Modifier, F::Modifier), (K::Number, F::Number)]),
+    sig(46, "Order", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Location, F::Location), (K::Location, F::Number), (K::Order, F::Modifier)]),
+    sig(47, "Comment", &[(K::String, F::String)]),
+    sig(48, "Give Units to Player", &[(K::Player, F::Group), (K::Player, F::Number), (K::Unit, F::UnitType), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(49, "Modify Unit Hit Points", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Number, F::Number), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(50, "Modify Unit Energy", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Number, F::Number), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(51, "Modify Unit Shield Points", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Number, F::Number), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(52, "Modify Unit Resource Amount", &[(K::Player, F::Group), (K::Number, F::Number), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(53, "Modify Unit Hanger Count", &[(K::Player, F::Group), (K::Unit, F::UnitType), (K::Number, F::Number), (K::Count, F::Modifier), (K::Location, F::Location)]),
+    sig(54, "Pause Timer", &[]),
+    sig(55, "Unpause Timer", &[]),
+    sig(56, "Draw", &[]),
+    sig(57, "Set Alliance Status", &[(K::Player, F::Group), (K::Alliance, F::UnitType)]),
+    sig(58, "Disable Debug Mode", &[]),
+    sig(59, "Enable Debug Mode", &[]),
+];
+
+/// Mission briefing actions, from MBRF. They share the record layout with trigger actions.
+#[rustfmt::skip]
+pub(crate) const BRIEFING_ACTIONS: &[Signature] = &[
+    sig(1, "Wait", &[(K::Number, F::Time)]),
+    sig(2, "Play WAV", &[(K::Wav, F::Wav), (K::Number, F::Time)]),
+    sig(3, "Text Message", &[(K::String, F::String), (K::Number, F::Time)]),
+    sig(4, "Mission Objectives", &[(K::String, F::String)]),
+    sig(5, "Show Portrait", &[(K::Unit, F::UnitType), (K::Number, F::Group)]),
+    sig(6, "Hide Portrait", &[(K::Number, F::Group)]),
+    sig(7, "Display Speaking Portrait", &[(K::Number, F::Group), (K::Number, F::Time)]),
+    sig(8, "Transmission", &[(K::String, F::String), (K::Number, F::Group), (K::Modifier, F::Modifier), (K::Number, F::Number), (K::Wav, F::Wav), (K::Number, F::Time)]),
+    sig(9, "Skip Tutorial Enabled", &[]),
+];
+
+/// Every field of a condition, in record order, as `Custom(...)` lists them.
+pub(crate) const CONDITION_FIELDS: &[Field] = &[
+    F::Location,
+    F::Group,
+    F::Quantity,
+    F::UnitType,
+    F::Comparison,
+    F::Opcode,
+    F::ResourceType,
+    F::Flags,
+    F::Mask,
+];
+
+/// Every field of an action, in record order, as `Custom(...)` lists them.
+pub(crate) const ACTION_FIELDS: &[Field] = &[
+    F::Location,
+    F::String,
+    F::Wav,
+    F::Time,
+    F::Group,
+    F::Number,
+    F::UnitType,
+    F::Opcode,
+    F::Modifier,
+    F::Flags,
+    F::Padding,
+    F::Mask,
+];
+
+/// Raw access to the fields of a condition or action by name.
+pub(crate) trait Fields {
+    fn get(&self, field: Field) -> u32;
+    fn set(&mut self, field: Field, value: u32);
+}
+
+impl Fields for Condition {
+    fn get(&self, field: Field) -> u32 {
+        match field {
+            F::Location => self.location,
+            F::Group => self.group,
+            F::Quantity => self.quantity,
+            F::UnitType => self.unit_type as u32,
+            F::Comparison => self.comparison as u32,
+            F::Opcode => self.condition as u32,
+            F::ResourceType => self.resource_type as u32,
+            F::Flags => self.flags as u32,
+            F::Mask => self.mask as u32,
+            _ => 0,
+        }
+    }
+
+    fn set(&mut self, field: Field, value: u32) {
+        match field {
+            F::Location => self.location = value,
+            F::Group => self.group = value,
+            F::Quantity => self.quantity = value,
+            F::UnitType => self.unit_type = value as u16,
+            F::Comparison => self.comparison = value as u8,
+            F::Opcode => self.condition = value as u8,
+            F::ResourceType => self.resource_type = value as u8,
+            F::Flags => self.flags = value as u8,
+            F::Mask => self.mask = value as u16,
+            _ => {}
+        }
+    }
+}
+
+impl Fields for Action {
+    fn get(&self, field: Field) -> u32 {
+        match field {
+            F::Location => self.location,
+            F::String => self.string,
+            F::Wav => self.wav,
+            F::Time => self.time,
+            F::Group => self.group,
+            F::Number => self.number,
+            F::UnitType => self.unit_type as u32,
+            F::Opcode => self.action as u32,
+            F::Modifier => self.modifier as u32,
+            F::Flags => self.flags as u32,
+            F::Padding => self.padding as u32,
+            F::Mask => self.mask as u32,
+            _ => 0,
+        }
+    }
+
+    fn set(&mut self, field: Field, value: u32) {
+        match field {
+            F::Location => self.location = value,
+            F::String => self.string = value,
+            F::Wav => self.wav = value,
+            F::Time => self.time = value,
+            F::Group => self.group = value,
+            F::Number => self.number = value,
+            F::UnitType => self.unit_type = value as u16,
+            F::Opcode => self.action = value as u8,
+            F::Modifier => self.modifier = value as u8,
+            F::Flags => self.flags = value as u8,
+            F::Padding => self.padding = value as u8,
+            F::Mask => self.mask = value as u16,
+            _ => {}
+        }
+    }
+}
+
+/// Condition and action flag bits.
+pub(crate) const FLAG_DISABLED: u32 = 0x02;
+pub(crate) const FLAG_ALWAYS_DISPLAY: u32 = 0x04;
+
+pub(crate) const PLAYERS: &[&str] = &[
+    "Player 1",
+    "Player 2",
+    "Player 3",
+    "Player 4",
+    "Player 5",
+    "Player 6",
+    "Player 7",
+    "Player 8",
+    "Player 9",
+    "Player 10",
+    "Player 11",
+    "Player 12",
+    "None",
+    "Current Player",
+    "Foes",
+    "Allies",
+    "Neutral Players",
+    "All players",
+    "Force 1",
+    "Force 2",
+    "Force 3",
+    "Force 4",
+    "Unused 1",
+    "Unused 2",
+    "Unused 3",
+    "Unused 4",
+    "Non Allied Victory Players",
+];
+
+/// Values of the small enumerations, as `(value, name)`.
+pub(crate) const COMPARISONS: &[(u32, &str)] = &[(0, "At least"), (1, "At most"), (10, "Exactly")];
+pub(crate) const MODIFIERS: &[(u32, &str)] = &[(7, "Set To"), (8, "Add"), (9, "Subtract")];
+pub(crate) const RESOURCES: &[(u32, &str)] = &[(0, "ore"), (1, "gas"), (2, "ore and gas")];
+pub(crate) const SCORES: &[(u32, &str)] = &[
+    (0, "Total"),
+    (1, "Units"),
+    (2, "Buildings"),
+    (3, "Units and buildings"),
+    (4, "Kills"),
+    (5, "Razings"),
+    (6, "Kills and razings"),
+    (7, "Custom"),
+];
+pub(crate) const SWITCH_STATES: &[(u32, &str)] = &[(2, "set"), (3, "not set")];
+pub(crate) const SWITCH_ACTIONS: &[(u32, &str)] =
+    &[(4, "set"), (5, "clear"), (6, "toggle"), (11, "randomize")];
+pub(crate) const STATES: &[(u32, &str)] = &[(4, "enable"), (5, "disable"), (6, "toggle")];
+pub(crate) const ORDERS: &[(u32, &str)] = &[(0, "move"), (1, "patrol"), (2, "attack")];
+pub(crate) const ALLIANCES: &[(u32, &str)] = &[(0, "Enemy"), (1, "Ally"), (2, "Allied Victory")];
+pub(crate) const ALWAYS_DISPLAY: &[(u32, &str)] = &[
+    (0, "Don't Always Display"),
+    (FLAG_ALWAYS_DISPLAY, "Always Display"),
+];
+
+/// SCMDraft's default unit names. Names that SCMDraft repeats are made unique so they survive a round trip.
+pub(crate) const UNITS: &[&str] = &[
+    "Terran Marine",
+    "Terran Ghost",
+    "Terran Vulture",
+    "Terran Goliath",
+    "Goliath Turret",
+    "Terran Siege Tank (Tank Mode)",
+    "Tank Turret type 1",
+    "Terran SCV",
+    "Terran Wraith",
+    "Terran Science Vessel",
+    "Gui Montag (Firebat)",
+    "Terran Dropship",
+    "Terran Battlecruiser",
+    "Vulture Spider Mine",
+    "Nuclear Missile",
+    "Terran Civilian",
+    "Sarah Kerrigan (Ghost)",
+    "Alan Schezar (Goliath)",
+    "Alan Schezar Turret",
+    "Jim Raynor (Vulture)",
+    "Jim Raynor (Marine)",
+    "Tom Kazansky (Wraith)",
+    "Magellan (Science Vessel)",
+    "Edmund Duke (Siege Tank)",
+    "Edmund Duke Turret",
+    "Edmund Duke (Siege Mode)",
+    "Edmund Duke Turret type 2",
+    "Arcturus Mengsk (Battlecruiser)",
+    "Hyperion (Battlecruiser)",
+    "Norad II (Battlecruiser)",
+    "Terran Siege Tank (Siege Mode)",
+    "Tank Turret type 2",
+    "Terran Firebat",
+    "Scanner Sweep",
+    "Terran Medic",
+    "Zerg Larva",
+    "Zerg Egg",
+    "Zerg Zergling",
+    "Zerg Hydralisk",
+    "Zerg Ultralisk",
+    "Zerg Broodling",
+    "Zerg Drone",
+    "Zerg Overlord",
+    "Zerg Mutalisk",
+    "Zerg Guardian",
+    "Zerg Queen",
+    "Zerg Defiler",
+    "Zerg Scourge",
+    "Torrasque (Ultralisk)",
+    "Matriarch (Queen)",
+    "Infested Terran",
+    "Infested Kerrigan (Infested Terran)",
+    "Unclean One (Defiler)",
+    "Hunter Killer (Hydralisk)",
+    "Devouring One (Zergling)",
+    "Kukulza (Mutalisk)",
+    "Kukulza (Guardian)",
+    "Yggdrasill (Overlord)",
+    "Terran Valkyrie",
+    "Cocoon",
+    "Protoss Corsair",
+    "Protoss Dark Templar",
+    "Zerg Devourer",
+    "Protoss Dark Archon",
+    "Protoss Probe",
+    "Protoss Zealot",
+    "Protoss Dragoon",
+    "Protoss High Templar",
+    "Protoss Archon",
+    "Protoss Shuttle",
+    "Protoss Scout",
+    "Protoss Arbiter",
+    "Protoss Carrier",
+    "Protoss Interceptor",
+    "Dark Templar (Hero)",
+    "Zeratul (Dark Templar)",
+    "Tassadar/Zeratul (Archon)",
+    "Fenix (Zealot)",
+    "Fenix (Dragoon)",
+    "Tassadar (Templar)",
+    "Mojo (Scout)",
+    "Warbringer (Reaver)",
+    "Gantrithor (Carrier)",
+    "Protoss Reaver",
+    "Protoss Observer",
+    "Protoss Scarab",
+    "Danimoth (Arbiter)",
+    "Aldaris (Templar)",
+    "Artanis (Scout)",
+    "Rhynadon (Badlands Critter)",
+    "Bengalaas (Jungle Critter)",
+    "Unused - Cargo Ship",
+    "Unused - Mercenary Gunship",
+    "Scantid (Desert Critter)",
+    "Kakaru (Twilight Critter)",
+    "Ragnasaur (Ashworld Critter)",
+    "Ursadon (Ice World Critter)",
+    "Lurker Egg",
+    "Raszagal",
+    "Samir Duran (Ghost)",
+    "Alexei Stukov (Ghost)",
+    "Map Revealer",
+    "Gerard DuGalle",
+    "Zerg Lurker",
+    "Infested Duran",
+    "Disruption Web",
+    "Terran Command Center",
+    "Terran Comsat Station",
+    "Terran Nuclear Silo",
+    "Terran Supply Depot",
+    "Terran Refinery",
+    "Terran Barracks",
+    "Terran Academy",
+    "Terran Factory",
+    "Terran Starport",
+    "Terran Control Tower",
+    "Terran Science Facility",
+    "Terran Covert Ops",
+    "Terran Physics Lab",
+    "Unused Terran Bldg type 1",
+    "Terran Machine Shop",
+    "Unused Terran Bldg type 2",
+    "Terran Engineering Bay",
+    "Terran Armory",
+    "Terran Missile Turret",
+    "Terran Bunker",
+    "Norad II (Crashed Battlecruiser)",
+    "Ion Cannon",
+    "Uraj Crystal",
+    "Khalis Crystal",
+    "Infested Command Center",
+    "Zerg Hatchery",
+    "Zerg Lair",
+    "Zerg Hive",
+    "Zerg Nydus Canal",
+    "Zerg Hydralisk Den",
+    "Zerg Defiler Mound",
+    "Zerg Greater Spire",
+    "Zerg Queen's Nest",
+    "Zerg Evolution Chamber",
+    "Zerg Ultralisk Cavern",
+    "Zerg Spire",
+    "Zerg Spawning Pool",
+    "Zerg Creep Colony",
+    "Zerg Spore Colony",
+    "Unused Zerg Bldg",
+    "Zerg Sunken Colony",
+    "Zerg Overmind (With Shell)",
+    "Zerg Overmind",
+    "Zerg Extractor",
+    "Mature Chrysalis",
+    "Zerg Cerebrate",
+    "Zerg Cerebrate Daggoth",
+    "Unused Zerg Bldg 5",
+    "Protoss Nexus",
+    "Protoss Robotics Facility",
+    "Protoss Pylon",
+    "Protoss Assimilator",
+    "Unused Protoss Bldg type 1",
+    "Protoss Observatory",
+    "Protoss Gateway",
+    "Unused Protoss Bldg type 2",
+    "Protoss Photon Cannon",
+    "Protoss Citadel of Adun",
+    "Protoss Cybernetics Core",
+    "Protoss Templar Archives",
+    "Protoss Forge",
+    "Protoss Stargate",
+    "Stasis Cell/Prison",
+    "Protoss Fleet Beacon",
+    "Protoss Arbiter Tribunal",
+    "Protoss Robotics Support Bay",
+    "Protoss Shield Battery",
+    "Khaydarin Crystal Formation",
+    "Protoss Temple",
+    "Xel'Naga Temple",
+    "Mineral Field (Type 1)",
+    "Mineral Field (Type 2)",
+    "Mineral Field (Type 3)",
+    "Cave",
+    "Cave-in",
+    "Cantina",
+    "Mining Platform",
+    "Independent Command Center",
+    "Independent Starport",
+    "Independent Jump Gate",
+    "Ruins",
+    "Khaydarin Crystal Formation (Unused)",
+    "Vespene Geyser",
+    "Warp Gate",
+    "Psi Disrupter",
+    "Zerg Marker",
+    "Terran Marker",
+    "Protoss Marker",
+    "Zerg Beacon",
+    "Terran Beacon",
+    "Protoss Beacon",
+    "Zerg Flag Beacon",
+    "Terran Flag Beacon",
+    "Protoss Flag Beacon",
+    "Power Generator",
+    "Overmind Cocoon",
+    "Dark Swarm",
+    "Floor Missile Trap",
+    "Floor Hatch (UNUSED)",
+    "Left Upper Level Door",
+    "Right Upper Level Door",
+    "Left Pit Door",
+    "Right Pit Door",
+    "Floor Gun Trap",
+    "Left Wall Missile Trap",
+    "Left Wall Flame Trap",
+    "Right Wall Missile Trap",
+    "Right Wall Flame Trap",
+    "Start Location",
+    "Flag",
+    "Young Chrysalis",
+    "Psi Emitter",
+    "Data Disc",
+    "Khaydarin Crystal",
+    "Mineral Chunk (Type 1)",
+    "Mineral Chunk (Type 2)",
+    "Vespene Orb (Protoss Type 1)",
+    "Vespene Orb (Protoss Type 2)",
+    "Vespene Sac (Zerg Type 1)",
+    "Vespene Sac (Zerg Type 2)",
+    "Vespene Tank (Terran Type 1)",
+    "Vespene Tank (Terran Type 2)",
+    "None",
+    "Any unit",
+    "Men",
+    "Buildings",
+    "Factories",
+];
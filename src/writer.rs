@@ -0,0 +1,504 @@
+//! Building new MPQ format v1 archives and replacing files in existing ones.
+//!
+//! Archives are written the way StarCraft's own editor writes them: a 32 byte header, the file
+//! data, then the hash table and the block table.
+
+use crate::compression;
+use crate::crypto::encrypt_block;
+use crate::crypto::file_key;
+use crate::crypto::hash_string;
+use crate::crypto::MPQ_HASH_FILE_KEY;
+use crate::crypto::MPQ_HASH_NAME_A;
+use crate::crypto::MPQ_HASH_NAME_B;
+use crate::entry::MPQ_FILE_COMPRESS;
+use crate::entry::MPQ_FILE_ENCRYPTED;
+use crate::entry::MPQ_FILE_EXISTS;
+use crate::entry::MPQ_FILE_FIX_KEY;
+use crate::entry::MPQ_FILE_IMPLODE;
+use crate::error::Error;
+use crate::error::Result;
+use crate::reader::hash_probe;
+use crate::reader::BlockEntry;
+use crate::reader::HashEntry;
+use crate::reader::MpqReader;
+use crate::reader::ReaderOptions;
+use crate::reader::HASH_ENTRY_FREE;
+use tracing::instrument;
+
+const HEADER_SIZE: u32 = 32;
+
+/// How file data is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    None,
+    /// PKWare implode, which every version of StarCraft can read.
+    #[default]
+    Implode,
+    /// zlib. Smaller, but StarCraft before Remastered can't read it.
+    Zlib,
+}
+
+/// How a single file is stored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileOptions {
+    pub compression: Compression,
+    pub encrypt: bool,
+    /// Adjust the encryption key by the file's position and size, so that identical files get
+    /// different keys. Only meaningful with `encrypt`.
+    pub fix_key: bool,
+    pub locale: u16,
+}
+
+/// Options for a whole new archive.
+#[derive(Debug, Clone, Copy)]
+pub struct WriterOptions {
+    /// The number of hash table entries, which must be a power of two. `None` picks the smallest
+    /// power of two, at least 16, that leaves a quarter of the table free.
+    pub hash_table_size: Option<u32>,
+    /// Sectors are `512 << sector_size_shift` bytes.
+    pub sector_size_shift: u16,
+    /// Add a `(listfile)` naming every file.
+    pub listfile: bool,
+    /// Add an `(attributes)` file with the CRC32 of every file.
+    pub attributes: bool,
+}
+
+impl Default for WriterOptions {
+    /// The layout StarCraft's editor uses: 4096 byte sectors, with a listfile and attributes.
+    fn default() -> WriterOptions {
+        WriterOptions {
+            hash_table_size: None,
+            sector_size_shift: 3,
+            listfile: true,
+            attributes: true,
+        }
+    }
+}
+
+enum Contents {
+    Data(Vec<u8>),
+    /// Bytes that are already compressed, with the file size and block flags that go with them.
+    Stored {
+        bytes: Vec<u8>,
+        file_size: u32,
+        flags: u32,
+    },
+}
+
+struct NewFile {
+    name: String,
+    contents: Contents,
+    options: FileOptions,
+}
+
+/// Collects files in memory and writes them out as a new archive.
+pub struct MpqWriter {
+    options: WriterOptions,
+    files: Vec<NewFile>,
+}
+
+impl MpqWriter {
+    pub fn new(options: WriterOptions) -> MpqWriter {
+        MpqWriter {
+            options,
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds a file, replacing any file added earlier with the same name and locale.
+    pub fn add_file(&mut self, name: &str, data: Vec<u8>, options: FileOptions) {
+        self.add(name, Contents::Data(data), options);
+    }
+
+    /// Adds a file that is already compressed, as returned by `MpqReader::read_raw_hash_entry`.
+    /// The block must not be encrypted, the bytes are stored as they are.
+    pub(crate) fn add_stored_file(
+        &mut self,
+        name: &str,
+        block: BlockEntry,
+        bytes: Vec<u8>,
+        locale: u16,
+    ) {
+        let contents = Contents::Stored {
+            bytes,
+            file_size: block.file_size,
+            flags: block.flags,
+        };
+        let options = FileOptions {
+            locale,
+            ..FileOptions::default()
+        };
+        self.add(name, contents, options);
+    }
+
+    fn add(&mut self, name: &str, contents: Contents, options: FileOptions) {
+        self.files
+            .retain(|x| !(x.name.eq_ignore_ascii_case(name) && x.options.locale == options.locale));
+        self.files.push(NewFile {
+            name: name.to_owned(),
+            contents,
+            options,
+        });
+    }
+
+    /// Writes the archive. Files are stored in the order they were added, followed by the
+    /// listfile and attributes.
+    #[instrument(level = "trace", skip_all)]
+    pub fn finish(&self) -> Result<Vec<u8>> {
+        if self.options.sector_size_shift > 22 {
+            return Err(Error::Unsupported(format!(
+                "Sector size shift: {}",
+                self.options.sector_size_shift
+            )));
+        }
+        let sector_size = 512usize << self.options.sector_size_shift;
+
+        let listfile;
+        let attributes;
+        let mut files: Vec<(&str, &Contents, FileOptions)> = self
+            .files
+            .iter()
+            .filter(|x| !is_special(&x.name))
+            .map(|x| (x.name.as_str(), &x.contents, x.options))
+            .collect();
+
+        if self.options.listfile {
+            let mut names: Vec<&str> = Vec::new();
+            for (name, _, _) in &files {
+                if !names.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+                    names.push(name);
+                }
+            }
+
+            listfile = Contents::Data(
+                names
+                    .iter()
+                    .map(|x| format!("{x}\r\n"))
+                    .collect::<String>()
+                    .into_bytes(),
+            );
+            files.push(("(listfile)", &listfile, FileOptions::default()));
+        }
+
+        if self.options.attributes {
+            // Version 100 with CRC32s only. The attributes file's own CRC32 is left as 0, and so
+            // are those of stored files, which aren't decompressed.
+            let mut data = Vec::new();
+            data.extend(100u32.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+            for (_, contents, _) in &files {
+                let crc = match contents {
+                    Contents::Data(data) => {
+                        let mut crc = flate2::Crc::new();
+                        crc.update(data);
+                        crc.sum()
+                    }
+                    Contents::Stored { .. } => 0,
+                };
+                data.extend(crc.to_le_bytes());
+            }
+            data.extend(0u32.to_le_bytes());
+            attributes = Contents::Data(data);
+            files.push(("(attributes)", &attributes, FileOptions::default()));
+        }
+
+        let hash_table_size = match self.options.hash_table_size {
+            Some(size) if !size.is_power_of_two() => {
+                return Err(Error::Unsupported(format!(
+                    "Hash table size is not a power of two: {size}"
+                )));
+            }
+            Some(size) if (size as usize) < files.len() => {
+                return Err(Error::Unsupported(format!(
+                    "Hash table size {size} is too small for {} files",
+                    files.len()
+                )));
+            }
+            Some(size) => size,
+            None => ((files.len() * 4).div_ceil(3) as u32)
+                .next_power_of_two()
+                .max(16),
+        };
+
+        let mut out = vec![0u8; HEADER_SIZE as usize];
+        let mut hash_table = vec![free_hash_entry(); hash_table_size as usize];
+        let mut block_table = Vec::with_capacity(files.len());
+
+        for (block_index, (name, contents, options)) in files.iter().enumerate() {
+            let offset = out.len() as u32;
+            let (stored, file_size, flags) = match contents {
+                Contents::Data(data) => {
+                    let (stored, flags) = encode_file(name, data, *options, offset, sector_size);
+                    (stored, data.len() as u32, flags)
+                }
+                Contents::Stored {
+                    bytes,
+                    file_size,
+                    flags,
+                } => (bytes.clone(), *file_size, *flags),
+            };
+
+            let hash = insert_hash_entry(&mut hash_table, name, options.locale).unwrap();
+            hash.block_index = block_index as u32;
+            block_table.push(BlockEntry {
+                offset,
+                compressed_size: stored.len() as u32,
+                file_size,
+                flags,
+            });
+            out.extend(stored);
+        }
+
+        let hash_table_pos = out.len() as u32;
+        out.extend(hash_table_bytes(&hash_table));
+        let block_table_pos = out.len() as u32;
+        out.extend(block_table_bytes(&block_table));
+
+        let header = header_bytes(
+            out.len() as u32,
+            self.options.sector_size_shift,
+            hash_table_pos,
+            block_table_pos,
+            hash_table_size,
+            block_table.len() as u32,
+        );
+        out[..HEADER_SIZE as usize].copy_from_slice(&header);
+
+        Ok(out)
+    }
+}
+
+/// Replaces every copy of `filename` in an existing archive and returns the new archive.
+///
+/// Everything else is kept byte for byte, including files that aren't in the listfile: the new
+/// data and new tables are appended and the header is pointed at them. Every hash table entry
+/// for the name, whatever its locale, is pointed at the new data, so decoys at other locales
+/// no longer matter. `(attributes)` is not updated.
+#[instrument(level = "trace", skip_all)]
+pub fn replace_file(
+    mpq: &[u8],
+    filename: &str,
+    data: &[u8],
+    options: FileOptions,
+) -> Result<Vec<u8>> {
+    let reader = MpqReader::from_bytes(mpq, ReaderOptions::map())?;
+    let header = reader.header;
+
+    if (reader.hash_table.len() as u64) < header.hash_table_size as u64 {
+        return Err(Error::Unsupported(
+            "Hash table runs past the end of the archive".to_owned(),
+        ));
+    }
+    if header.sector_size_shift > 22 {
+        return Err(Error::Corrupt(format!(
+            "Invalid sector size shift: {}",
+            header.sector_size_shift
+        )));
+    }
+
+    let hash_indexes: Vec<usize> = reader.hash_chain(filename).collect();
+    if hash_indexes.is_empty() {
+        return Err(Error::FileNotFound(filename.to_owned()));
+    }
+
+    let archive_offset = reader.archive_offset as usize;
+    let mut out = mpq.to_vec();
+
+    let offset = u32::try_from(out.len() - archive_offset)
+        .map_err(|_| Error::Unsupported("Archive is larger than 4GB".to_owned()))?;
+    let sector_size = 512usize << header.sector_size_shift;
+    let (stored, flags) = encode_file(filename, data, options, offset, sector_size);
+    out.extend(stored.iter());
+
+    let mut block_table = reader.block_table.clone();
+    let block_index = block_table.len() as u32;
+    block_table.push(BlockEntry {
+        offset,
+        compressed_size: stored.len() as u32,
+        file_size: data.len() as u32,
+        flags,
+    });
+
+    let mut hash_table = reader.hash_table.clone();
+    hash_table.truncate(header.hash_table_size as usize);
+    for hash_index in hash_indexes {
+        hash_table[hash_index].block_index = block_index;
+    }
+
+    let hash_table_pos = (out.len() - archive_offset) as u32;
+    out.extend(hash_table_bytes(&hash_table));
+    let block_table_pos = (out.len() - archive_offset) as u32;
+    out.extend(block_table_bytes(&block_table));
+
+    // Only the fields that changed are rewritten, a bogus header size or format version stays.
+    let new_header = header_bytes(
+        (out.len() - archive_offset) as u32,
+        header.sector_size_shift,
+        hash_table_pos,
+        block_table_pos,
+        header.hash_table_size,
+        block_table.len() as u32,
+    );
+    out[archive_offset + 8..archive_offset + 12].copy_from_slice(&new_header[8..12]);
+    out[archive_offset + 16..archive_offset + 32].copy_from_slice(&new_header[16..32]);
+
+    Ok(out)
+}
+
+/// The listfile and attributes are generated, never taken from the caller.
+fn is_special(name: &str) -> bool {
+    name.eq_ignore_ascii_case("(listfile)") || name.eq_ignore_ascii_case("(attributes)")
+}
+
+/// Compresses and encrypts one file that will be stored at archive offset `offset`. Returns the
+/// stored bytes and the block flags.
+fn encode_file(
+    name: &str,
+    data: &[u8],
+    options: FileOptions,
+    offset: u32,
+    sector_size: usize,
+) -> (Vec<u8>, u32) {
+    let mut flags = MPQ_FILE_EXISTS;
+    let key = if options.encrypt {
+        flags |= MPQ_FILE_ENCRYPTED;
+        if options.fix_key {
+            flags |= MPQ_FILE_FIX_KEY;
+        }
+        file_key(name.as_bytes(), offset, data.len() as u32, options.fix_key)
+    } else {
+        0
+    };
+
+    let encrypt = |sector: &mut [u8], key: u32| {
+        if options.encrypt {
+            encrypt_block(sector, key);
+        }
+    };
+
+    let compress: fn(&[u8]) -> Vec<u8> = match options.compression {
+        Compression::Implode if !data.is_empty() => {
+            flags |= MPQ_FILE_IMPLODE;
+            compression::implode
+        }
+        Compression::Zlib if !data.is_empty() => {
+            flags |= MPQ_FILE_COMPRESS;
+            compression::compress_zlib
+        }
+        _ => {
+            let mut stored = data.to_vec();
+            for (i, sector) in stored.chunks_mut(sector_size).enumerate() {
+                encrypt(sector, key.wrapping_add(i as u32));
+            }
+            return (stored, flags);
+        }
+    };
+
+    // Compressed files start with a table of sector offsets, relative to the start of the file.
+    let sector_count = data.len().div_ceil(sector_size);
+    let mut offsets = vec![((sector_count + 1) * 4) as u32];
+    let mut sectors = Vec::new();
+    for (i, sector) in data.chunks(sector_size).enumerate() {
+        // A sector that doesn't get smaller is stored as is, readers tell by its size.
+        let compressed = compress(sector);
+        let mut stored = if compressed.len() < sector.len() {
+            compressed
+        } else {
+            sector.to_vec()
+        };
+
+        encrypt(&mut stored, key.wrapping_add(i as u32));
+        sectors.extend(stored);
+        offsets.push(offsets[0] + sectors.len() as u32);
+    }
+
+    let mut stored: Vec<u8> = offsets.iter().flat_map(|x| x.to_le_bytes()).collect();
+    encrypt(&mut stored, key.wrapping_sub(1));
+    stored.extend(sectors);
+
+    (stored, flags)
+}
+
+fn free_hash_entry() -> HashEntry {
+    HashEntry {
+        name1: HASH_ENTRY_FREE,
+        name2: HASH_ENTRY_FREE,
+        locale: 0xFFFF,
+        platform: 0xFF,
+        block_index: HASH_ENTRY_FREE,
+    }
+}
+
+/// Claims the first free entry in `name`'s hash chain.
+fn insert_hash_entry<'a>(
+    hash_table: &'a mut [HashEntry],
+    name: &str,
+    locale: u16,
+) -> Option<&'a mut HashEntry> {
+    let index = hash_probe(name, hash_table.len())
+        .find(|&i| hash_table[i].block_index == HASH_ENTRY_FREE)?;
+
+    let entry = &mut hash_table[index];
+    *entry = HashEntry {
+        name1: hash_string(name.as_bytes(), MPQ_HASH_NAME_A),
+        name2: hash_string(name.as_bytes(), MPQ_HASH_NAME_B),
+        locale,
+        platform: 0,
+        block_index: 0,
+    };
+    Some(entry)
+}
+
+fn hash_table_bytes(hash_table: &[HashEntry]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(hash_table.len() * 16);
+    for entry in hash_table {
+        out.extend(entry.name1.to_le_bytes());
+        out.extend(entry.name2.to_le_bytes());
+        out.extend(entry.locale.to_le_bytes());
+        // The second byte is reserved. Storm fills free entries with 0xFF throughout.
+        let reserved = if entry.block_index == HASH_ENTRY_FREE {
+            0xFF
+        } else {
+            0
+        };
+        out.extend([entry.platform, reserved]);
+        out.extend(entry.block_index.to_le_bytes());
+    }
+
+    encrypt_block(&mut out, hash_string(b"(hash table)", MPQ_HASH_FILE_KEY));
+    out
+}
+
+fn block_table_bytes(block_table: &[BlockEntry]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(block_table.len() * 16);
+    for entry in block_table {
+        out.extend(entry.offset.to_le_bytes());
+        out.extend(entry.compressed_size.to_le_bytes());
+        out.extend(entry.file_size.to_le_bytes());
+        out.extend(entry.flags.to_le_bytes());
+    }
+
+    encrypt_block(&mut out, hash_string(b"(block table)", MPQ_HASH_FILE_KEY));
+    out
+}
+
+fn header_bytes(
+    archive_size: u32,
+    sector_size_shift: u16,
+    hash_table_pos: u32,
+    block_table_pos: u32,
+    hash_table_size: u32,
+    block_table_size: u32,
+) -> [u8; HEADER_SIZE as usize] {
+    let mut header = [0u8; HEADER_SIZE as usize];
+    header[0..4].copy_from_slice(b"MPQ\x1A");
+    header[4..8].copy_from_slice(&HEADER_SIZE.to_le_bytes());
+    header[8..12].copy_from_slice(&archive_size.to_le_bytes());
+    header[12..14].copy_from_slice(&0u16.to_le_bytes());
+    header[14..16].copy_from_slice(&sector_size_shift.to_le_bytes());
+    header[16..20].copy_from_slice(&hash_table_pos.to_le_bytes());
+    header[20..24].copy_from_slice(&block_table_pos.to_le_bytes());
+    header[24..28].copy_from_slice(&hash_table_size.to_le_bytes());
+    header[28..32].copy_from_slice(&block_table_size.to_le_bytes());
+    header
+}